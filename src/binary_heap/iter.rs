@@ -0,0 +1,62 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::iter::FusedIterator;
+
+use super::ChunkedBinaryHeap;
+use crate::storage::{DefaultStorage, StorageBackend};
+
+/// An owning iterator which removes the elements of a stored heap in descending order.
+///
+/// Created by [`ChunkedBinaryHeap::into_sorted_iter`].
+#[derive(Debug)]
+pub struct IntoSortedIter<T, const N: usize = 5, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    /// Heap to pop the elements from
+    heap: ChunkedBinaryHeap<T, N, B>,
+}
+
+impl<T, const N: usize, B> IntoSortedIter<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    pub(super) fn new(heap: ChunkedBinaryHeap<T, N, B>) -> Self {
+        Self { heap }
+    }
+}
+
+impl<T, const N: usize, B> Iterator for IntoSortedIter<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.heap.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.heap.len() as usize;
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.heap.len() as usize
+    }
+}
+
+impl<T, const N: usize, B> ExactSizeIterator for IntoSortedIter<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+}
+impl<T, const N: usize, B> FusedIterator for IntoSortedIter<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+}
@@ -0,0 +1,647 @@
+//! A priority queue implemented with a chunk-aligned d-ary heap, persisted to storage in chunks
+//! and lazily loaded.
+//!
+//! Values in the [`ChunkedBinaryHeap`] are kept in an in-memory cache and are only persisted on
+//! [`Drop`].
+//!
+//! # Examples
+//!
+//! ```
+//! use near_chunked_collections::ChunkedBinaryHeap;
+//!
+//! let mut heap: ChunkedBinaryHeap<u32> = ChunkedBinaryHeap::new(b"h");
+//! heap.push(3);
+//! heap.push(7);
+//! heap.push(5);
+//!
+//! assert_eq!(heap.peek(), Some(&7));
+//! assert_eq!(heap.pop(), Some(7));
+//! assert_eq!(heap.into_sorted_iter().collect::<Vec<_>>(), &[5, 3]);
+//! ```
+
+mod iter;
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub use self::iter::IntoSortedIter;
+
+use crate::chunk::{chunk_index, chunk_pos, Chunk};
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+use crate::utils::{expect_consistent_state, panic_str};
+
+const ERR_INDEX_OUT_OF_BOUNDS: &str = "Index out of bounds";
+
+/// Returns the chunk index and position within the chunk of the element at `index`. The root is
+/// the only element of the first chunk, and every following chunk holds `N` elements.
+fn locate<const N: usize>(index: u32) -> (u32, usize) {
+    match index.checked_sub(1) {
+        Some(i) => (chunk_index::<N>(i) + 1, chunk_pos::<N>(i)),
+        None => (0, 0),
+    }
+}
+
+/// Index of the parent of the element at `index`, which must not be the root.
+fn parent<const N: usize>(index: u32) -> u32 {
+    (index - 1) / N as u32
+}
+
+/// A max-heap priority queue, which stores its content on the trie. This implementation will
+/// load and store values in the underlying storage lazily.
+///
+/// Uses the following map: chunk index -> chunk of up to `N` elements, which is the same chunk
+/// format as [`ChunkedVector`](crate::ChunkedVector). The heap is `N`-ary and its elements are
+/// laid out so that the children of the element at index `i` fill exactly the chunk `i + 1`,
+/// with the root stored alone in the first chunk. Every level of a sift therefore only touches
+/// the chunk of the parent and the single chunk holding all of its children, and a heap of
+/// `len` elements is `log_N(len)` levels deep.
+///
+/// This implementation will cache all changes and loads and only updates values that are changed
+/// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
+/// in storage before the variable is dropped by using [`ChunkedBinaryHeap::flush`].
+///
+/// # Examples
+/// ```
+/// use near_chunked_collections::ChunkedBinaryHeap;
+///
+/// let mut heap: ChunkedBinaryHeap<(u64, String)> = ChunkedBinaryHeap::new(b"h");
+/// heap.push((10, "low".to_string()));
+/// heap.push((50, "high".to_string()));
+///
+/// if let Some(mut top) = heap.peek_mut() {
+///     top.0 = 0;
+/// }
+/// // Lowering the top element moves it down the heap.
+/// assert_eq!(heap.pop(), Some((10, "low".to_string())));
+/// assert_eq!(heap.len(), 1);
+/// ```
+pub struct ChunkedBinaryHeap<T, const N: usize = 5, B = DefaultStorage>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    pub(crate) len: u32,
+    pub(crate) values: IndexMap<Chunk<T, N>, B>,
+}
+
+impl<T, const N: usize> ChunkedBinaryHeap<T, N, DefaultStorage>
+where
+    T: BorshSerialize,
+{
+    /// Create new heap with zero elements. Prefixes storage accesss with the prefix provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedBinaryHeap;
+    ///
+    /// let mut heap: ChunkedBinaryHeap<u8> = ChunkedBinaryHeap::new(b"h");
+    /// ```
+    pub fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self::new_in(prefix, DefaultStorage::default())
+    }
+}
+
+impl<T, const N: usize, B> Drop for ChunkedBinaryHeap<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+impl<T, const N: usize, B> BorshSerialize for ChunkedBinaryHeap<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.len, writer)?;
+        BorshSerialize::serialize(&self.values, writer)?;
+        Ok(())
+    }
+}
+
+impl<T, const N: usize, B> BorshDeserialize for ChunkedBinaryHeap<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend + Default,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            len: BorshDeserialize::deserialize(buf)?,
+            values: BorshDeserialize::deserialize(buf)?,
+        })
+    }
+}
+
+impl<T, const N: usize, B> ChunkedBinaryHeap<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    /// Returns the number of elements in the heap.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns `true` if the heap contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Create new heap with zero elements, which is persisted to the storage backend provided.
+    /// Prefixes storage accesss with the prefix provided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedBinaryHeap;
+    ///
+    /// let mut heap: ChunkedBinaryHeap<u8, 5, _> =
+    ///     ChunkedBinaryHeap::new_in(b"h", InMemoryStorage::new());
+    /// ```
+    pub fn new_in<S>(prefix: S, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            len: 0,
+            values: IndexMap::new_in(prefix, storage),
+        }
+    }
+
+    /// Removes all elements from the heap. This will remove the storage value of every chunk,
+    /// without loading any of them.
+    pub fn clear(&mut self) {
+        if let Some(last) = self.len.checked_sub(1) {
+            for chunk_idx in 0..=locate::<N>(last).0 {
+                self.values.set(chunk_idx, None);
+            }
+        }
+        self.len = 0;
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    ///
+    /// This operation is performed on [`Drop`], but this method can be called to persist
+    /// intermediate writes in cases where [`Drop`] is not called or to identify storage changes.
+    pub fn flush(&mut self) {
+        self.values.flush();
+    }
+}
+
+impl<T, const N: usize, B> ChunkedBinaryHeap<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    fn get(&self, index: u32) -> &T {
+        let (chunk_idx, pos) = locate::<N>(index);
+        let chunk = expect_consistent_state(self.values.get(chunk_idx));
+        expect_consistent_state(chunk.get(pos))
+    }
+
+    /// Swaps the elements at the indices provided, which must be in different chunks.
+    fn swap(&mut self, a: u32, b: u32) {
+        let (a_idx, a_pos) = locate::<N>(a);
+        let (b_idx, b_pos) = locate::<N>(b);
+        debug_assert_ne!(a_idx, b_idx);
+        // Take the first chunk out of the cache to be able to hold both chunks mutably, then put
+        // it back after swapping.
+        let mut a_chunk = expect_consistent_state(self.values.remove(a_idx));
+        let b_chunk = expect_consistent_state(self.values.get_mut(b_idx));
+        core::mem::swap(&mut a_chunk[a_pos], &mut b_chunk[b_pos]);
+        self.values.set(a_idx, Some(a_chunk));
+    }
+
+    /// Moves the element at `index` up until its parent is greater or equal to it.
+    fn sift_up(&mut self, mut index: u32) {
+        while index > 0 {
+            let parent = parent::<N>(index);
+            if self.get(index) <= self.get(parent) {
+                break;
+            }
+            self.swap(index, parent);
+            index = parent;
+        }
+    }
+
+    /// Moves the element at `index` down until all of its children are less or equal to it.
+    fn sift_down(&mut self, mut index: u32) {
+        loop {
+            // Children of the element are the chunk after its index, if it exists.
+            let first_child = index as u64 * N as u64 + 1;
+            if first_child >= self.len as u64 {
+                break;
+            }
+            let children = expect_consistent_state(self.values.get(index + 1));
+            let (pos, child) =
+                expect_consistent_state(children.iter().enumerate().reduce(|max, child| {
+                    if child.1 > max.1 {
+                        child
+                    } else {
+                        max
+                    }
+                }));
+            if child <= self.get(index) {
+                break;
+            }
+            let child = first_child as u32 + pos as u32;
+            self.swap(index, child);
+            index = child;
+        }
+    }
+
+    /// Pushes an element onto the heap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedBinaryHeap;
+    ///
+    /// let mut heap: ChunkedBinaryHeap<u8> = ChunkedBinaryHeap::new(b"h");
+    /// heap.push(3);
+    /// heap.push(5);
+    ///
+    /// assert_eq!(heap.peek(), Some(&5));
+    /// ```
+    pub fn push(&mut self, element: T) {
+        let index = self.len;
+        self.len = self
+            .len
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+
+        let (chunk_idx, pos) = locate::<N>(index);
+        if pos == 0 {
+            self.values.set(chunk_idx, Some(Chunk::with_first(element)));
+        } else {
+            expect_consistent_state(self.values.get_mut(chunk_idx)).push(element);
+        }
+        self.sift_up(index);
+    }
+
+    /// Removes the greatest element from the heap and returns it, or [`None`] if it is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedBinaryHeap;
+    ///
+    /// let mut heap: ChunkedBinaryHeap<u8> = ChunkedBinaryHeap::new(b"h");
+    /// heap.extend([1, 3]);
+    ///
+    /// assert_eq!(heap.pop(), Some(3));
+    /// assert_eq!(heap.pop(), Some(1));
+    /// assert_eq!(heap.pop(), None);
+    /// ```
+    pub fn pop(&mut self) -> Option<T> {
+        let last = self.len.checked_sub(1)?;
+        if last != 0 {
+            self.swap(0, last);
+        }
+
+        let (chunk_idx, pos) = locate::<N>(last);
+        let element = if pos == 0 {
+            // Element is the only one in its chunk, remove the chunk.
+            expect_consistent_state(self.values.remove(chunk_idx)).pop()
+        } else {
+            expect_consistent_state(self.values.get_mut(chunk_idx)).pop()
+        };
+        self.len = last;
+
+        if last != 0 {
+            self.sift_down(0);
+        }
+        element
+    }
+
+    /// Returns the greatest element in the heap, or [`None`] if it is empty. This only loads the
+    /// first chunk, which holds the root alone.
+    pub fn peek(&self) -> Option<&T> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(self.get(0))
+    }
+
+    /// Returns a mutable reference to the greatest element in the heap, or [`None`] if it is
+    /// empty.
+    ///
+    /// If the element is modified through the returned guard, it is moved to its position in
+    /// the heap when the guard is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedBinaryHeap;
+    ///
+    /// let mut heap: ChunkedBinaryHeap<u8> = ChunkedBinaryHeap::new(b"h");
+    /// heap.extend([1, 5, 2]);
+    ///
+    /// if let Some(mut val) = heap.peek_mut() {
+    ///     *val = 0;
+    /// }
+    /// assert_eq!(heap.peek(), Some(&2));
+    /// ```
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, T, N, B>> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(PeekMut {
+            heap: self,
+            sift: false,
+        })
+    }
+
+    /// Returns an iterator which removes and yields the elements of the heap in descending
+    /// order. Elements which are not yielded are kept in the heap when the iterator is dropped.
+    pub fn into_sorted_iter(self) -> IntoSortedIter<T, N, B> {
+        IntoSortedIter::new(self)
+    }
+}
+
+impl<T, const N: usize, B> Extend<T> for ChunkedBinaryHeap<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for item in iter {
+            self.push(item)
+        }
+    }
+}
+
+impl<T, const N: usize, B> fmt::Debug for ChunkedBinaryHeap<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if cfg!(feature = "expensive-debug") {
+            f.debug_list()
+                .entries((0..self.len).map(|i| self.get(i)))
+                .finish()
+        } else {
+            f.debug_struct("BinaryHeap")
+                .field("len", &self.len)
+                .field("prefix", &self.values.prefix)
+                .finish()
+        }
+    }
+}
+
+/// Guard of a mutable reference to the greatest element of a [`ChunkedBinaryHeap`].
+///
+/// Created by [`ChunkedBinaryHeap::peek_mut`].
+pub struct PeekMut<'a, T, const N: usize = 5, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    heap: &'a mut ChunkedBinaryHeap<T, N, B>,
+    /// Whether the element was accessed mutably and may need to be moved down the heap.
+    sift: bool,
+}
+
+impl<'a, T, const N: usize, B> PeekMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    /// Removes the peeked element from the heap and returns it.
+    pub fn pop(mut this: Self) -> T {
+        // Element is removed, so there is nothing to sift on drop.
+        this.sift = false;
+        expect_consistent_state(this.heap.pop())
+    }
+}
+
+impl<'a, T, const N: usize, B> Deref for PeekMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.heap.get(0)
+    }
+}
+
+impl<'a, T, const N: usize, B> DerefMut for PeekMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        self.sift = true;
+        let root = expect_consistent_state(self.heap.values.get_mut(0));
+        expect_consistent_state(root.get_mut(0))
+    }
+}
+
+impl<'a, T, const N: usize, B> Drop for PeekMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        if self.sift {
+            self.heap.sift_down(0);
+        }
+    }
+}
+
+impl<'a, T, const N: usize, B> fmt::Debug for PeekMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + Ord + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PeekMut").field(self.heap.get(0)).finish()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{Rng, RngCore, SeedableRng};
+    use std::collections::BinaryHeap;
+
+    use super::{ChunkedBinaryHeap, PeekMut};
+    use crate::utils::test_iters;
+    use near_sdk::test_utils::test_env::setup_free;
+
+    #[test]
+    fn test_push_pop() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut heap = ChunkedBinaryHeap::<u64, 3>::new(b"h");
+        let mut baseline = BinaryHeap::new();
+        for _ in 0..test_iters(500) {
+            let value = rng.gen::<u64>() % 100;
+            heap.push(value);
+            baseline.push(value);
+            assert_eq!(heap.peek(), baseline.peek());
+        }
+        for _ in 0..=test_iters(500) {
+            assert_eq!(heap.pop(), baseline.pop());
+        }
+        heap.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[test]
+    fn test_peek_mut() {
+        let mut heap = ChunkedBinaryHeap::<u32, 2>::new(b"h");
+        heap.extend([4, 8, 1, 6, 3]);
+
+        // Reading through the guard does not move the element.
+        assert_eq!(*heap.peek_mut().unwrap(), 8);
+        *heap.peek_mut().unwrap() = 2;
+        assert_eq!(heap.peek(), Some(&6));
+        assert_eq!(PeekMut::pop(heap.peek_mut().unwrap()), 6);
+        assert_eq!(heap.into_sorted_iter().collect::<Vec<_>>(), [4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_into_sorted_iter() {
+        let mut heap = ChunkedBinaryHeap::<u32, 4>::new(b"h");
+        heap.extend((0..50).rev());
+
+        let mut iter = heap.into_sorted_iter();
+        assert_eq!(iter.len(), 50);
+        assert!(Iterator::eq(iter.by_ref().take(10), (40..50).rev()));
+        drop(iter);
+
+        // Remaining elements are kept in storage.
+        let heap = ChunkedBinaryHeap::<u32, 4> {
+            len: 40,
+            values: crate::index_map::IndexMap::new_in(b"h", crate::storage::NearStorage),
+        };
+        assert!(Iterator::eq(heap.into_sorted_iter(), (0..40).rev()));
+    }
+
+    #[test]
+    fn chunk_layout() {
+        let mut heap = ChunkedBinaryHeap::<u64, 3>::new(b"h");
+        heap.extend(0..7);
+        heap.flush();
+
+        let chunk_key = |index: u32| [&b"h"[..], &index.to_le_bytes()].concat();
+        let stored = |index: u32| {
+            let bytes = near_sdk::env::storage_read(&chunk_key(index)).unwrap();
+            Vec::<u64>::try_from_slice(&bytes).unwrap()
+        };
+
+        // Root is alone in the first chunk, and the children of element `i` fill chunk `i + 1`.
+        assert_eq!(stored(0), [6]);
+        assert_eq!(stored(1).len(), 3);
+        assert_eq!(stored(2).len(), 3);
+        for (i, parent) in stored(1).into_iter().enumerate() {
+            if let Some(children) = near_sdk::env::storage_read(&chunk_key(i as u32 + 2)) {
+                let children = Vec::<u64>::try_from_slice(&children).unwrap();
+                assert!(children.iter().all(|child| *child <= parent));
+            }
+        }
+    }
+
+    #[derive(Arbitrary, Debug)]
+    enum Op {
+        Push(u8),
+        Pop,
+        Peek,
+        PeekMut(u8),
+        Flush,
+        Reset,
+        Clear,
+    }
+
+    #[test]
+    fn arbitrary() {
+        setup_free();
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut buf = vec![0; 4096];
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 1024 };
+        for _ in 0..runs {
+            // Clear storage in-between runs
+            near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+            rng.fill_bytes(&mut buf);
+
+            let mut sh = ChunkedBinaryHeap::<_, 2>::new(b"h");
+            let mut mh = BinaryHeap::new();
+            let u = Unstructured::new(&buf);
+            if let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) {
+                for op in ops {
+                    match op {
+                        Op::Push(v) => {
+                            sh.push(v);
+                            mh.push(v);
+                        }
+                        Op::Pop => {
+                            assert_eq!(sh.pop(), mh.pop());
+                        }
+                        Op::Peek => {
+                            assert_eq!(sh.peek(), mh.peek());
+                        }
+                        Op::PeekMut(v) => {
+                            if let Some(mut top) = sh.peek_mut() {
+                                *top = v;
+                            }
+                            if let Some(mut top) = mh.peek_mut() {
+                                *top = v;
+                            }
+                        }
+                        Op::Flush => {
+                            sh.flush();
+                        }
+                        Op::Reset => {
+                            let serialized = sh.try_to_vec().unwrap();
+                            sh =
+                                ChunkedBinaryHeap::deserialize(&mut serialized.as_slice()).unwrap();
+                        }
+                        Op::Clear => {
+                            sh.clear();
+                            mh.clear();
+                        }
+                    }
+                    assert_eq!(sh.len() as usize, mh.len());
+                }
+            }
+
+            // After all operations, compare both heaps
+            assert!(Iterator::eq(
+                sh.into_sorted_iter(),
+                mh.into_sorted_vec().into_iter().rev()
+            ));
+        }
+    }
+}
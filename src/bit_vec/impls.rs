@@ -0,0 +1,16 @@
+use super::ChunkedBitVec;
+use crate::storage::StorageBackend;
+
+impl<const N: usize, B> Extend<bool> for ChunkedBitVec<N, B>
+where
+    B: StorageBackend,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = bool>,
+    {
+        for value in iter {
+            self.push(value)
+        }
+    }
+}
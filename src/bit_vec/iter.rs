@@ -0,0 +1,87 @@
+use core::iter::FusedIterator;
+use core::ops::Range;
+use core::slice;
+
+use super::ChunkedBitVec;
+use crate::chunk::chunk_count;
+use crate::storage::{DefaultStorage, StorageBackend};
+use crate::utils::expect_consistent_state;
+
+/// An iterator over the indices of the set bits of a stored bit vector.
+#[derive(Debug)]
+pub struct IterOnes<'a, const N: usize, B = DefaultStorage>
+where
+    B: StorageBackend,
+{
+    /// Underlying bit vector to iterate through
+    vec: &'a ChunkedBitVec<N, B>,
+    /// Indices of the chunks which have not been visited yet.
+    chunks: Range<u32>,
+    /// Index of the next byte to visit, and the remaining bytes of the current chunk.
+    bytes: (u32, slice::Iter<'a, u8>),
+    /// Index of the first bit of the current byte, and the bits of it not yet yielded.
+    current: (u32, u8),
+    /// Number of set bits left to yield.
+    remaining: u32,
+}
+
+impl<'a, const N: usize, B> IterOnes<'a, N, B>
+where
+    B: StorageBackend,
+{
+    pub(super) fn new(vec: &'a ChunkedBitVec<N, B>) -> Self {
+        Self {
+            vec,
+            chunks: 0..chunk_count::<N>(vec.byte_len()),
+            bytes: (0, [].iter()),
+            current: (0, 0),
+            remaining: vec.count_ones(),
+        }
+    }
+}
+
+impl<'a, const N: usize, B> Iterator for IterOnes<'a, N, B>
+where
+    B: StorageBackend,
+{
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            let (first, bits) = &mut self.current;
+            if *bits != 0 {
+                let index = *first + bits.trailing_zeros();
+                // Clear the lowest set bit.
+                *bits &= *bits - 1;
+                self.remaining -= 1;
+                return Some(index);
+            }
+
+            let (byte_idx, bytes) = &mut self.bytes;
+            if let Some(byte) = bytes.next() {
+                self.current = (*byte_idx * 8, *byte);
+                *byte_idx += 1;
+                continue;
+            }
+
+            let chunk_idx = self.chunks.next()?;
+            let chunk = expect_consistent_state(self.vec.values.get(chunk_idx));
+            self.bytes = (chunk_idx * N as u32, chunk.iter());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining as usize;
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining as usize
+    }
+}
+
+impl<'a, const N: usize, B> ExactSizeIterator for IterOnes<'a, N, B> where B: StorageBackend {}
+impl<'a, const N: usize, B> FusedIterator for IterOnes<'a, N, B> where B: StorageBackend {}
@@ -0,0 +1,561 @@
+//! A growable bit vector with bits packed into bytes, persisted to storage in chunks and lazily
+//! loaded.
+//!
+//! Values in the [`ChunkedBitVec`] are kept in an in-memory cache and are only persisted on
+//! [`Drop`].
+//!
+//! # Examples
+//!
+//! ```
+//! use near_chunked_collections::ChunkedBitVec;
+//!
+//! let mut claimed: ChunkedBitVec = ChunkedBitVec::new(b"c");
+//! claimed.extend([false; 100]);
+//!
+//! assert!(!claimed.set(42, true));
+//! // Setting the bit again returns that it was already set.
+//! assert!(claimed.set(42, true));
+//! assert_eq!(claimed.count_ones(), 1);
+//! ```
+
+mod impls;
+mod iter;
+
+use core::fmt;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub use self::iter::IterOnes;
+
+use crate::chunk::{chunk_count, chunk_index, chunk_pos, Chunk};
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+use crate::utils::{expect_consistent_state, panic_str};
+
+const ERR_INDEX_OUT_OF_BOUNDS: &str = "Index out of bounds";
+
+/// Index of the byte which holds the bit at `index`.
+fn byte_index(index: u32) -> u32 {
+    index / 8
+}
+
+/// Position within its byte of the bit at `index`. Bits are ordered from least significant.
+fn bit_pos(index: u32) -> u32 {
+    index % 8
+}
+
+/// Mask of the bit at `index` within its byte.
+fn bit_mask(index: u32) -> u8 {
+    1 << bit_pos(index)
+}
+
+/// A growable vector of bits, which stores its content on the trie. This implementation will load
+/// and store values in the underlying storage lazily.
+///
+/// Uses the following map: chunk index -> chunk of up to `N` bytes, so every storage value holds
+/// up to `N * 8` bits. Like [`ChunkedVector`](crate::ChunkedVector), only the last chunk can be
+/// partially filled and it only persists the bytes it contains. Bits past the length of the
+/// vector are always kept unset.
+///
+/// The number of set bits is tracked alongside the length, so [`ChunkedBitVec::count_ones`] does
+/// not need to load any chunks.
+///
+/// This implementation will cache all changes and loads and only updates values that are changed
+/// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
+/// in storage before the variable is dropped by using [`ChunkedBitVec::flush`].
+///
+/// # Examples
+/// ```
+/// use near_chunked_collections::ChunkedBitVec;
+///
+/// let mut bits: ChunkedBitVec = ChunkedBitVec::new(b"b");
+/// bits.push(true);
+/// bits.push(false);
+/// bits.push(true);
+///
+/// assert_eq!(bits.get(1), Some(false));
+/// assert_eq!(bits.first_zero(), Some(1));
+/// assert_eq!(bits.iter_ones().collect::<Vec<_>>(), &[0, 2]);
+///
+/// assert_eq!(bits.pop(), Some(true));
+/// assert_eq!(bits.count_ones(), 1);
+/// ```
+pub struct ChunkedBitVec<const N: usize = 32, B = DefaultStorage>
+where
+    B: StorageBackend,
+{
+    pub(crate) len: u32,
+    /// Number of set bits.
+    pub(crate) ones: u32,
+    pub(crate) values: IndexMap<Chunk<u8, N>, B>,
+}
+
+impl<const N: usize> ChunkedBitVec<N, DefaultStorage> {
+    /// Create new bit vector with zero bits. Prefixes storage accesss with the prefix provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedBitVec;
+    ///
+    /// let mut bits: ChunkedBitVec = ChunkedBitVec::new(b"b");
+    /// ```
+    pub fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self::new_in(prefix, DefaultStorage::default())
+    }
+}
+
+impl<const N: usize, B> Drop for ChunkedBitVec<N, B>
+where
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+impl<const N: usize, B> BorshSerialize for ChunkedBitVec<N, B>
+where
+    B: StorageBackend,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.len, writer)?;
+        BorshSerialize::serialize(&self.ones, writer)?;
+        BorshSerialize::serialize(&self.values, writer)?;
+        Ok(())
+    }
+}
+
+impl<const N: usize, B> BorshDeserialize for ChunkedBitVec<N, B>
+where
+    B: StorageBackend + Default,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            len: BorshDeserialize::deserialize(buf)?,
+            ones: BorshDeserialize::deserialize(buf)?,
+            values: BorshDeserialize::deserialize(buf)?,
+        })
+    }
+}
+
+impl<const N: usize, B> ChunkedBitVec<N, B>
+where
+    B: StorageBackend,
+{
+    /// Returns the number of bits in the vector.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns `true` if the vector contains no bits.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bits which are set. This does not load any chunks.
+    pub fn count_ones(&self) -> u32 {
+        self.ones
+    }
+
+    /// Returns the number of bits which are not set. This does not load any chunks.
+    pub fn count_zeros(&self) -> u32 {
+        self.len - self.ones
+    }
+
+    /// Create new bit vector with zero bits, which is persisted to the storage backend provided.
+    /// Prefixes storage accesss with the prefix provided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedBitVec;
+    ///
+    /// let mut bits: ChunkedBitVec<32, _> = ChunkedBitVec::new_in(b"b", InMemoryStorage::new());
+    /// ```
+    pub fn new_in<S>(prefix: S, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            len: 0,
+            ones: 0,
+            values: IndexMap::new_in(prefix, storage),
+        }
+    }
+
+    /// Removes all bits from the vector. This will remove the storage value of every chunk,
+    /// without loading any of them.
+    pub fn clear(&mut self) {
+        for chunk_idx in 0..chunk_count::<N>(self.byte_len()) {
+            self.values.set(chunk_idx, None);
+        }
+        self.len = 0;
+        self.ones = 0;
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    ///
+    /// This operation is performed on [`Drop`], but this method can be called to persist
+    /// intermediate writes in cases where [`Drop`] is not called or to identify storage changes.
+    pub fn flush(&mut self) {
+        self.values.flush();
+    }
+
+    /// Returns the number of bytes needed to store the bits.
+    fn byte_len(&self) -> u32 {
+        self.len.div_ceil(8)
+    }
+
+    /// Returns the byte which holds the bit at `index`.
+    fn byte(&self, index: u32) -> u8 {
+        let byte_idx = byte_index(index);
+        let chunk = expect_consistent_state(self.values.get(chunk_index::<N>(byte_idx)));
+        *expect_consistent_state(chunk.get(chunk_pos::<N>(byte_idx)))
+    }
+
+    /// Returns a mutable reference to the byte which holds the bit at `index`.
+    fn byte_mut(&mut self, index: u32) -> &mut u8 {
+        let byte_idx = byte_index(index);
+        let chunk = expect_consistent_state(self.values.get_mut(chunk_index::<N>(byte_idx)));
+        expect_consistent_state(chunk.get_mut(chunk_pos::<N>(byte_idx)))
+    }
+
+    /// Appends a bit to the back of the vector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    pub fn push(&mut self, value: bool) {
+        let index = self.len;
+        self.len = self
+            .len
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+
+        let byte = value as u8;
+        let byte_idx = byte_index(index);
+        if bit_pos(index) != 0 {
+            // Byte already exists, bits past the length are always unset.
+            if value {
+                *self.byte_mut(index) |= bit_mask(index);
+            }
+        } else if chunk_pos::<N>(byte_idx) == 0 {
+            // Push is on new chunk, create new chunk
+            self.values
+                .set(chunk_index::<N>(byte_idx), Some(Chunk::with_first(byte)));
+        } else {
+            expect_consistent_state(self.values.get_mut(chunk_index::<N>(byte_idx))).push(byte);
+        }
+        self.ones += value as u32;
+    }
+
+    /// Removes the last bit from the vector and returns it, or [`None`] if it is empty.
+    pub fn pop(&mut self) -> Option<bool> {
+        let index = self.len.checked_sub(1)?;
+        let byte_idx = byte_index(index);
+        let chunk_idx = chunk_index::<N>(byte_idx);
+        let value = if bit_pos(index) != 0 {
+            let byte = self.byte_mut(index);
+            let value = *byte & bit_mask(index) != 0;
+            // Unset the bit, to keep bits past the length unset.
+            *byte &= !bit_mask(index);
+            value
+        } else if chunk_pos::<N>(byte_idx) == 0 {
+            // Bit is the only one in the chunk, remove the chunk.
+            let mut chunk = expect_consistent_state(self.values.remove(chunk_idx));
+            expect_consistent_state(chunk.pop()) != 0
+        } else {
+            // Shrink the chunk so that only the remaining bytes are persisted.
+            let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+            expect_consistent_state(chunk.pop()) != 0
+        };
+        self.len = index;
+        self.ones -= value as u32;
+        Some(value)
+    }
+
+    /// Returns the bit at `index`, or `None` if it is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedBitVec;
+    ///
+    /// let mut bits: ChunkedBitVec = ChunkedBitVec::new(b"b");
+    /// bits.push(true);
+    ///
+    /// assert_eq!(bits.get(0), Some(true));
+    /// assert_eq!(bits.get(1), None);
+    /// ```
+    pub fn get(&self, index: u32) -> Option<bool> {
+        if index >= self.len {
+            return None;
+        }
+
+        Some(self.byte(index) & bit_mask(index) != 0)
+    }
+
+    /// Sets the bit at `index` to `value`, returning the previous value of the bit.
+    ///
+    /// The chunk is only marked as modified if the bit changes, so setting a bit to its current
+    /// value does not write to storage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedBitVec;
+    ///
+    /// let mut bits: ChunkedBitVec = ChunkedBitVec::new(b"b");
+    /// bits.extend([false, false]);
+    ///
+    /// assert!(!bits.set(1, true));
+    /// assert_eq!(bits.get(1), Some(true));
+    /// ```
+    pub fn set(&mut self, index: u32, value: bool) -> bool {
+        let prev = self
+            .get(index)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+        if prev != value {
+            *self.byte_mut(index) ^= bit_mask(index);
+            if value {
+                self.ones += 1;
+            } else {
+                self.ones -= 1;
+            }
+        }
+        prev
+    }
+
+    /// Returns the index of the first bit which is not set, or `None` if every bit is set.
+    ///
+    /// Chunks are loaded in order until one with an unset bit is found. If every bit is set,
+    /// this returns without loading any chunks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedBitVec;
+    ///
+    /// let mut bits: ChunkedBitVec = ChunkedBitVec::new(b"b");
+    /// bits.extend([true, true, false, true]);
+    ///
+    /// assert_eq!(bits.first_zero(), Some(2));
+    /// bits.set(2, true);
+    /// assert_eq!(bits.first_zero(), None);
+    /// ```
+    pub fn first_zero(&self) -> Option<u32> {
+        if self.ones == self.len {
+            return None;
+        }
+
+        for chunk_idx in 0..chunk_count::<N>(self.byte_len()) {
+            let chunk = expect_consistent_state(self.values.get(chunk_idx));
+            if let Some(pos) = chunk.iter().position(|byte| *byte != u8::MAX) {
+                let byte_idx = chunk_idx * N as u32 + pos as u32;
+                let index = byte_idx * 8 + chunk[pos].trailing_ones();
+                // Bits past the length are unset, but there is a zero bit within the length.
+                return Some(index).filter(|index| *index < self.len);
+            }
+        }
+        None
+    }
+
+    /// Returns an iterator over the indices of the bits which are set, in ascending order. This
+    /// iterator will lazily load any chunks iterated over from storage.
+    ///
+    /// No chunks are loaded once every set bit has been yielded.
+    pub fn iter_ones(&self) -> IterOnes<'_, N, B> {
+        IterOnes::new(self)
+    }
+}
+
+impl<const N: usize, B> fmt::Debug for ChunkedBitVec<N, B>
+where
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if cfg!(feature = "expensive-debug") {
+            f.debug_list()
+                .entries((0..self.len).map(|i| self.get(i).unwrap_or_default()))
+                .finish()
+        } else {
+            f.debug_struct("BitVec")
+                .field("len", &self.len)
+                .field("ones", &self.ones)
+                .field("prefix", &self.values.prefix)
+                .finish()
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{Rng, RngCore, SeedableRng};
+
+    use super::ChunkedBitVec;
+    use near_sdk::test_utils::test_env::setup_free;
+
+    #[test]
+    fn test_push_pop() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut bits = ChunkedBitVec::<4>::new(b"b");
+        let mut baseline = vec![];
+        for _ in 0..500 {
+            let value = rng.gen::<bool>();
+            bits.push(value);
+            baseline.push(value);
+        }
+        assert_eq!(
+            bits.count_ones() as usize,
+            baseline.iter().filter(|b| **b).count()
+        );
+        for _ in 0..501 {
+            assert_eq!(baseline.pop(), bits.pop());
+        }
+        assert_eq!(bits.count_ones(), 0);
+        bits.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[test]
+    fn test_first_zero() {
+        let mut bits = ChunkedBitVec::<2>::new(b"b");
+        assert_eq!(bits.first_zero(), None);
+        bits.extend([true; 37]);
+        assert_eq!(bits.first_zero(), None);
+        bits.set(20, false);
+        assert_eq!(bits.first_zero(), Some(20));
+        bits.set(20, true);
+        bits.push(false);
+        assert_eq!(bits.first_zero(), Some(37));
+    }
+
+    #[test]
+    fn test_iter_ones() {
+        let mut bits = ChunkedBitVec::<2>::new(b"b");
+        bits.extend((0..100).map(|i| i % 7 == 0));
+        assert!(Iterator::eq(bits.iter_ones(), (0..100).step_by(7)));
+        assert_eq!(bits.iter_ones().len(), 15);
+        bits.clear();
+        assert_eq!(bits.iter_ones().next(), None);
+    }
+
+    #[test]
+    fn chunk_bytes() {
+        let mut bits = ChunkedBitVec::<2>::new(b"b");
+        bits.extend((0..20).map(|i| i % 2 == 0));
+        bits.flush();
+
+        let chunk_key = |index: u32| [&b"b"[..], &index.to_le_bytes()].concat();
+        let stored = |index: u32| near_sdk::env::storage_read(&chunk_key(index)).unwrap();
+
+        // Each chunk holds up to 16 bits, the tail chunk only stores the single byte it uses.
+        assert_eq!(stored(0), vec![0x55u8, 0x55].try_to_vec().unwrap());
+        assert_eq!(stored(1), vec![0x05u8].try_to_vec().unwrap());
+
+        // Popping unsets the bit rather than leaving it past the length.
+        assert_eq!(bits.pop(), Some(false));
+        assert_eq!(bits.pop(), Some(true));
+        bits.flush();
+        assert_eq!(stored(1), vec![0x01u8].try_to_vec().unwrap());
+    }
+
+    #[derive(Arbitrary, Debug)]
+    enum Op {
+        Push(bool),
+        Pop,
+        Set(u32, bool),
+        Get(u32),
+        FirstZero,
+        Flush,
+        Reset,
+        Clear,
+    }
+
+    #[test]
+    fn arbitrary() {
+        setup_free();
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut buf = vec![0; 4096];
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 1024 };
+        for _ in 0..runs {
+            // Clear storage in-between runs
+            near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+            rng.fill_bytes(&mut buf);
+
+            let mut sv = ChunkedBitVec::<1>::new(b"b");
+            let mut mv = Vec::new();
+            let u = Unstructured::new(&buf);
+            if let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) {
+                for op in ops {
+                    match op {
+                        Op::Push(v) => {
+                            sv.push(v);
+                            mv.push(v);
+                        }
+                        Op::Pop => {
+                            assert_eq!(sv.pop(), mv.pop());
+                        }
+                        Op::Set(i, v) => {
+                            if sv.is_empty() {
+                                continue;
+                            }
+                            let i = i % sv.len();
+                            let prev = core::mem::replace(&mut mv[i as usize], v);
+                            assert_eq!(sv.set(i, v), prev);
+                        }
+                        Op::Get(i) => {
+                            assert_eq!(sv.get(i), mv.get(i as usize).copied());
+                        }
+                        Op::FirstZero => {
+                            let expected = mv.iter().position(|b| !*b).map(|i| i as u32);
+                            assert_eq!(sv.first_zero(), expected);
+                        }
+                        Op::Flush => {
+                            sv.flush();
+                        }
+                        Op::Reset => {
+                            let serialized = sv.try_to_vec().unwrap();
+                            sv = ChunkedBitVec::deserialize(&mut serialized.as_slice()).unwrap();
+                        }
+                        Op::Clear => {
+                            sv.clear();
+                            mv.clear();
+                        }
+                    }
+                    assert_eq!(sv.len() as usize, mv.len());
+                    assert_eq!(sv.count_ones() as usize, mv.iter().filter(|b| **b).count());
+                }
+            }
+
+            // After all operations, compare both vectors
+            assert!(Iterator::eq(
+                sv.iter_ones(),
+                mv.iter()
+                    .enumerate()
+                    .filter(|(_, b)| **b)
+                    .map(|(i, _)| i as u32)
+            ));
+        }
+    }
+}
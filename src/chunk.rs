@@ -0,0 +1,149 @@
+use alloc::vec::Vec;
+
+use borsh::maybestd::io::{Error, ErrorKind, Write};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+const ERR_CHUNK_OVERFLOW: &str = "chunk length exceeds chunk size";
+
+/// Index of the chunk which holds the element at `index`.
+pub(crate) fn chunk_index<const N: usize>(index: u32) -> u32 {
+    // TODO yeah this is a bit unsafe if N is > 32 bits range. Fix
+    (index as usize / N) as u32
+}
+
+/// Position within its chunk of the element at `index`.
+pub(crate) fn chunk_pos<const N: usize>(index: u32) -> usize {
+    index as usize % N
+}
+
+/// Number of chunks needed to store `len` elements.
+pub(crate) fn chunk_count<const N: usize>(len: u32) -> u32 {
+    (len as usize).div_ceil(N) as u32
+}
+
+/// A group of up to `N` elements which is stored under a single storage key.
+///
+/// Only the occupied elements are kept, so a partially filled chunk (such as the last one of a
+/// vector, or either end of a deque) only persists the elements it contains rather than all `N`
+/// slots.
+///
+/// # Storage format
+///
+/// The chunk is serialized as its length as a little-endian `u32` followed by each element,
+/// which is the same layout as a Borsh encoded [`Vec`].
+#[derive(Debug)]
+pub(crate) struct Chunk<T, const N: usize> {
+    items: Vec<T>,
+}
+
+impl<T, const N: usize> Chunk<T, N> {
+    /// Creates a chunk with no elements.
+    pub(crate) fn new() -> Self {
+        Self {
+            items: Vec::with_capacity(N),
+        }
+    }
+
+    /// Creates a chunk with a single element.
+    pub(crate) fn with_first(element: T) -> Self {
+        let mut chunk = Self::new();
+        chunk.push(element);
+        chunk
+    }
+
+    /// Appends an element to the end of the chunk.
+    pub(crate) fn push(&mut self, element: T) {
+        debug_assert!(self.items.len() < N, "{}", ERR_CHUNK_OVERFLOW);
+        self.items.push(element);
+    }
+
+    /// Removes the last element of the chunk.
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        self.items.pop()
+    }
+
+    /// Inserts an element at `pos`, shifting the later elements back. If the chunk was already
+    /// full, the last element is removed and returned.
+    pub(crate) fn insert(&mut self, pos: usize, element: T) -> Option<T> {
+        self.items.insert(pos, element);
+        if self.items.len() > N {
+            self.items.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Removes the element at `pos`, shifting the later elements forward.
+    pub(crate) fn remove(&mut self, pos: usize) -> T {
+        self.items.remove(pos)
+    }
+
+    /// Shortens the chunk, keeping the first `len` elements.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.items.truncate(len)
+    }
+
+    /// Returns the number of elements in the chunk, without borrowing the elements.
+    pub(crate) fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns a raw pointer to the elements of the chunk. Unlike going through a mutable slice,
+    /// this does not borrow the elements, so references to other elements of the chunk which
+    /// were created from the pointer before stay valid.
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut T {
+        self.items.as_mut_ptr()
+    }
+
+    /// Consumes the chunk, returning the elements it contains.
+    pub(crate) fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T, const N: usize> From<Vec<T>> for Chunk<T, N> {
+    fn from(items: Vec<T>) -> Self {
+        debug_assert!(items.len() <= N, "{}", ERR_CHUNK_OVERFLOW);
+        Self { items }
+    }
+}
+
+impl<T, const N: usize> core::ops::Deref for Chunk<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+impl<T, const N: usize> core::ops::DerefMut for Chunk<T, N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.items
+    }
+}
+
+impl<T, const N: usize> BorshSerialize for Chunk<T, N>
+where
+    T: BorshSerialize,
+{
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        BorshSerialize::serialize(&self.items, writer)
+    }
+}
+
+impl<T, const N: usize> BorshDeserialize for Chunk<T, N>
+where
+    T: BorshDeserialize,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, Error> {
+        let len = u32::deserialize(buf)? as usize;
+        if len > N {
+            return Err(Error::new(ErrorKind::InvalidData, ERR_CHUNK_OVERFLOW));
+        }
+        let mut chunk = Self::new();
+        for _ in 0..len {
+            chunk.items.push(T::deserialize(buf)?);
+        }
+        Ok(chunk)
+    }
+}
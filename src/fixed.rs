@@ -0,0 +1,57 @@
+//! Marker for types which are always serialized as the same number of bytes.
+//!
+//! Elements of a chunk are serialized one after the other, following the length of the chunk.
+//! When every element has the same size, the bytes of a single element can be found within a
+//! stored chunk without deserializing the elements before it. Collections use this to read or
+//! patch a single element of a chunk which has not been loaded, through methods such as
+//! [`ChunkedVector::get_fixed`](crate::ChunkedVector::get_fixed).
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+/// A type which is always Borsh serialized as exactly [`FixedSize::SIZE`] bytes.
+///
+/// This is implemented for integers, [`bool`], and arrays of fixed size types, such as
+/// `[u8; 32]` for hashes of account ids or public keys.
+///
+/// Implementing this trait for a type which can be serialized with a different number of bytes
+/// is a logic error, and will panic or corrupt the chunk when an element is written in place.
+///
+/// # Examples
+///
+/// ```
+/// use borsh::{BorshDeserialize, BorshSerialize};
+/// use near_chunked_collections::FixedSize;
+///
+/// #[derive(Clone, BorshSerialize, BorshDeserialize)]
+/// struct Balance {
+///     owner: [u8; 32],
+///     amount: u128,
+/// }
+///
+/// impl FixedSize for Balance {
+///     const SIZE: usize = <[u8; 32]>::SIZE + u128::SIZE;
+/// }
+/// ```
+pub trait FixedSize: BorshSerialize + BorshDeserialize {
+    /// Number of bytes the type is serialized as.
+    const SIZE: usize;
+}
+
+macro_rules! impl_fixed_size {
+    ($($ty:ty),*) => {
+        $(
+            impl FixedSize for $ty {
+                const SIZE: usize = core::mem::size_of::<$ty>();
+            }
+        )*
+    };
+}
+
+impl_fixed_size!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, bool);
+
+impl<T, const L: usize> FixedSize for [T; L]
+where
+    T: FixedSize,
+{
+    const SIZE: usize = T::SIZE * L;
+}
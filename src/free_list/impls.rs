@@ -0,0 +1,56 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::iter::{Iter, IterMut};
+use super::{ChunkedFreeList, FreeListIndex, ERR_INDEX_OUT_OF_BOUNDS};
+use crate::storage::StorageBackend;
+use crate::utils::panic_str;
+
+impl<'a, T, const N: usize, B> IntoIterator for &'a ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = (FreeListIndex, &'a T);
+    type IntoIter = Iter<'a, T, N, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize, B> IntoIterator for &'a mut ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = (FreeListIndex, &'a mut T);
+    type IntoIter = IterMut<'a, T, N, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T, const N: usize, B> core::ops::Index<FreeListIndex> for ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Output = T;
+
+    fn index(&self, index: FreeListIndex) -> &Self::Output {
+        self.get(index)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS))
+    }
+}
+
+impl<T, const N: usize, B> core::ops::IndexMut<FreeListIndex> for ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn index_mut(&mut self, index: FreeListIndex) -> &mut Self::Output {
+        self.get_mut(index)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS))
+    }
+}
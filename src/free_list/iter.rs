@@ -0,0 +1,196 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::iter::{Enumerate, FusedIterator};
+use core::ops::Range;
+use core::slice;
+
+use super::slot::Slot;
+use super::{ChunkedFreeList, FreeListIndex};
+use crate::chunk::chunk_count;
+use crate::storage::{DefaultStorage, StorageBackend};
+use crate::utils::expect_consistent_state;
+
+/// An iterator over references to each occupied slot of a stored free list, along with its index.
+#[derive(Debug)]
+pub struct Iter<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Underlying free list to iterate through
+    list: &'a ChunkedFreeList<T, N, B>,
+    /// Indices of the chunks which have not been visited yet.
+    chunks: Range<u32>,
+    /// Index of the first slot of the current chunk, and the remaining slots of it.
+    current: Option<(u32, Enumerate<slice::Iter<'a, Slot<T>>>)>,
+    /// Number of occupied slots left to yield.
+    remaining: u32,
+}
+
+impl<'a, T, const N: usize, B> Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(list: &'a ChunkedFreeList<T, N, B>) -> Self {
+        Self {
+            list,
+            chunks: 0..chunk_count::<N>(list.next_index),
+            current: None,
+            remaining: list.len(),
+        }
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = (FreeListIndex, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some((first, slots)) = &mut self.current {
+                for (pos, slot) in slots {
+                    if let Slot::Occupied(value) = slot {
+                        self.remaining -= 1;
+                        return Some((FreeListIndex(*first + pos as u32), value));
+                    }
+                }
+            }
+
+            let chunk_idx = self.chunks.next()?;
+            // Chunks without any occupied slot are skipped without being loaded.
+            if !self.list.occupied_chunks.is_occupied(chunk_idx) {
+                continue;
+            }
+            let chunk = expect_consistent_state(self.list.values.get(chunk_idx));
+            self.current = Some((chunk_idx * N as u32, chunk.slots.iter().enumerate()));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining as usize;
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining as usize
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+/// An iterator over exclusive references to each occupied slot of a stored free list, along with
+/// its index.
+#[derive(Debug)]
+pub struct IterMut<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Mutable reference to free list used to iterate through.
+    list: &'a mut ChunkedFreeList<T, N, B>,
+    /// Indices of the chunks which have not been visited yet.
+    chunks: Range<u32>,
+    /// Index of the first slot of the current chunk, and the remaining slots of it.
+    current: Option<(u32, Enumerate<slice::IterMut<'a, Slot<T>>>)>,
+    /// Number of occupied slots left to yield.
+    remaining: u32,
+}
+
+impl<'a, T, const N: usize, B> IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Creates a new iterator for the given storage free list.
+    pub(crate) fn new(list: &'a mut ChunkedFreeList<T, N, B>) -> Self {
+        let chunks = 0..chunk_count::<N>(list.next_index);
+        let remaining = list.len();
+        Self {
+            list,
+            chunks,
+            current: None,
+            remaining,
+        }
+    }
+
+    fn slots_mut<'b>(&'b mut self, chunk_idx: u32) -> Option<&'a mut [Slot<T>]> {
+        self.list.values.get_mut(chunk_idx).map(|chunk| {
+            //* SAFETY: The lifetime can be swapped here because we can assert that the iterator
+            //*         will only give out one mutable reference for every individual chunk
+            //*         during the iteration, and there is no overlap. Every chunk index is only
+            //*         visited once, as they are taken from the `chunks` range.
+            unsafe { &mut *(&mut *chunk.slots as *mut [Slot<T>]) }
+        })
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = (FreeListIndex, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some((first, slots)) = &mut self.current {
+                for (pos, slot) in slots {
+                    if let Slot::Occupied(value) = slot {
+                        self.remaining -= 1;
+                        return Some((FreeListIndex(*first + pos as u32), value));
+                    }
+                }
+            }
+
+            let chunk_idx = self.chunks.next()?;
+            // Chunks without any occupied slot are skipped without being loaded.
+            if !self.list.occupied_chunks.is_occupied(chunk_idx) {
+                continue;
+            }
+            let slots = expect_consistent_state(self.slots_mut(chunk_idx));
+            self.current = Some((chunk_idx * N as u32, slots.iter_mut().enumerate()));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining as usize;
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining as usize
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
@@ -0,0 +1,614 @@
+//! A list of values with stable indices, persisted to storage in chunks and lazily loaded.
+//!
+//! Values in the [`ChunkedFreeList`] are kept in an in-memory cache and are only persisted on
+//! [`Drop`].
+//!
+//! # Examples
+//!
+//! ```
+//! use near_chunked_collections::ChunkedFreeList;
+//!
+//! let mut list: ChunkedFreeList<String> = ChunkedFreeList::new(b"f");
+//! let a = list.insert("a".to_string());
+//! let b = list.insert("b".to_string());
+//!
+//! assert_eq!(list.remove(a), Some("a".to_string()));
+//! // Removing an element does not move any others.
+//! assert_eq!(list.get(b), Some(&"b".to_string()));
+//! ```
+
+mod impls;
+mod iter;
+mod occupancy;
+mod slot;
+
+use core::fmt;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub use self::iter::{Iter, IterMut};
+
+use self::occupancy::ChunkOccupancy;
+use self::slot::{Slot, SlotChunk};
+use crate::chunk::{chunk_count, chunk_index, chunk_pos};
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+use crate::utils::{expect_consistent_state, panic_str};
+
+const ERR_INDEX_OUT_OF_BOUNDS: &str = "Index out of bounds";
+
+/// Index of a value in a [`ChunkedFreeList`]. The index stays valid until the value is removed,
+/// after which it may be reused for a value inserted later.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, BorshSerialize, BorshDeserialize,
+)]
+pub struct FreeListIndex(pub(crate) u32);
+
+impl FreeListIndex {
+    /// Returns the raw index of the slot.
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+/// A list of values with stable indices, which stores its content on the trie. This
+/// implementation will load and store values in the underlying storage lazily.
+///
+/// [`ChunkedFreeList::insert`] returns a [`FreeListIndex`] which can be used to access the value
+/// until it is removed. Removing a value never swaps or shifts other values, the slot is instead
+/// marked as vacant and reused by a later insert.
+///
+/// Uses the following map: chunk index -> chunk of up to `N` slots. Vacant slots are linked
+/// together by an intrusive chain stored within the chunks themselves: each chunk links its own
+/// vacant slots and the next chunk which has a vacant slot, so inserting never has to search
+/// for a free slot and only touches a single chunk.
+///
+/// Which chunks have an occupied slot is tracked in the root of the list, so iterating skips
+/// empty chunks without loading them. Empty chunks at the end of the list are removed from
+/// storage, and their slots are allocated again by later inserts.
+///
+/// This implementation will cache all changes and loads and only updates values that are changed
+/// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
+/// in storage before the variable is dropped by using [`ChunkedFreeList::flush`].
+///
+/// # Examples
+/// ```
+/// use near_chunked_collections::ChunkedFreeList;
+///
+/// let mut list: ChunkedFreeList<u32> = ChunkedFreeList::new(b"f");
+/// let a = list.insert(1);
+/// let b = list.insert(2);
+/// list.remove(a);
+///
+/// // The vacant slot is reused.
+/// let c = list.insert(3);
+/// assert_eq!(a, c);
+/// assert_eq!(list[b], 2);
+/// assert_eq!(list.iter().map(|(_, v)| *v).collect::<Vec<_>>(), &[3, 2]);
+/// ```
+pub struct ChunkedFreeList<T, const N: usize = 5, B = DefaultStorage>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    /// Number of occupied slots.
+    pub(crate) occupied_count: u32,
+    /// Number of slots allocated, including vacant ones.
+    pub(crate) next_index: u32,
+    /// Index of the first chunk which has a vacant slot.
+    pub(crate) first_vacant: Option<u32>,
+    /// Chunks which have an occupied slot.
+    pub(crate) occupied_chunks: ChunkOccupancy,
+    pub(crate) values: IndexMap<SlotChunk<T, N>, B>,
+}
+
+impl<T, const N: usize> ChunkedFreeList<T, N, DefaultStorage>
+where
+    T: BorshSerialize,
+{
+    /// Create new free list with zero elements. Prefixes storage accesss with the prefix
+    /// provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedFreeList;
+    ///
+    /// let mut list: ChunkedFreeList<u8> = ChunkedFreeList::new(b"f");
+    /// ```
+    pub fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self::new_in(prefix, DefaultStorage::default())
+    }
+}
+
+impl<T, const N: usize, B> Drop for ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+impl<T, const N: usize, B> BorshSerialize for ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.occupied_count, writer)?;
+        BorshSerialize::serialize(&self.next_index, writer)?;
+        BorshSerialize::serialize(&self.first_vacant, writer)?;
+        BorshSerialize::serialize(&self.occupied_chunks, writer)?;
+        BorshSerialize::serialize(&self.values, writer)?;
+        Ok(())
+    }
+}
+
+impl<T, const N: usize, B> BorshDeserialize for ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend + Default,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            occupied_count: BorshDeserialize::deserialize(buf)?,
+            next_index: BorshDeserialize::deserialize(buf)?,
+            first_vacant: BorshDeserialize::deserialize(buf)?,
+            occupied_chunks: BorshDeserialize::deserialize(buf)?,
+            values: BorshDeserialize::deserialize(buf)?,
+        })
+    }
+}
+
+impl<T, const N: usize, B> ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    /// Returns the number of occupied slots in the list.
+    pub fn len(&self) -> u32 {
+        self.occupied_count
+    }
+
+    /// Returns `true` if the list contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.occupied_count == 0
+    }
+
+    /// Create new free list with zero elements, which is persisted to the storage backend
+    /// provided. Prefixes storage accesss with the prefix provided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedFreeList;
+    ///
+    /// let mut list: ChunkedFreeList<u8, 5, _> =
+    ///     ChunkedFreeList::new_in(b"f", InMemoryStorage::new());
+    /// ```
+    pub fn new_in<S>(prefix: S, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            occupied_count: 0,
+            next_index: 0,
+            first_vacant: None,
+            occupied_chunks: ChunkOccupancy::default(),
+            values: IndexMap::new_in(prefix, storage),
+        }
+    }
+
+    /// Removes all elements from the list. This will remove the storage value of every chunk,
+    /// without loading any of them. Indices given out before clearing are invalidated and will
+    /// be reused.
+    pub fn clear(&mut self) {
+        for i in 0..chunk_count::<N>(self.next_index) {
+            self.values.set(i, None);
+        }
+        self.occupied_count = 0;
+        self.next_index = 0;
+        self.first_vacant = None;
+        self.occupied_chunks.clear();
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    ///
+    /// This operation is performed on [`Drop`], but this method can be called to persist
+    /// intermediate writes in cases where [`Drop`] is not called or to identify storage changes.
+    pub fn flush(&mut self) {
+        self.values.flush();
+    }
+}
+
+impl<T, const N: usize, B> ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Inserts a value into the list, returning the index it can be accessed with.
+    ///
+    /// A vacant slot left by a previous removal is reused if there is one, otherwise a new slot
+    /// is appended.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of slots exceeds `u32::MAX`
+    pub fn insert(&mut self, value: T) -> FreeListIndex {
+        let index = if let Some(chunk_idx) = self.first_vacant {
+            let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+            let pos = expect_consistent_state(chunk.first_vacant);
+            let slot = expect_consistent_state(chunk.slots.get_mut(pos as usize));
+            match core::mem::replace(slot, Slot::Occupied(value)) {
+                Slot::Vacant(next) => chunk.first_vacant = next,
+                Slot::Occupied(_) => panic_str("inconsistent state"),
+            }
+            chunk.occupied += 1;
+            if chunk.occupied == 1 {
+                self.occupied_chunks.set(chunk_idx, true);
+            }
+            if chunk.first_vacant.is_none() {
+                // Chunk is now full, unlink it from the chain of chunks with vacant slots.
+                self.first_vacant = chunk.next_vacant.take();
+            }
+            chunk_idx * N as u32 + pos
+        } else {
+            let index = self.next_index;
+            self.next_index = self
+                .next_index
+                .checked_add(1)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+
+            let chunk_idx = chunk_index::<N>(index);
+            if chunk_pos::<N>(index) == 0 {
+                self.values
+                    .set(chunk_idx, Some(SlotChunk::with_first(value)));
+                self.occupied_chunks.set(chunk_idx, true);
+            } else {
+                let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+                chunk.slots.push(Slot::Occupied(value));
+                chunk.occupied += 1;
+            }
+            index
+        };
+        self.occupied_count += 1;
+        FreeListIndex(index)
+    }
+
+    /// Removes the value at the index provided, returning it if the slot was occupied.
+    ///
+    /// No other values are moved, the slot is marked as vacant and will be reused by a later
+    /// [`ChunkedFreeList::insert`]. If this leaves the chunks at the end of the list empty, they
+    /// are removed from storage.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedFreeList;
+    ///
+    /// let mut list: ChunkedFreeList<u8> = ChunkedFreeList::new(b"f");
+    /// let index = list.insert(1);
+    ///
+    /// assert_eq!(list.remove(index), Some(1));
+    /// assert_eq!(list.remove(index), None);
+    /// ```
+    pub fn remove(&mut self, index: FreeListIndex) -> Option<T> {
+        if index.0 >= self.next_index || self.get(index).is_none() {
+            return None;
+        }
+
+        let chunk_idx = chunk_index::<N>(index.0);
+        let pos = chunk_pos::<N>(index.0);
+        let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+        let slot = expect_consistent_state(chunk.slots.get_mut(pos));
+        let value = match core::mem::replace(slot, Slot::Vacant(chunk.first_vacant)) {
+            Slot::Occupied(value) => value,
+            Slot::Vacant(_) => panic_str("inconsistent state"),
+        };
+        let link = chunk.first_vacant.is_none();
+        if link {
+            // First vacant slot of the chunk, link it into the chain of chunks with vacant slots.
+            chunk.next_vacant = self.first_vacant;
+        }
+        chunk.first_vacant = Some(pos as u32);
+        chunk.occupied -= 1;
+        let empty = chunk.occupied == 0;
+        self.occupied_count -= 1;
+
+        if link {
+            if let Some(next_idx) = self.first_vacant {
+                let next = expect_consistent_state(self.values.get_mut(next_idx));
+                next.prev_vacant = Some(chunk_idx);
+            }
+            self.first_vacant = Some(chunk_idx);
+        }
+        if empty {
+            self.occupied_chunks.set(chunk_idx, false);
+            self.truncate_empty();
+        }
+        Some(value)
+    }
+
+    /// Removes the chunks at the end of the list which have no occupied slot.
+    fn truncate_empty(&mut self) {
+        let mut count = chunk_count::<N>(self.next_index);
+        while count > 0 && !self.occupied_chunks.is_occupied(count - 1) {
+            count -= 1;
+            self.unlink_vacant(count);
+            self.values.set(count, None);
+        }
+        self.next_index = core::cmp::min(self.next_index, count * N as u32);
+    }
+
+    /// Unlinks the chunk at `chunk_idx` from the chain of chunks with vacant slots.
+    fn unlink_vacant(&mut self, chunk_idx: u32) {
+        let chunk = expect_consistent_state(self.values.get(chunk_idx));
+        let next_vacant = chunk.next_vacant;
+        let prev_vacant = chunk.prev_vacant;
+        if self.first_vacant == Some(chunk_idx) {
+            self.first_vacant = next_vacant;
+            return;
+        }
+        let prev = expect_consistent_state(prev_vacant.and_then(|i| self.values.get_mut(i)));
+        prev.next_vacant = next_vacant;
+        if let Some(next_idx) = next_vacant {
+            let next = expect_consistent_state(self.values.get_mut(next_idx));
+            next.prev_vacant = prev_vacant;
+        }
+    }
+
+    /// Returns a reference to the value at the index provided, or `None` if the slot is vacant.
+    pub fn get(&self, index: FreeListIndex) -> Option<&T> {
+        if index.0 >= self.next_index {
+            return None;
+        }
+
+        let chunk = self.values.get(chunk_index::<N>(index.0))?;
+        match chunk.slots.get(chunk_pos::<N>(index.0))? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value at the index provided, or `None` if the slot is
+    /// vacant.
+    pub fn get_mut(&mut self, index: FreeListIndex) -> Option<&mut T> {
+        if index.0 >= self.next_index {
+            return None;
+        }
+
+        let chunk = self.values.get_mut(chunk_index::<N>(index.0))?;
+        match chunk.slots.get_mut(chunk_pos::<N>(index.0))? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    /// Returns `true` if there is a value at the index provided.
+    pub fn contains(&self, index: FreeListIndex) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Returns an iterator over the occupied slots of the list, along with their indices. This
+    /// iterator will lazily load any chunks iterated over from storage.
+    ///
+    /// Chunks which have no occupied slots are skipped over without being loaded, and no chunks
+    /// are loaded once every value has been yielded.
+    pub fn iter(&self) -> Iter<'_, T, N, B> {
+        Iter::new(self)
+    }
+
+    /// Returns an iterator over the occupied slots of the list that allows modifying each
+    /// value. This iterator will lazily load any chunks iterated over from storage.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, N, B> {
+        IterMut::new(self)
+    }
+}
+
+impl<T, const N: usize, B> fmt::Debug for ChunkedFreeList<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if cfg!(feature = "expensive-debug") {
+            f.debug_map().entries(self.iter()).finish()
+        } else {
+            f.debug_struct("FreeList")
+                .field("len", &self.occupied_count)
+                .field("next_index", &self.next_index)
+                .field("first_vacant", &self.first_vacant)
+                .field("prefix", &self.values.prefix)
+                .finish()
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{RngCore, SeedableRng};
+    use std::collections::BTreeMap;
+
+    use super::{ChunkedFreeList, FreeListIndex};
+    use near_sdk::test_utils::test_env::setup_free;
+
+    #[test]
+    fn test_stable_indices() {
+        let mut list = ChunkedFreeList::<u32, 3>::new(b"f");
+        let indices: Vec<_> = (0..10).map(|i| list.insert(i)).collect();
+        for i in (0..10).step_by(2) {
+            assert_eq!(list.remove(indices[i]), Some(i as u32));
+        }
+        assert_eq!(list.len(), 5);
+        for i in (1..10).step_by(2) {
+            assert_eq!(list[indices[i]], i as u32);
+        }
+        assert!(Iterator::eq(
+            list.iter(),
+            (1..10u32)
+                .step_by(2)
+                .map(|i| (indices[i as usize], &list[indices[i as usize]]))
+        ));
+
+        // Vacant slots are reused before any new slots are allocated.
+        let mut reused: Vec<_> = (0..5).map(|i| list.insert(100 + i)).collect();
+        reused.sort();
+        assert_eq!(
+            reused,
+            indices.iter().copied().step_by(2).collect::<Vec<_>>()
+        );
+        assert_eq!(list.next_index, 10);
+        assert_eq!(list.insert(200), FreeListIndex(10));
+    }
+
+    #[test]
+    fn test_remove_vacant() {
+        let mut list = ChunkedFreeList::<u8>::new(b"f");
+        let index = list.insert(1);
+        assert_eq!(list.remove(index), Some(1));
+        assert_eq!(list.remove(index), None);
+        assert_eq!(list.remove(FreeListIndex(5)), None);
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_empty_chunks() {
+        let mut list = ChunkedFreeList::<u32, 2>::new(b"f");
+        let indices: Vec<_> = (0..10).map(|i| list.insert(i)).collect();
+        for &index in &indices[2..6] {
+            list.remove(index);
+        }
+        list.flush();
+
+        // Empty chunks are skipped without being loaded.
+        let serialized = list.try_to_vec().unwrap();
+        let mut list = ChunkedFreeList::<u32, 2>::deserialize(&mut serialized.as_slice()).unwrap();
+        assert!(Iterator::eq(
+            list.iter().map(|(_, v)| *v),
+            [0, 1, 6, 7, 8, 9]
+        ));
+        assert_eq!(list.values.cached_len(), 3);
+
+        // Empty chunks at the end are removed, and unlinked from the chain of vacant slots also
+        // when they are not the first chunk of it.
+        list.remove(indices[9]);
+        list.remove(indices[0]);
+        list.remove(indices[8]);
+        assert_eq!(list.next_index, 8);
+        for &index in &indices[6..8] {
+            list.remove(index);
+        }
+        assert_eq!(list.next_index, 2);
+        assert_eq!(list.insert(10), indices[0]);
+        assert_eq!(list.insert(11), FreeListIndex(2));
+        list.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert_eq!(m.take_storage().len(), 2));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut list = ChunkedFreeList::<u64>::new(b"f");
+        for i in 0..20 {
+            list.insert(i);
+        }
+        list.flush();
+        list.clear();
+        list.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[derive(Arbitrary, Debug)]
+    enum Op {
+        Insert(u8),
+        Remove(u8),
+        Flush,
+        Reset,
+        Get(u8),
+        Set(u8, u8),
+        Clear,
+    }
+
+    #[test]
+    fn arbitrary() {
+        setup_free();
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut buf = vec![0; 4096];
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 1024 };
+        for _ in 0..runs {
+            // Clear storage in-between runs
+            near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+            rng.fill_bytes(&mut buf);
+
+            let mut sl = ChunkedFreeList::<_, 3>::new(b"f");
+            let mut ml = BTreeMap::new();
+            let u = Unstructured::new(&buf);
+            if let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) {
+                for op in ops {
+                    match op {
+                        Op::Insert(v) => {
+                            let index = sl.insert(v);
+                            assert!(ml.insert(index, v).is_none());
+                        }
+                        Op::Remove(i) => {
+                            let index = FreeListIndex(i as u32);
+                            assert_eq!(sl.remove(index), ml.remove(&index));
+                        }
+                        Op::Flush => {
+                            sl.flush();
+                        }
+                        Op::Reset => {
+                            let serialized = sl.try_to_vec().unwrap();
+                            sl = ChunkedFreeList::deserialize(&mut serialized.as_slice()).unwrap();
+                        }
+                        Op::Get(i) => {
+                            let index = FreeListIndex(i as u32);
+                            assert_eq!(sl.get(index), ml.get(&index));
+                        }
+                        Op::Set(i, v) => {
+                            let index = FreeListIndex(i as u32);
+                            if let Some(value) = sl.get_mut(index) {
+                                *value = v;
+                            }
+                            if let Some(value) = ml.get_mut(&index) {
+                                *value = v;
+                            }
+                        }
+                        Op::Clear => {
+                            sl.clear();
+                            ml.clear();
+                        }
+                    }
+                    assert_eq!(sl.len() as usize, ml.len());
+                }
+            }
+
+            // After all operations, compare both lists
+            assert!(Iterator::eq(sl.iter(), ml.iter().map(|(k, v)| (*k, v))));
+            for (_, value) in sl.iter_mut() {
+                *value = value.wrapping_add(1);
+            }
+            assert!(Iterator::eq(
+                sl.iter().map(|(k, v)| (k, *v)),
+                ml.iter().map(|(k, v)| (*k, v.wrapping_add(1)))
+            ));
+        }
+    }
+}
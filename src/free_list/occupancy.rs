@@ -0,0 +1,46 @@
+use alloc::vec::Vec;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+/// Bitmap of the chunks of a free list which have an occupied slot. It is kept in the root of
+/// the list, so that chunks without any occupied slot are skipped without being loaded.
+#[derive(Debug, Default, BorshSerialize, BorshDeserialize)]
+pub(crate) struct ChunkOccupancy {
+    /// Bits of the chunks, ordered from the least significant bit of each byte.
+    bits: Vec<u8>,
+}
+
+impl ChunkOccupancy {
+    /// Returns `true` if the chunk at `chunk_idx` has an occupied slot.
+    pub(crate) fn is_occupied(&self, chunk_idx: u32) -> bool {
+        self.bits
+            .get((chunk_idx / 8) as usize)
+            .is_some_and(|byte| byte & (1 << (chunk_idx % 8)) != 0)
+    }
+
+    /// Marks whether the chunk at `chunk_idx` has an occupied slot.
+    pub(crate) fn set(&mut self, chunk_idx: u32, occupied: bool) {
+        let byte_idx = (chunk_idx / 8) as usize;
+        if byte_idx >= self.bits.len() {
+            if !occupied {
+                return;
+            }
+            self.bits.resize(byte_idx + 1, 0);
+        }
+        let mask = 1 << (chunk_idx % 8);
+        if occupied {
+            self.bits[byte_idx] |= mask;
+        } else {
+            self.bits[byte_idx] &= !mask;
+            // Trailing bytes without any occupied chunk are not persisted.
+            while self.bits.last() == Some(&0) {
+                self.bits.pop();
+            }
+        }
+    }
+
+    /// Marks every chunk as empty.
+    pub(crate) fn clear(&mut self) {
+        self.bits.clear();
+    }
+}
@@ -0,0 +1,87 @@
+use borsh::maybestd::io::{Error, Write};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::chunk::Chunk;
+
+/// A single slot of a free list, which either holds a value or links to the next vacant slot.
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub(crate) enum Slot<T> {
+    /// Slot holds a value.
+    Occupied(T),
+    /// Slot is free. Holds the position of the next vacant slot within the same chunk.
+    Vacant(Option<u32>),
+}
+
+/// Chunk of up to `N` free list slots, along with the intrusive links of the free slot chain.
+///
+/// Vacant slots within a chunk are linked together starting from `first_vacant`, and every
+/// chunk which has a vacant slot is linked to the next through `next_vacant`. This means that
+/// finding a free slot only requires loading the first chunk of the chain. Chunks also link to
+/// the previous chunk of the chain through `prev_vacant`, so that a chunk can be unlinked from
+/// the middle of the chain when it is removed.
+///
+/// # Storage format
+///
+/// The chunk is serialized as `next_vacant`, `prev_vacant` and `first_vacant` as Borsh encoded
+/// `Option<u32>`s, followed by the slots in the same layout as a [`Chunk`].
+#[derive(Debug)]
+pub(crate) struct SlotChunk<T, const N: usize> {
+    /// Index of the next chunk which has a vacant slot.
+    pub(crate) next_vacant: Option<u32>,
+    /// Index of the previous chunk which has a vacant slot. This is not kept up to date for the
+    /// first chunk of the chain, which has no previous chunk.
+    pub(crate) prev_vacant: Option<u32>,
+    /// Position of the first vacant slot in this chunk.
+    pub(crate) first_vacant: Option<u32>,
+    /// Number of occupied slots. This is not serialized, it is computed when the chunk is loaded.
+    pub(crate) occupied: u32,
+    pub(crate) slots: Chunk<Slot<T>, N>,
+}
+
+impl<T, const N: usize> SlotChunk<T, N> {
+    /// Creates a chunk with a single occupied slot.
+    pub(crate) fn with_first(value: T) -> Self {
+        Self {
+            next_vacant: None,
+            prev_vacant: None,
+            first_vacant: None,
+            occupied: 1,
+            slots: Chunk::with_first(Slot::Occupied(value)),
+        }
+    }
+}
+
+impl<T, const N: usize> BorshSerialize for SlotChunk<T, N>
+where
+    T: BorshSerialize,
+{
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        BorshSerialize::serialize(&self.next_vacant, writer)?;
+        BorshSerialize::serialize(&self.prev_vacant, writer)?;
+        BorshSerialize::serialize(&self.first_vacant, writer)?;
+        BorshSerialize::serialize(&self.slots, writer)
+    }
+}
+
+impl<T, const N: usize> BorshDeserialize for SlotChunk<T, N>
+where
+    T: BorshDeserialize,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, Error> {
+        let next_vacant = BorshDeserialize::deserialize(buf)?;
+        let prev_vacant = BorshDeserialize::deserialize(buf)?;
+        let first_vacant = BorshDeserialize::deserialize(buf)?;
+        let slots: Chunk<Slot<T>, N> = BorshDeserialize::deserialize(buf)?;
+        let occupied = slots
+            .iter()
+            .filter(|slot| matches!(slot, Slot::Occupied(_)))
+            .count() as u32;
+        Ok(Self {
+            next_vacant,
+            prev_vacant,
+            first_vacant,
+            occupied,
+            slots,
+        })
+    }
+}
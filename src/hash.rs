@@ -0,0 +1,52 @@
+//! Hash functions used to group keys of hashed collections into buckets.
+//!
+//! The hash of a key's Borsh serialized bytes determines which bucket it is stored in, so the
+//! hasher must not change after values are persisted. With the `near` feature enabled, [`Sha256`]
+//! is used by default so that keys cannot be crafted to all land in the same bucket.
+//! [`Fnv1a`] is the default without it, which is cheap but should only be used when keys are not
+//! controlled by untrusted parties.
+
+/// Hash function used to determine the bucket a key is stored in.
+pub trait KeyHasher {
+    /// Hashes the serialized bytes of a key.
+    fn hash(bytes: &[u8]) -> u64;
+}
+
+/// Uses the first 8 bytes of the SHA-256 hash of the key, computed through the NEAR host
+/// function.
+#[cfg(feature = "near")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "near")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sha256 {}
+
+#[cfg(feature = "near")]
+impl KeyHasher for Sha256 {
+    fn hash(bytes: &[u8]) -> u64 {
+        let hash = near_sdk::env::sha256_array(bytes);
+        let mut prefix = [0; 8];
+        prefix.copy_from_slice(&hash[..8]);
+        u64::from_be_bytes(prefix)
+    }
+}
+
+/// 64-bit FNV-1a hash. This is not resistant to collisions being crafted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fnv1a {}
+
+impl KeyHasher for Fnv1a {
+    fn hash(bytes: &[u8]) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0100_0000_01b3;
+        bytes.iter().fold(OFFSET_BASIS, |hash, b| {
+            (hash ^ *b as u64).wrapping_mul(PRIME)
+        })
+    }
+}
+
+/// Hasher used by collections when none is specified.
+#[cfg(feature = "near")]
+pub type DefaultHasher = Sha256;
+
+/// Hasher used by collections when none is specified.
+#[cfg(not(feature = "near"))]
+pub type DefaultHasher = Fnv1a;
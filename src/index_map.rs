@@ -0,0 +1,685 @@
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::cell::{Cell, OnceCell, RefCell};
+use core::fmt;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::storage::{IntoStorageKey, StorageBackend};
+use crate::utils::{panic_str, CacheEntry, EntryState, Queue, StableMap, Stored};
+
+const ERR_ELEMENT_DESERIALIZATION: &str = "Cannot deserialize element";
+const ERR_ELEMENT_SERIALIZATION: &str = "Cannot serialize element";
+
+/// Key of a value in an [`IndexMap`], which is appended to the prefix to build the storage key.
+pub(crate) trait MapKey: Ord + Copy {
+    /// Maximum number of bytes the key is encoded as.
+    const MAX_LEN: usize;
+
+    /// Appends the encoded key to the buffer.
+    fn append_to(&self, buf: &mut Vec<u8>);
+}
+
+impl MapKey for u32 {
+    const MAX_LEN: usize = 4;
+
+    fn append_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+/// A mapping of `K` -> `T` in storage, which caches loaded and modified values until flushed.
+/// Values are keyed by `u32` indices unless specified otherwise.
+///
+/// Values keep the bytes they were stored as from their first mutable access until they are
+/// flushed, so that modified values which serialize to the same bytes are not written again.
+/// Values which are only read keep no bytes.
+///
+/// Values are kept in the cache until flushed unless a capacity is set, in which case the least
+/// recently used values are written to storage if modified and evicted once the cache holds
+/// more values than the capacity. Values are only evicted through a mutable reference to the
+/// map, so no reference handed out through a shared reference can be alive. Values referenced
+/// through [`get_mut_borrowed`](Self::get_mut_borrowed) are kept until the map is accessed
+/// mutably through another method, and values pinned through [`get_pinned`](Self::get_pinned)
+/// or [`get_mut_pinned`](Self::get_mut_pinned) are kept until unpinned. The capacity is only
+/// set by [`ChunkedVector`](crate::ChunkedVector), the other collections cache every value they
+/// load until flushed.
+///
+/// Cached values are queued in the order they were last used, so that the least recently used
+/// value is found without going through the cache. Values found to be referenced when they are
+/// about to be evicted are held out of the queue until they are released.
+pub(crate) struct IndexMap<T, B, K = u32>
+where
+    T: BorshSerialize,
+{
+    pub(crate) prefix: Box<[u8]>,
+    /// Cache for loads and intermediate changes to the underlying index map.
+    /// The cached entries are wrapped in a [`Box`] to avoid existing pointers from being
+    /// invalidated.
+    ///
+    /// Note: u32 indices are used over usize to have consistent functionality across architectures.
+    /// Some functionality would be different from tests to Wasm if exceeding 32-bit length.
+    cache: StableMap<K, OnceCell<CacheEntry<T>>>,
+    /// Bytes of values which were patched in place through [`raw_mut`](Self::raw_mut) rather
+    /// than loaded, to be written on flush. Values are moved to `cache` when loaded.
+    raw: RefCell<BTreeMap<K, Vec<u8>>>,
+    /// Backend that values are read from and written to.
+    pub(crate) storage: B,
+    /// Maximum number of values to keep in the cache, if any.
+    capacity: Option<u32>,
+    /// Order in which cached values are evicted, only maintained when a capacity is set.
+    lru: RefCell<Lru<K>>,
+    /// Incremented on every access, to find the least recently used value.
+    tick: Cell<u64>,
+    /// Incremented on every mutable access other than through the methods which keep values
+    /// referenced or pinned, after which none of the references handed out before can be alive.
+    epoch: u64,
+}
+
+impl<T, B, K> IndexMap<T, B, K>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+    K: MapKey,
+{
+    /// Create new index map. This creates a mapping of `K` -> `T` in the storage provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    pub(crate) fn new_in<S>(prefix: S, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            prefix: prefix.into_storage_key().into_boxed_slice(),
+            cache: Default::default(),
+            raw: Default::default(),
+            storage,
+            capacity: None,
+            lru: Default::default(),
+            tick: Cell::new(0),
+            epoch: 1,
+        }
+    }
+
+    fn index_to_lookup_key(prefix: &[u8], index: K, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(prefix);
+        index.append_to(buf);
+    }
+
+    /// Starts a new epoch, after which no reference handed out before can be alive, so held
+    /// values are queued again.
+    pub(crate) fn next_epoch(&mut self) {
+        self.epoch += 1;
+        let Self { cache, lru, .. } = self;
+        let lru = lru.get_mut();
+        for k in core::mem::take(&mut lru.held) {
+            if let Some(entry) = cache.inner().get(&k).and_then(|v| v.get()) {
+                lru.enqueue(k, entry);
+            }
+        }
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    pub(crate) fn flush(&mut self) {
+        self.next_epoch();
+        let mut buf = Vec::new();
+        // Capacity is prefix length plus bytes needed for the key (4*u8 for u32 indices)
+        let mut key_buf = Vec::with_capacity(self.prefix.len() + K::MAX_LEN);
+        for (k, bytes) in core::mem::take(self.raw.get_mut()) {
+            key_buf.clear();
+            Self::index_to_lookup_key(&self.prefix, k, &mut key_buf);
+            self.storage.write(&key_buf, &bytes);
+        }
+        for (k, v) in self.cache.inner().iter_mut() {
+            if let Some(v) = v.get_mut() {
+                Self::write_entry(
+                    &self.prefix,
+                    &mut self.storage,
+                    *k,
+                    v,
+                    &mut buf,
+                    &mut key_buf,
+                );
+            }
+        }
+    }
+
+    /// Writes the value of the entry to storage if it has been modified.
+    fn write_entry(
+        prefix: &[u8],
+        storage: &mut B,
+        k: K,
+        v: &mut CacheEntry<T>,
+        buf: &mut Vec<u8>,
+        key_buf: &mut Vec<u8>,
+    ) {
+        if !v.is_modified() {
+            return;
+        }
+        key_buf.clear();
+        Self::index_to_lookup_key(prefix, k, key_buf);
+        match v.value().as_ref() {
+            Some(modified) => {
+                buf.clear();
+                BorshSerialize::serialize(modified, buf)
+                    .unwrap_or_else(|_| panic_str(ERR_ELEMENT_SERIALIZATION));
+                // Values which were only accessed mutably, or changed back, serialize to the
+                // bytes already in storage and are not written again.
+                if !matches!(v.stored(), Stored::Bytes(stored) if **stored == **buf) {
+                    storage.write(key_buf, buf);
+                }
+            }
+            None => {
+                // Element was removed, clear the storage for the value
+                if *v.stored() != Stored::Absent {
+                    storage.remove(key_buf);
+                }
+            }
+        }
+
+        // Update state of flushed state as cached, to avoid duplicate writes/removes
+        // while also keeping the cached values in memory.
+        v.mark_written();
+    }
+
+    /// Sets the maximum number of values kept in the cache, or `None` for no limit. Values over
+    /// the capacity are evicted right away.
+    pub(crate) fn set_capacity(&mut self, capacity: Option<u32>) {
+        self.next_epoch();
+        self.capacity = capacity;
+        self.requeue();
+        self.evict(None);
+    }
+
+    /// Queues all cached values again in the order they were last used, or clears the queues if
+    /// no capacity is set.
+    fn requeue(&mut self) {
+        let Self {
+            cache, lru, tick, ..
+        } = self;
+        let lru = lru.get_mut();
+        *lru = Lru::default();
+        let mut entries: Vec<_> = cache
+            .inner()
+            .iter()
+            .filter_map(|(k, v)| v.get().map(|entry| (*k, entry)))
+            .collect();
+        if self.capacity.is_none() {
+            for (_, entry) in entries {
+                entry.usage().set_queue(Queue::None);
+            }
+            return;
+        }
+        entries.sort_by_key(|(_, entry)| entry.usage().last_used());
+        for (k, entry) in entries {
+            // Values which were never used share the same tick, so they are all used again.
+            tick.set(tick.get() + 1);
+            entry.usage().touch(tick.get());
+            lru.enqueue(k, entry);
+        }
+    }
+
+    /// Returns the maximum number of values kept in the cache, if any.
+    pub(crate) fn capacity(&self) -> Option<u32> {
+        self.capacity
+    }
+
+    /// Returns the number of values held in the cache, including values patched in place.
+    pub(crate) fn cached_len(&self) -> u32 {
+        let loaded = self
+            .cache
+            .with_inner(|map| map.values().filter(|v| v.get().is_some()).count());
+        (loaded + self.raw.borrow().len()) as u32
+    }
+
+    /// Returns the number of entries counted against the capacity.
+    fn held_len(&self) -> usize {
+        self.cache.len() + self.raw.borrow().len()
+    }
+
+    /// Marks the entry at `index` as used, returning it.
+    fn touch<'a>(&self, index: K, entry: &'a CacheEntry<T>) -> &'a CacheEntry<T> {
+        let tick = self.tick.get() + 1;
+        self.tick.set(tick);
+        if self.capacity.is_some() {
+            let mut lru = self.lru.borrow_mut();
+            lru.dequeue(index, entry);
+            entry.usage().touch(tick);
+            lru.enqueue(index, entry);
+        } else {
+            entry.usage().touch(tick);
+        }
+        entry
+    }
+
+    /// Writes modified values to storage and evicts them, least recently used first, until the
+    /// cache is within capacity. Values patched in place are written first. Values referenced in
+    /// the current epoch are kept.
+    fn evict(&mut self, except: Option<K>) {
+        let capacity = match self.capacity {
+            Some(capacity) => capacity as usize,
+            None => return,
+        };
+        let mut buf = Vec::new();
+        let mut key_buf = Vec::with_capacity(self.prefix.len() + K::MAX_LEN);
+        while self.held_len() > capacity {
+            let key = match self.raw.get_mut().keys().find(|k| Some(**k) != except) {
+                Some(key) => *key,
+                None => break,
+            };
+            let bytes = expect_initialized(self.raw.get_mut().remove(&key));
+            key_buf.clear();
+            Self::index_to_lookup_key(&self.prefix, key, &mut key_buf);
+            self.storage.write(&key_buf, &bytes);
+        }
+        while self.held_len() > capacity {
+            let lru = self.lru.get_mut();
+            let (tick, key) = match lru.queue.pop_first() {
+                Some(next) => next,
+                None => break,
+            };
+            let entry = match self.cache.inner().get(&key).and_then(|v| v.get()) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let usage = entry.usage();
+            if usage.queue() != Queue::Queued || usage.last_used() != tick {
+                // Stale position of a value which has been used again since.
+                continue;
+            }
+            usage.set_queue(Queue::None);
+            if Some(key) == except || usage.is_referenced(self.epoch) {
+                lru.hold(key, entry);
+                continue;
+            }
+            if let Some(mut entry) = self.remove_entry(key) {
+                let storage = &mut self.storage;
+                Self::write_entry(
+                    &self.prefix,
+                    storage,
+                    key,
+                    &mut entry,
+                    &mut buf,
+                    &mut key_buf,
+                );
+            }
+        }
+    }
+
+    /// Returns mutable references to the cached values which have been modified since the last
+    /// flush, to update them before they are written.
+    pub(crate) fn modified_values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cache
+            .inner()
+            .values_mut()
+            .filter_map(|v| v.get_mut())
+            .filter(|v| v.is_modified())
+            .filter_map(|v| v.value_mut().as_mut())
+    }
+
+    /// Returns `true` if the value at `index` has been loaded or set, in which case the cached
+    /// value has to be used rather than the bytes in storage.
+    pub(crate) fn is_loaded(&self, index: K) -> bool {
+        self.cache
+            .with_inner(|map| map.get(&index).is_some_and(|v| v.get().is_some()))
+    }
+
+    /// Calls `f` with the bytes of the value at `index`, either as patched in place or as read
+    /// from storage. This must only be used for values which are not loaded.
+    pub(crate) fn with_raw<R, F>(&self, index: K, f: F) -> R
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        debug_assert!(!self.is_loaded(index));
+        if let Some(bytes) = self.raw.borrow().get(&index) {
+            return f(Some(bytes));
+        }
+        let mut key = Vec::with_capacity(self.prefix.len() + K::MAX_LEN);
+        Self::index_to_lookup_key(&self.prefix, index, &mut key);
+        f(self.storage.read(&key).as_deref())
+    }
+
+    /// Returns the bytes of the value at `index` to patch them in place, or `None` if there is
+    /// no value. The bytes are kept in the cache and written on flush or eviction, unless the
+    /// value is loaded first, in which case it is deserialized from them. This must only be used
+    /// for values which are not loaded.
+    pub(crate) fn raw_mut(&mut self, index: K) -> Option<&mut Vec<u8>> {
+        debug_assert!(!self.is_loaded(index));
+        if !self.raw.get_mut().contains_key(&index) {
+            let mut key = Vec::with_capacity(self.prefix.len() + K::MAX_LEN);
+            Self::index_to_lookup_key(&self.prefix, index, &mut key);
+            let bytes = self.storage.read(&key)?;
+            self.raw.get_mut().insert(index, bytes);
+            self.evict(Some(index));
+        }
+        self.raw.get_mut().get_mut(&index)
+    }
+
+    /// Moves the value at `index` to `to_index` of `other`, removing it from this map.
+    ///
+    /// If the value has not been loaded, its bytes are copied to the storage of `other` right
+    /// away rather than being deserialized and written on flush. Any value cached by `other`
+    /// at `to_index` is discarded, as it is overwritten.
+    pub(crate) fn move_to<C>(&mut self, index: K, other: &mut IndexMap<T, C, K>, to_index: K)
+    where
+        C: StorageBackend,
+    {
+        if let Some(mut entry) = self.remove_entry(index) {
+            other.set(to_index, entry.replace(None));
+        } else {
+            let mut key = Vec::with_capacity(self.prefix.len() + K::MAX_LEN);
+            Self::index_to_lookup_key(&self.prefix, index, &mut key);
+            let mut to_key = Vec::with_capacity(other.prefix.len() + K::MAX_LEN);
+            Self::index_to_lookup_key(&other.prefix, to_index, &mut to_key);
+
+            other.remove_entry(to_index);
+            other.raw.get_mut().remove(&to_index);
+            match self.raw.get_mut().remove(&index) {
+                Some(bytes) => other.storage.write(&to_key, &bytes),
+                None => match self.storage.read(&key) {
+                    Some(bytes) => other.storage.write(&to_key, &bytes),
+                    None => other.storage.remove(&to_key),
+                },
+            }
+        }
+        // Value is removed from storage when flushed.
+        self.cache
+            .get_mut(index)
+            .get_or_init(|| CacheEntry::new_modified(None));
+        self.touch(index, expect_initialized(self.cache.get(index).get()));
+    }
+
+    /// Removes the entry at `index` from the cache, along with its position in the queues.
+    fn remove_entry(&mut self, index: K) -> Option<CacheEntry<T>> {
+        let entry = self.cache.remove(&index).and_then(OnceCell::into_inner)?;
+        self.lru.get_mut().dequeue(index, &entry);
+        Some(entry)
+    }
+
+    /// Sets a value at a given index to the value provided. If none is provided, this index will
+    /// be removed from storage.
+    pub(crate) fn set(&mut self, index: K, value: Option<T>) {
+        self.next_epoch();
+        self.raw.get_mut().remove(&index);
+        let entry = self.cache.get_mut(index);
+        match entry.get_mut() {
+            Some(entry) => *entry.value_mut() = value,
+            None => {
+                let _ = entry.set(CacheEntry::new_modified(value));
+            }
+        }
+        self.touch(index, expect_initialized(self.cache.get(index).get()));
+        self.evict(Some(index));
+    }
+
+    /// Sets the value at an index which has no value in storage, such as past the end of a
+    /// collection, so that nothing is removed from storage if it is removed before a flush.
+    pub(crate) fn set_vacant(&mut self, index: K, value: Option<T>) {
+        let entry = self.cache.get_mut(index);
+        if entry.get().is_none() {
+            let _ = entry.set(CacheEntry::new_vacant(None));
+        }
+        self.set(index, value);
+    }
+}
+
+impl<T, B, K> IndexMap<T, B, K>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+    K: MapKey,
+{
+    fn deserialize_element(raw_element: &[u8]) -> T {
+        T::try_from_slice(raw_element).unwrap_or_else(|_| panic_str(ERR_ELEMENT_DESERIALIZATION))
+    }
+
+    /// Loads the value at `index` from the bytes patched in place or from storage. If
+    /// `keep_bytes` is set, the entry keeps the bytes it was loaded from storage, as it is about
+    /// to be accessed mutably.
+    fn load(
+        prefix: &[u8],
+        storage: &B,
+        raw: &RefCell<BTreeMap<K, Vec<u8>>>,
+        index: K,
+        keep_bytes: bool,
+    ) -> CacheEntry<T> {
+        if let Some(bytes) = raw.borrow_mut().remove(&index) {
+            return CacheEntry::new_modified(Some(Self::deserialize_element(&bytes)));
+        }
+        let mut key = Vec::with_capacity(prefix.len() + K::MAX_LEN);
+        Self::index_to_lookup_key(prefix, index, &mut key);
+        let storage_bytes = storage.read(&key);
+        let value = storage_bytes.as_deref().map(Self::deserialize_element);
+        let mut entry = CacheEntry::new(value, EntryState::Cached);
+        if keep_bytes {
+            entry.set_stored(match storage_bytes {
+                Some(bytes) => Stored::Bytes(bytes.into_boxed_slice()),
+                None => Stored::Absent,
+            });
+        }
+        entry
+    }
+
+    /// Keeps the bytes of an unmodified value before it is accessed mutably, unless they are
+    /// already known.
+    fn snapshot(entry: &mut CacheEntry<T>) {
+        if entry.is_modified() || *entry.stored() != Stored::Unknown {
+            return;
+        }
+        let stored = match entry.value() {
+            Some(value) => {
+                let mut buf = Vec::new();
+                BorshSerialize::serialize(value, &mut buf)
+                    .unwrap_or_else(|_| panic_str(ERR_ELEMENT_SERIALIZATION));
+                Stored::Bytes(buf.into_boxed_slice())
+            }
+            None => Stored::Absent,
+        };
+        entry.set_stored(stored);
+    }
+
+    /// Loads the entry at `index` if it is not cached, and marks it as used.
+    fn get_entry(&self, index: K) -> &CacheEntry<T> {
+        let cell = self.cache.get(index);
+        cell.get_or_init(|| Self::load(&self.prefix, &self.storage, &self.raw, index, false));
+        // The reference returned when initializing is derived from a unique borrow of the cell,
+        // which would be invalidated by the usage of the entry being updated through the map.
+        self.touch(index, expect_initialized(cell.get()))
+    }
+
+    /// Returns the element by index or `None` if it is not present.
+    ///
+    /// The value may exceed the capacity of the cache until the map is accessed mutably, as
+    /// values are only evicted through a mutable reference.
+    pub(crate) fn get(&self, index: K) -> Option<&T> {
+        self.get_entry(index).value().as_ref()
+    }
+
+    /// Returns the element by index or `None` if it is not present, pinning it in the cache so
+    /// that it is not evicted until [`unpin`](Self::unpin) is called for it. Unlike
+    /// [`get`](Self::get), other values are evicted if the cache is over capacity, other than
+    /// the values pinned or referenced since the last epoch.
+    pub(crate) fn get_pinned(&mut self, index: K) -> Option<&T> {
+        let epoch = self.epoch;
+        let entry = self.load_mut(index, false);
+        entry.usage().pin(epoch);
+        entry.value().as_ref()
+    }
+
+    /// Releases a pin on the value at `index` from [`get_pinned`](Self::get_pinned) or
+    /// [`get_mut_pinned`](Self::get_mut_pinned). The reference returned with it must not be
+    /// used anymore.
+    pub(crate) fn unpin(&self, index: K) {
+        self.cache.with_inner(|map| {
+            let entry = match map.get(&index).and_then(|v| v.get()) {
+                Some(entry) => entry,
+                None => return,
+            };
+            let usage = entry.usage();
+            usage.unpin(self.epoch);
+            if usage.queue() == Queue::Held && !usage.is_referenced(self.epoch) {
+                let mut lru = self.lru.borrow_mut();
+                lru.dequeue(index, entry);
+                lru.enqueue(index, entry);
+            }
+        });
+    }
+
+    /// Returns a mutable reference to the element at the `index` provided.
+    fn get_mut_inner(&mut self, index: K) -> &mut CacheEntry<T> {
+        // References handed out before can't be alive anymore.
+        self.next_epoch();
+        self.load_mut(index, true)
+    }
+
+    /// Loads the entry at `index` if it is not cached, marks it as used and evicts other values
+    /// if the cache is over capacity. If `keep_bytes` is set, the bytes read are kept as the
+    /// value is about to be accessed mutably.
+    fn load_mut(&mut self, index: K, keep_bytes: bool) -> &mut CacheEntry<T> {
+        let Self {
+            prefix,
+            cache,
+            raw,
+            storage,
+            ..
+        } = self;
+        cache
+            .get_mut(index)
+            .get_or_init(|| Self::load(prefix, storage, raw, index, keep_bytes));
+        self.touch(index, expect_initialized(self.cache.get(index).get()));
+        self.evict(Some(index));
+        expect_initialized(self.cache.get_mut(index).get_mut())
+    }
+
+    /// Returns a mutable reference to the element at the `index` provided.
+    pub(crate) fn get_mut(&mut self, index: K) -> Option<&mut T> {
+        let entry = self.get_mut_inner(index);
+        Self::snapshot(entry);
+        entry.value_mut().as_mut()
+    }
+
+    /// Returns a mutable reference to the element at the `index` provided, which is not evicted
+    /// from the cache until the map is accessed mutably through another method. This allows
+    /// holding on to references to multiple values, which must not overlap.
+    pub(crate) fn get_mut_borrowed(&mut self, index: K) -> Option<&mut T> {
+        let epoch = self.epoch;
+        let entry = self.load_mut(index, true);
+        entry.usage().borrow(epoch);
+        Self::snapshot(entry);
+        entry.value_mut().as_mut()
+    }
+
+    /// Returns a mutable reference to the element at the `index` provided, pinning it in the
+    /// cache like [`get_pinned`](Self::get_pinned). Unlike [`get_mut`](Self::get_mut), values
+    /// pinned or referenced since the last epoch stay pinned or referenced, so references to
+    /// multiple values can be held on to, which must not overlap.
+    pub(crate) fn get_mut_pinned(&mut self, index: K) -> Option<&mut T> {
+        let epoch = self.epoch;
+        let entry = self.load_mut(index, true);
+        entry.usage().pin(epoch);
+        Self::snapshot(entry);
+        entry.value_mut().as_mut()
+    }
+
+    /// Removes value at index and returns existing value.
+    pub(crate) fn remove(&mut self, index: K) -> Option<T> {
+        self.get_mut_inner(index).replace(None)
+    }
+}
+
+/// Queue of cached values in the order they were last used, from which values are evicted.
+struct Lru<K> {
+    /// Values keyed by the tick they were last used at.
+    queue: BTreeMap<u64, K>,
+    /// Values which were referenced when they were about to be evicted.
+    held: BTreeSet<K>,
+}
+
+impl<K> Default for Lru<K> {
+    fn default() -> Self {
+        Self {
+            queue: BTreeMap::new(),
+            held: BTreeSet::new(),
+        }
+    }
+}
+
+impl<K: MapKey> Lru<K> {
+    /// Queues the value by the tick it was last used at, which must not be queued.
+    fn enqueue<T>(&mut self, k: K, entry: &CacheEntry<T>) {
+        let usage = entry.usage();
+        self.queue.insert(usage.last_used(), k);
+        usage.set_queue(Queue::Queued);
+    }
+
+    /// Holds the value out of the queues until it is released, as it is referenced.
+    fn hold<T>(&mut self, k: K, entry: &CacheEntry<T>) {
+        self.held.insert(k);
+        entry.usage().set_queue(Queue::Held);
+    }
+
+    /// Removes the value from the queue it is in, if any.
+    fn dequeue<T>(&mut self, k: K, entry: &CacheEntry<T>) {
+        let usage = entry.usage();
+        match usage.queue() {
+            Queue::None => {}
+            Queue::Queued => {
+                self.queue.remove(&usage.last_used());
+            }
+            Queue::Held => {
+                self.held.remove(&k);
+            }
+        }
+        usage.set_queue(Queue::None);
+    }
+}
+
+fn expect_initialized<T>(val: Option<T>) -> T {
+    val.unwrap_or_else(|| panic_str("cache entry not initialized"))
+}
+
+//? Only the prefix is serialized, the backend is recreated through `Default`.
+impl<T, B, K> BorshSerialize for IndexMap<T, B, K>
+where
+    T: BorshSerialize,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.prefix, writer)
+    }
+}
+
+impl<T, B, K> BorshDeserialize for IndexMap<T, B, K>
+where
+    T: BorshSerialize,
+    B: Default,
+    K: Ord,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            prefix: BorshDeserialize::deserialize(buf)?,
+            cache: Default::default(),
+            raw: Default::default(),
+            storage: B::default(),
+            capacity: None,
+            lru: Default::default(),
+            tick: Cell::new(0),
+            epoch: 1,
+        })
+    }
+}
+
+impl<T, B, K> fmt::Debug for IndexMap<T, B, K>
+where
+    T: BorshSerialize,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IndexMap")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
@@ -0,0 +1,244 @@
+use alloc::vec::Vec;
+use core::cell::OnceCell;
+use core::ops::Range;
+
+use borsh::maybestd::io::{Error, ErrorKind, Write};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::utils::{expect_consistent_state, panic_str};
+
+const ERR_CHUNK_OVERFLOW: &str = "chunk length exceeds chunk size";
+const ERR_INVALID_OFFSETS: &str = "chunk element offsets are not in order";
+const ERR_ELEMENT_DESERIALIZATION: &str = "Cannot deserialize element";
+
+/// Element of a [`LazyChunk`], which is only deserialized when first accessed.
+#[derive(Debug)]
+struct LazyElement<T> {
+    value: OnceCell<T>,
+    /// Whether the value has been accessed mutably, so its encoded bytes are outdated.
+    dirty: bool,
+}
+
+impl<T> LazyElement<T> {
+    fn encoded() -> Self {
+        Self {
+            value: OnceCell::new(),
+            dirty: false,
+        }
+    }
+
+    fn dirty(value: T) -> Self {
+        Self {
+            value: OnceCell::from(value),
+            dirty: true,
+        }
+    }
+}
+
+/// A group of up to `N` elements stored under a single storage key, which keeps the encoded
+/// bytes of its elements and only deserializes an element when it is accessed.
+///
+/// Elements accessed mutably are marked dirty. When the chunk is written, only the dirty
+/// elements are serialized again, and the bytes of the other elements are copied as is.
+///
+/// # Storage format
+///
+/// The chunk is serialized as its length as a little-endian `u32`, followed by the end offset of
+/// each element as a little-endian `u32`, and then the encoded elements one after the other.
+/// Offsets are relative to the start of the encoded elements, so element `i` is encoded in the
+/// bytes between the end offsets of elements `i - 1` and `i`.
+#[derive(Debug)]
+pub(crate) struct LazyChunk<T, const N: usize> {
+    /// Encoded elements which are not dirty, as loaded from storage or last spliced.
+    bytes: Vec<u8>,
+    /// End offset within `bytes` of the first encoded elements. Elements after these have been
+    /// pushed and are always dirty.
+    ends: Vec<u32>,
+    elements: Vec<LazyElement<T>>,
+}
+
+impl<T, const N: usize> LazyChunk<T, N> {
+    /// Creates a chunk with a single element.
+    pub(crate) fn with_first(element: T) -> Self {
+        let mut elements = Vec::with_capacity(N);
+        elements.push(LazyElement::dirty(element));
+        Self {
+            bytes: Vec::new(),
+            ends: Vec::new(),
+            elements,
+        }
+    }
+
+    /// Returns `true` if the chunk contains no elements.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Appends an element to the end of the chunk.
+    pub(crate) fn push(&mut self, element: T) {
+        debug_assert!(self.elements.len() < N, "{}", ERR_CHUNK_OVERFLOW);
+        self.elements.push(LazyElement::dirty(element));
+    }
+
+    /// Range within `bytes` of the encoded element at `pos`, which must be below the length of
+    /// `ends`.
+    fn range(&self, pos: usize) -> Range<usize> {
+        let start = pos
+            .checked_sub(1)
+            .map_or(0, |prev| self.ends[prev] as usize);
+        start..self.ends[pos] as usize
+    }
+}
+
+impl<T, const N: usize> LazyChunk<T, N>
+where
+    T: BorshDeserialize,
+{
+    fn decode(&self, pos: usize) -> T {
+        let bytes = expect_consistent_state(self.bytes.get(self.range(pos)));
+        T::try_from_slice(bytes).unwrap_or_else(|_| panic_str(ERR_ELEMENT_DESERIALIZATION))
+    }
+
+    /// Returns the element at `pos`, deserializing it if it has not been accessed before.
+    pub(crate) fn get(&self, pos: usize) -> Option<&T> {
+        let element = self.elements.get(pos)?;
+        Some(element.value.get_or_init(|| self.decode(pos)))
+    }
+
+    /// Returns a mutable reference to the element at `pos`, deserializing it if it has not been
+    /// accessed before. The element is marked dirty.
+    pub(crate) fn get_mut(&mut self, pos: usize) -> Option<&mut T> {
+        if pos >= self.elements.len() {
+            return None;
+        }
+        if self.elements[pos].value.get().is_none() {
+            let value = self.decode(pos);
+            let _ = self.elements[pos].value.set(value);
+        }
+        let element = &mut self.elements[pos];
+        element.dirty = true;
+        element.value.get_mut()
+    }
+
+    /// Removes the last element of the chunk, deserializing it if it has not been accessed.
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        let pos = self.elements.len().checked_sub(1)?;
+        let element = expect_consistent_state(self.elements.pop());
+        let value = element
+            .value
+            .into_inner()
+            .unwrap_or_else(|| self.decode(pos));
+        if pos < self.ends.len() {
+            self.bytes.truncate(self.range(pos).start);
+            self.ends.truncate(pos);
+        }
+        Some(value)
+    }
+}
+
+impl<T, const N: usize> LazyChunk<T, N>
+where
+    T: BorshSerialize,
+{
+    /// Appends the encoding of the elements from `from` to `bytes` along with their end
+    /// offsets, serializing dirty elements and copying the bytes of the others.
+    fn encode_from(
+        &self,
+        from: usize,
+        bytes: &mut Vec<u8>,
+        ends: &mut Vec<u32>,
+    ) -> Result<(), Error> {
+        for (pos, element) in self.elements.iter().enumerate().skip(from) {
+            if element.dirty {
+                let value = expect_consistent_state(element.value.get());
+                BorshSerialize::serialize(value, bytes)?;
+            } else {
+                bytes.extend_from_slice(&self.bytes[self.range(pos)]);
+            }
+            ends.push(bytes.len() as u32);
+        }
+        Ok(())
+    }
+
+    /// Serializes the dirty elements and splices them into the encoded bytes of the chunk, so
+    /// that no element is dirty. Bytes before the first dirty element are left in place.
+    pub(crate) fn splice(&mut self) -> Result<(), Error> {
+        let from = match self.elements.iter().position(|e| e.dirty) {
+            Some(from) => from,
+            None => return Ok(()),
+        };
+        let start = self.ends[..from].last().copied().unwrap_or(0);
+
+        let mut bytes = Vec::new();
+        let mut ends = Vec::with_capacity(self.elements.len() - from);
+        self.encode_from(from, &mut bytes, &mut ends)?;
+
+        self.bytes.truncate(start as usize);
+        self.bytes.extend_from_slice(&bytes);
+        self.ends.truncate(from);
+        self.ends.extend(ends.into_iter().map(|end| start + end));
+        for element in &mut self.elements[from..] {
+            element.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+impl<T, const N: usize> BorshSerialize for LazyChunk<T, N>
+where
+    T: BorshSerialize,
+{
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        BorshSerialize::serialize(&(self.elements.len() as u32), writer)?;
+        if self.elements.iter().any(|e| e.dirty) {
+            let mut bytes = Vec::new();
+            let mut ends = Vec::with_capacity(self.elements.len());
+            self.encode_from(0, &mut bytes, &mut ends)?;
+            write_encoded(writer, &ends, &bytes)
+        } else {
+            write_encoded(writer, &self.ends, &self.bytes)
+        }
+    }
+}
+
+fn write_encoded<W: Write>(writer: &mut W, ends: &[u32], bytes: &[u8]) -> Result<(), Error> {
+    for end in ends {
+        writer.write_all(&end.to_le_bytes())?;
+    }
+    writer.write_all(bytes)
+}
+
+impl<T, const N: usize> BorshDeserialize for LazyChunk<T, N> {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, Error> {
+        let len = u32::deserialize(buf)? as usize;
+        if len > N {
+            return Err(Error::new(ErrorKind::InvalidData, ERR_CHUNK_OVERFLOW));
+        }
+        let mut ends = Vec::with_capacity(len);
+        let mut prev = 0;
+        for _ in 0..len {
+            let end = u32::deserialize(buf)?;
+            if end < prev {
+                return Err(Error::new(ErrorKind::InvalidData, ERR_INVALID_OFFSETS));
+            }
+            ends.push(end);
+            prev = end;
+        }
+        if buf.len() < prev as usize {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Unexpected length of input",
+            ));
+        }
+        let (bytes, rest) = buf.split_at(prev as usize);
+        *buf = rest;
+
+        let mut elements = Vec::with_capacity(N);
+        elements.resize_with(len, LazyElement::encoded);
+        Ok(Self {
+            bytes: bytes.to_vec(),
+            ends,
+            elements,
+        })
+    }
+}
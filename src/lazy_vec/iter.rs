@@ -0,0 +1,218 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::{iter::FusedIterator, ops::Range};
+
+use super::{ChunkedLazyVector, ERR_INDEX_OUT_OF_BOUNDS};
+use crate::storage::{DefaultStorage, StorageBackend};
+use crate::utils::panic_str;
+
+/// An iterator over references to each element in the stored vector.
+#[derive(Debug)]
+pub struct Iter<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Underlying vector to iterate through
+    vec: &'a ChunkedLazyVector<T, N, B>,
+    /// Range of indices to iterate.
+    range: Range<u32>,
+}
+
+impl<'a, T, const N: usize, B> Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(vec: &'a ChunkedLazyVector<T, N, B>) -> Self {
+        Self::with_range(
+            vec,
+            Range {
+                start: 0,
+                end: vec.len(),
+            },
+        )
+    }
+
+    /// Creates an iterator over the elements of `vec` within `range`, which must be in bounds.
+    pub(super) fn with_range(vec: &'a ChunkedLazyVector<T, N, B>, range: Range<u32>) -> Self {
+        Self { vec, range }
+    }
+
+    /// Returns number of elements left to iterate.
+    fn remaining(&self) -> usize {
+        self.range.len()
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        <Self as Iterator>::nth(self, 0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth(n)?;
+        Some(
+            self.vec
+                .get(idx)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+        )
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+impl<'a, T, const N: usize, B> DoubleEndedIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        <Self as DoubleEndedIterator>::nth_back(self, 0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth_back(n)?;
+        Some(
+            self.vec
+                .get(idx)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+        )
+    }
+}
+
+/// An iterator over exclusive references to each element of a stored vector.
+#[derive(Debug)]
+pub struct IterMut<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Mutable reference to vector used to iterate through.
+    vec: &'a mut ChunkedLazyVector<T, N, B>,
+    /// Range of indices to iterate.
+    range: Range<u32>,
+}
+
+impl<'a, T, const N: usize, B> IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Creates a new iterator for the given storage vector.
+    pub(crate) fn new(vec: &'a mut ChunkedLazyVector<T, N, B>) -> Self {
+        let end = vec.len();
+        Self::with_range(vec, Range { start: 0, end })
+    }
+
+    /// Creates an iterator over the elements of `vec` within `range`, which must be in bounds.
+    pub(super) fn with_range(vec: &'a mut ChunkedLazyVector<T, N, B>, range: Range<u32>) -> Self {
+        Self { vec, range }
+    }
+
+    /// Returns the amount of remaining elements to yield by the iterator.
+    fn remaining(&self) -> usize {
+        self.range.len()
+    }
+}
+
+impl<'a, T, const N: usize, B> IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn get_mut<'b>(&'b mut self, at: u32) -> Option<&'a mut T> {
+        self.vec.get_mut(at).map(|value| {
+            //* SAFETY: The lifetime can be swapped here because we can assert that the iterator
+            //*         will only give out one mutable reference for every individual item
+            //*         during the iteration, and there is no overlap. This must be checked
+            //*         that no element in this iterator is ever revisited during iteration.
+            unsafe { &mut *(value as *mut T) }
+        })
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        <Self as Iterator>::nth(self, 0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth(n)?;
+        Some(
+            self.get_mut(idx)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+        )
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+impl<'a, T, const N: usize, B> DoubleEndedIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        <Self as DoubleEndedIterator>::nth_back(self, 0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth_back(n)?;
+        Some(
+            self.get_mut(idx)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+        )
+    }
+}
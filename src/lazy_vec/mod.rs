@@ -0,0 +1,480 @@
+//! A growable array type with values persisted to storage in chunks, where each element of a
+//! loaded chunk is only deserialized when it is accessed.
+//!
+//! Values in the [`ChunkedLazyVector`] are kept in an in-memory cache and are only persisted on
+//! [`Drop`].
+//!
+//! # Examples
+//!
+//! ```
+//! use near_chunked_collections::ChunkedLazyVector;
+//!
+//! let mut vec: ChunkedLazyVector<Vec<u8>, 16> = ChunkedLazyVector::new(b"v");
+//! vec.extend((0..32).map(|i| vec![i; 1024]));
+//!
+//! // Only the element accessed is deserialized, the other 15 elements of its chunk are not.
+//! assert_eq!(vec[20][0], 20);
+//! ```
+
+mod chunk;
+mod impls;
+mod iter;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub use self::iter::{Iter, IterMut};
+
+use self::chunk::LazyChunk;
+use crate::chunk::{chunk_count, chunk_index, chunk_pos};
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+use crate::utils::{expect_consistent_state, panic_str};
+
+const ERR_INDEX_OUT_OF_BOUNDS: &str = "Index out of bounds";
+const ERR_ELEMENT_SERIALIZATION: &str = "Cannot serialize element";
+
+/// An iterable implementation of vector that stores its content on the trie, for elements which
+/// are expensive to deserialize. This implementation will load and store values in the
+/// underlying storage lazily.
+///
+/// Uses the following map: chunk index -> chunk of up to `N` elements, as in
+/// [`ChunkedVector`](crate::ChunkedVector). Chunks are stored along with the offset of each
+/// element within them, so loading a chunk only reads its bytes, and an element is deserialized
+/// the first time it is accessed. Accessing a single element of a chunk then costs a single
+/// storage read and the deserialization of that element, rather than of the whole chunk.
+///
+/// Elements accessed mutably are marked dirty. When a modified chunk is flushed, only its dirty
+/// elements are serialized again and spliced into the bytes of the chunk, and the bytes of the
+/// other elements are written back as they were loaded.
+///
+/// This implementation will cache all changes and loads and only updates values that are changed
+/// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
+/// in storage before the variable is dropped by using [`ChunkedLazyVector::flush`].
+///
+/// # Examples
+/// ```
+/// use near_chunked_collections::ChunkedLazyVector;
+///
+/// let mut vec: ChunkedLazyVector<String> = ChunkedLazyVector::new(b"a");
+/// assert!(vec.is_empty());
+///
+/// vec.push("a".to_string());
+/// vec.push("b".to_string());
+///
+/// assert_eq!(vec.len(), 2);
+/// assert_eq!(vec[1], "b");
+///
+/// vec[0].push('c');
+/// assert_eq!(vec.pop().as_deref(), Some("b"));
+/// assert!(Iterator::eq(vec.iter(), ["ac"].iter()));
+/// ```
+pub struct ChunkedLazyVector<T, const N: usize = 5, B = DefaultStorage>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    pub(crate) len: u32,
+    pub(crate) values: IndexMap<LazyChunk<T, N>, B>,
+}
+
+impl<T, const N: usize> ChunkedLazyVector<T, N, DefaultStorage>
+where
+    T: BorshSerialize,
+{
+    /// Create new vector with zero elements. Prefixes storage accesss with the prefix provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedLazyVector;
+    ///
+    /// let mut vec: ChunkedLazyVector<Vec<u8>> = ChunkedLazyVector::new(b"a");
+    /// ```
+    pub fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self::new_in(prefix, DefaultStorage::default())
+    }
+}
+
+impl<T, const N: usize, B> Drop for ChunkedLazyVector<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+impl<T, const N: usize, B> BorshSerialize for ChunkedLazyVector<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.len, writer)?;
+        BorshSerialize::serialize(&self.values, writer)?;
+        Ok(())
+    }
+}
+
+impl<T, const N: usize, B> BorshDeserialize for ChunkedLazyVector<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend + Default,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            len: BorshDeserialize::deserialize(buf)?,
+            values: BorshDeserialize::deserialize(buf)?,
+        })
+    }
+}
+
+impl<T, const N: usize, B> ChunkedLazyVector<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    /// Returns the number of elements in the vector.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns `true` if the vector contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Create new vector with zero elements, which is persisted to the storage backend provided.
+    /// Prefixes storage accesss with the prefix provided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedLazyVector;
+    ///
+    /// let mut vec: ChunkedLazyVector<String, 5, _> =
+    ///     ChunkedLazyVector::new_in(b"a", InMemoryStorage::new());
+    /// ```
+    pub fn new_in<S>(prefix: S, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            len: 0,
+            values: IndexMap::new_in(prefix, storage),
+        }
+    }
+
+    /// Removes all elements from the vector. This will remove the storage value of every chunk,
+    /// without loading any of them.
+    pub fn clear(&mut self) {
+        for chunk_idx in 0..chunk_count::<N>(self.len) {
+            self.values.set(chunk_idx, None);
+        }
+        self.len = 0;
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    ///
+    /// The dirty elements of each modified chunk are serialized and spliced into the bytes of
+    /// the chunk before it is written, so they are not serialized again on the next flush unless
+    /// they are accessed mutably again.
+    ///
+    /// This operation is performed on [`Drop`], but this method can be called to persist
+    /// intermediate writes in cases where [`Drop`] is not called or to identify storage changes.
+    pub fn flush(&mut self) {
+        for chunk in self.values.modified_values_mut() {
+            chunk
+                .splice()
+                .unwrap_or_else(|_| panic_str(ERR_ELEMENT_SERIALIZATION));
+        }
+        self.values.flush();
+    }
+}
+
+impl<T, const N: usize, B> ChunkedLazyVector<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Appends an element to the back of the collection. The element is only serialized when
+    /// its chunk is flushed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    pub fn push(&mut self, element: T) {
+        let last_idx = self.len;
+        self.len = self
+            .len
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+
+        let chunk_idx = chunk_index::<N>(last_idx);
+        if chunk_pos::<N>(last_idx) == 0 {
+            self.values
+                .set(chunk_idx, Some(LazyChunk::with_first(element)));
+        } else {
+            // Loads the bytes of the chunk, but none of its elements are deserialized.
+            expect_consistent_state(self.values.get_mut(chunk_idx)).push(element);
+        }
+    }
+
+    /// Removes the last element from the vector and returns it, or [`None`] if it is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        let new_idx = self.len.checked_sub(1)?;
+        let chunk_idx = chunk_index::<N>(new_idx);
+        let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+        let element = chunk.pop();
+        if chunk.is_empty() {
+            self.values.set(chunk_idx, None);
+        }
+        self.len = new_idx;
+        element
+    }
+
+    /// Returns the element by index or `None` if it is not present. Only this element of its
+    /// chunk is deserialized.
+    pub fn get(&self, index: u32) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        let chunk = expect_consistent_state(self.values.get(chunk_index::<N>(index)));
+        chunk.get(chunk_pos::<N>(index))
+    }
+
+    /// Returns a mutable reference to the element by index or `None` if it is not present.
+    ///
+    /// The element is marked dirty, and is serialized again when its chunk is flushed.
+    pub fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+        let chunk = expect_consistent_state(self.values.get_mut(chunk_index::<N>(index)));
+        chunk.get_mut(chunk_pos::<N>(index))
+    }
+
+    /// Returns an iterator over the vector. This iterator will lazily load any values iterated
+    /// over from storage.
+    pub fn iter(&self) -> Iter<'_, T, N, B> {
+        Iter::new(self)
+    }
+
+    /// Returns an iterator over the vector that allows modifying each value. Every element
+    /// yielded is marked dirty.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, N, B> {
+        IterMut::new(self)
+    }
+}
+
+impl<T, const N: usize, B> fmt::Debug for ChunkedLazyVector<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if cfg!(feature = "expensive-debug") {
+            fmt::Debug::fmt(&self.iter().collect::<Vec<_>>(), f)
+        } else {
+            f.debug_struct("LazyVector")
+                .field("len", &self.len)
+                .field("prefix", &self.values.prefix)
+                .finish()
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{Rng, RngCore, SeedableRng};
+
+    use super::ChunkedLazyVector;
+    use crate::utils::test_iters;
+    use near_sdk::test_utils::test_env::setup_free;
+
+    /// Number of [`Counted`] values deserialized.
+    static DECODED: AtomicUsize = AtomicUsize::new(0);
+    /// Number of [`Counted`] values serialized.
+    static ENCODED: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, PartialEq)]
+    struct Counted(u64);
+
+    impl BorshSerialize for Counted {
+        fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+            ENCODED.fetch_add(1, Ordering::Relaxed);
+            self.0.serialize(writer)
+        }
+    }
+
+    impl BorshDeserialize for Counted {
+        fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+            DECODED.fetch_add(1, Ordering::Relaxed);
+            u64::deserialize(buf).map(Self)
+        }
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut vec = ChunkedLazyVector::<Vec<u8>>::new(b"v");
+        let mut baseline = vec![];
+        for _ in 0..test_iters(500) {
+            let value = vec![rng.gen::<u8>(); rng.gen::<usize>() % 64];
+            vec.push(value.clone());
+            baseline.push(value);
+            if rng.gen::<u8>() < 16 {
+                vec.flush();
+            }
+        }
+        assert!(Iterator::eq(vec.iter(), baseline.iter()));
+        for _ in 0..=test_iters(500) {
+            assert_eq!(vec.pop(), baseline.pop());
+        }
+        vec.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[test]
+    fn test_lazy_elements() {
+        let mut vec = ChunkedLazyVector::<Counted, 8>::new(b"v");
+        vec.extend((0..16).map(Counted));
+        vec.flush();
+        drop(vec);
+
+        // Only the elements accessed are deserialized.
+        let mut vec = ChunkedLazyVector::<Counted, 8> {
+            len: 16,
+            values: crate::index_map::IndexMap::new_in(b"v", crate::storage::NearStorage),
+        };
+        let decoded = DECODED.load(Ordering::Relaxed);
+        assert_eq!(vec[3], Counted(3));
+        assert_eq!(vec[3], Counted(3));
+        assert_eq!(vec[12], Counted(12));
+        assert_eq!(DECODED.load(Ordering::Relaxed) - decoded, 2);
+
+        // Only the dirty elements are serialized when their chunk is flushed.
+        let encoded = ENCODED.load(Ordering::Relaxed);
+        vec[5].0 = 50;
+        vec.push(Counted(16));
+        vec.flush();
+        assert_eq!(ENCODED.load(Ordering::Relaxed) - encoded, 2);
+
+        // Spliced elements are not serialized again by the next flush.
+        let encoded = ENCODED.load(Ordering::Relaxed);
+        vec[6].0 = 60;
+        vec.flush();
+        assert_eq!(ENCODED.load(Ordering::Relaxed) - encoded, 1);
+        drop(vec);
+
+        let vec = ChunkedLazyVector::<Counted, 8> {
+            len: 17,
+            values: crate::index_map::IndexMap::new_in(b"v", crate::storage::NearStorage),
+        };
+        let expected = (0..17).map(|i| match i {
+            5 => 50,
+            6 => 60,
+            i => i,
+        });
+        assert!(Iterator::eq(vec.iter().map(|c| c.0), expected));
+    }
+
+    #[derive(Arbitrary, Debug)]
+    enum Op {
+        Push(Vec<u8>),
+        Pop,
+        Set(u32, Vec<u8>),
+        Append(u32, u8),
+        Get(u32),
+        Flush,
+        Reset,
+        Clear,
+    }
+
+    #[test]
+    fn arbitrary() {
+        setup_free();
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut buf = vec![0; 4096];
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 512 };
+        for _ in 0..runs {
+            // Clear storage in-between runs
+            near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+            rng.fill_bytes(&mut buf);
+
+            let mut sv = ChunkedLazyVector::<_, 4>::new(b"v");
+            let mut mv = Vec::new();
+            let u = Unstructured::new(&buf);
+            if let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) {
+                for op in ops {
+                    match op {
+                        Op::Push(v) => {
+                            sv.push(v.clone());
+                            mv.push(v);
+                        }
+                        Op::Pop => {
+                            assert_eq!(sv.pop(), mv.pop());
+                        }
+                        Op::Set(i, v) => {
+                            if sv.is_empty() {
+                                continue;
+                            }
+                            let i = i % sv.len();
+                            sv[i] = v.clone();
+                            mv[i as usize] = v;
+                        }
+                        Op::Append(i, b) => {
+                            if sv.is_empty() {
+                                continue;
+                            }
+                            let i = i % sv.len();
+                            sv[i].push(b);
+                            mv[i as usize].push(b);
+                        }
+                        Op::Get(i) => {
+                            assert_eq!(sv.get(i), mv.get(i as usize));
+                        }
+                        Op::Flush => {
+                            sv.flush();
+                        }
+                        Op::Reset => {
+                            let serialized = sv.try_to_vec().unwrap();
+                            sv =
+                                ChunkedLazyVector::deserialize(&mut serialized.as_slice()).unwrap();
+                        }
+                        Op::Clear => {
+                            sv.clear();
+                            mv.clear();
+                        }
+                    }
+                    assert_eq!(sv.len() as usize, mv.len());
+                }
+            }
+
+            // After all operations, compare both vectors
+            assert!(Iterator::eq(sv.iter(), mv.iter()));
+            assert!(Iterator::eq(sv.iter_mut(), mv.iter_mut()));
+        }
+    }
+}
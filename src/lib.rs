@@ -4,9 +4,39 @@
 //! fewer overall reads is greater than the potentially increased number of bytes written per
 //! element.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![cfg_attr(doc_cfg, feature(doc_cfg))]
 #![deny(dead_code, unused_mut)]
 #![warn(missing_docs)]
 
+extern crate alloc;
+
+pub mod binary_heap;
+pub mod bit_vec;
+mod chunk;
+pub mod fixed;
+pub mod free_list;
+pub mod hash;
+mod index_map;
+pub mod lazy_vec;
+pub mod map;
+pub mod set;
+pub mod storage;
+pub mod tree_map;
+mod utils;
+pub mod var_vec;
 pub mod vec;
+pub mod vec_deque;
+
+pub use binary_heap::ChunkedBinaryHeap;
+pub use bit_vec::ChunkedBitVec;
+pub use fixed::FixedSize;
+pub use free_list::ChunkedFreeList;
+pub use lazy_vec::ChunkedLazyVector;
+pub use map::ChunkedMap;
+pub use set::ChunkedSet;
+pub use storage::{IntoStorageKey, StorageBackend};
+pub use tree_map::ChunkedTreeMap;
+pub use var_vec::ChunkedVarVector;
 pub use vec::ChunkedVector;
+pub use vec_deque::ChunkedVecDeque;
@@ -0,0 +1,65 @@
+use alloc::vec::Vec;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::node::NodeKey;
+
+/// Branch nodes at or below the shallowest depth of the trie which contains a bucket.
+///
+/// These are kept alongside the length of the map, so the bucket a hash belongs to is found
+/// without reading the nodes above it from storage.
+#[derive(Debug, Default, BorshSerialize, BorshDeserialize)]
+pub(crate) struct Branches {
+    /// One bit per node at the shallowest depth, set if the node is a branch.
+    shallow: Vec<u8>,
+    /// Branches deeper than the shallowest depth, sorted by depth and then path.
+    deep: Vec<NodeKey>,
+}
+
+impl Branches {
+    /// Returns `true` if the node provided, which is not above `min_depth`, is a branch.
+    pub(crate) fn contains(&self, node: NodeKey, min_depth: u8) -> bool {
+        if node.depth == min_depth {
+            let (byte, bit) = Self::bit(node.path);
+            self.shallow.get(byte).is_some_and(|bits| bits & bit != 0)
+        } else {
+            self.deep.binary_search(&node).is_ok()
+        }
+    }
+
+    /// Records the node provided, which is not above `min_depth`, as a branch.
+    pub(crate) fn insert(&mut self, node: NodeKey, min_depth: u8) {
+        if node.depth == min_depth {
+            self.set_shallow(node.path);
+        } else if let Err(pos) = self.deep.binary_search(&node) {
+            self.deep.insert(pos, node);
+        }
+    }
+
+    /// Drops the branches at `min_depth` once all nodes at that depth are branches, and moves
+    /// the branches of the next depth into the bitset.
+    pub(crate) fn descend(&mut self, min_depth: u8) {
+        self.shallow.clear();
+        let next = self
+            .deep
+            .iter()
+            .take_while(|node| node.depth == min_depth + 1)
+            .count();
+        let deeper = self.deep.split_off(next);
+        for node in core::mem::replace(&mut self.deep, deeper) {
+            self.set_shallow(node.path);
+        }
+    }
+
+    fn set_shallow(&mut self, path: u64) {
+        let (byte, bit) = Self::bit(path);
+        if self.shallow.len() <= byte {
+            self.shallow.resize(byte + 1, 0);
+        }
+        self.shallow[byte] |= bit;
+    }
+
+    fn bit(path: u64) -> (usize, u8) {
+        ((path / 8) as usize, 1 << (path % 8))
+    }
+}
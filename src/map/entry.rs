@@ -0,0 +1,213 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::node::NodeKey;
+use super::ChunkedMap;
+use crate::hash::{DefaultHasher, KeyHasher};
+use crate::storage::{DefaultStorage, StorageBackend};
+use crate::utils::expect_consistent_state;
+
+/// A view into a single entry in the map, which can either be vacant or occupied.
+///
+/// This `enum` is constructed from the [`ChunkedMap::entry`] method.
+pub enum Entry<'a, K, V, const N: usize, H = DefaultHasher, B = DefaultStorage>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V, N, H, B>),
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V, N, H, B>),
+}
+
+impl<'a, K, V, const N: usize, H, B> Entry<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize + Eq,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    /// Returns a reference to this entry's key.
+    pub fn key(&self) -> &K {
+        match self {
+            Self::Occupied(entry) => entry.key(),
+            Self::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default if empty, and returns a mutable
+    /// reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function if empty,
+    /// and returns a mutable reference to the value in the entry.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with_key(|_| default())
+    }
+
+    /// Ensures a value is in the entry by inserting, if empty, the result of the default
+    /// function. The function is given a reference to the key.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Self::Occupied(entry) => entry.into_mut(),
+            Self::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty, and returns a
+    /// mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any potential inserts into
+    /// the map.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Self::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+/// A view into an occupied entry in a [`ChunkedMap`]. It is part of the [`Entry`] enum.
+pub struct OccupiedEntry<'a, K, V, const N: usize, H = DefaultHasher, B = DefaultStorage>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    map: &'a mut ChunkedMap<K, V, N, H, B>,
+    /// Bucket the entry is in.
+    node: NodeKey,
+    /// Position of the entry within the bucket.
+    pos: usize,
+}
+
+impl<'a, K, V, const N: usize, H, B> OccupiedEntry<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(super) fn new(map: &'a mut ChunkedMap<K, V, N, H, B>, node: NodeKey, pos: usize) -> Self {
+        Self { map, node, pos }
+    }
+
+    fn entry(&self) -> &(K, V) {
+        expect_consistent_state(self.map.bucket(self.node).and_then(|e| e.get(self.pos)))
+    }
+
+    fn entry_mut(&mut self) -> &mut (K, V) {
+        let pos = self.pos;
+        expect_consistent_state(self.map.bucket_mut(self.node).and_then(|e| e.get_mut(pos)))
+    }
+
+    /// Gets a reference to the key in the entry.
+    pub fn key(&self) -> &K {
+        &self.entry().0
+    }
+
+    /// Gets a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        &self.entry().1
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.entry_mut().1
+    }
+
+    /// Converts the entry into a mutable reference to the value in the entry with a lifetime
+    /// bound to the map itself.
+    pub fn into_mut(self) -> &'a mut V {
+        let Self { map, node, pos } = self;
+        expect_consistent_state(
+            map.bucket_mut(node)
+                .and_then(|e| e.get_mut(pos))
+                .map(|e| &mut e.1),
+        )
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        core::mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value out of the entry, and returns it.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Take the ownership of the key and value from the map.
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove_at(self.node, self.pos)
+    }
+}
+
+/// A view into a vacant entry in a [`ChunkedMap`]. It is part of the [`Entry`] enum.
+pub struct VacantEntry<'a, K, V, const N: usize, H = DefaultHasher, B = DefaultStorage>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    map: &'a mut ChunkedMap<K, V, N, H, B>,
+    /// Bucket the key belongs to.
+    node: NodeKey,
+    key: K,
+}
+
+impl<'a, K, V, const N: usize, H, B> VacantEntry<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(super) fn new(map: &'a mut ChunkedMap<K, V, N, H, B>, node: NodeKey, key: K) -> Self {
+        Self { map, node, key }
+    }
+
+    /// Gets a reference to the key that would be used when inserting a value through the
+    /// `VacantEntry`.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Take ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Sets the value of the entry with the `VacantEntry`'s key, and returns a mutable reference
+    /// to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let Self { map, node, key } = self;
+        let hash = ChunkedMap::<K, V, N, H, B>::hash_key(&key);
+        map.insert_new(node, key, value);
+        // The bucket may have been split, but the new entry is always last in its bucket.
+        let node = map.find_bucket(hash);
+        expect_consistent_state(
+            map.bucket_mut(node)
+                .and_then(|e| e.last_mut())
+                .map(|e| &mut e.1),
+        )
+    }
+}
@@ -0,0 +1,181 @@
+use alloc::vec::Vec;
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::iter::FusedIterator;
+use core::ops::RangeInclusive;
+use core::slice;
+
+use super::node::{Node, NodeKey, FANOUT};
+use super::ChunkedMap;
+use crate::hash::{DefaultHasher, KeyHasher};
+use crate::storage::{DefaultStorage, StorageBackend};
+
+/// An iterator over the entries of a stored map, in the order of their buckets.
+#[derive(Debug)]
+pub struct Iter<'a, K, V, const N: usize, H = DefaultHasher, B = DefaultStorage>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    /// Underlying map to iterate through
+    map: &'a ChunkedMap<K, V, N, H, B>,
+    /// Depth of the shallowest bucket, which iteration starts from.
+    depth: u8,
+    /// Paths of the nodes at the starting depth which have not been visited yet.
+    roots: RangeInclusive<u64>,
+    /// Children of branches which have not been visited yet, the next node to visit is last.
+    stack: Vec<NodeKey>,
+    /// Remaining entries of the bucket being iterated.
+    current: slice::Iter<'a, (K, V)>,
+    /// Number of entries left to yield.
+    remaining: u32,
+}
+
+impl<'a, K, V, const N: usize, H, B> Iter<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(super) fn new(map: &'a ChunkedMap<K, V, N, H, B>) -> Self {
+        // Nodes above the shallowest bucket are all branches, so start from that depth.
+        let depth = map.min_depth();
+        Self {
+            map,
+            depth,
+            roots: 0..=NodeKey::new(u64::MAX, depth).path,
+            stack: Vec::new(),
+            current: [].iter(),
+            remaining: map.len(),
+        }
+    }
+}
+
+impl<'a, K, V, const N: usize, H, B> Iterator for Iter<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.remaining == 0 {
+                // Avoid loading the remaining nodes when all entries have been yielded.
+                return None;
+            }
+            if let Some((key, value)) = self.current.next() {
+                self.remaining -= 1;
+                return Some((key, value));
+            }
+
+            let node = match self.stack.pop() {
+                Some(node) => node,
+                None => NodeKey {
+                    depth: self.depth,
+                    path: self.roots.next()?,
+                },
+            };
+            match self.map.values.get(node) {
+                Some(Node::Bucket(entries)) => self.current = entries.iter(),
+                Some(Node::Branch) => self.stack.extend((0..FANOUT).rev().map(|i| node.child(i))),
+                None => {}
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining as usize;
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining as usize
+    }
+}
+
+impl<'a, K, V, const N: usize, H, B> ExactSizeIterator for Iter<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+}
+impl<'a, K, V, const N: usize, H, B> FusedIterator for Iter<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+}
+
+/// An iterator over the keys of a stored map, in the order of their buckets.
+#[derive(Debug)]
+pub struct Keys<'a, K, V, const N: usize, H = DefaultHasher, B = DefaultStorage>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    inner: Iter<'a, K, V, N, H, B>,
+}
+
+impl<'a, K, V, const N: usize, H, B> Keys<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(super) fn new(map: &'a ChunkedMap<K, V, N, H, B>) -> Self {
+        Self {
+            inner: Iter::new(map),
+        }
+    }
+}
+
+impl<'a, K, V, const N: usize, H, B> Iterator for Keys<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn count(self) -> usize {
+        self.inner.count()
+    }
+}
+
+impl<'a, K, V, const N: usize, H, B> ExactSizeIterator for Keys<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+}
+impl<'a, K, V, const N: usize, H, B> FusedIterator for Keys<'a, K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+}
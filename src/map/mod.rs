@@ -0,0 +1,734 @@
+//! A hash map with entries persisted to storage in buckets grouped by key hash prefix.
+//!
+//! Values in the [`ChunkedMap`] are kept in an in-memory cache and are only persisted on
+//! [`Drop`].
+//!
+//! # Examples
+//!
+//! ```
+//! use near_chunked_collections::ChunkedMap;
+//!
+//! let mut map: ChunkedMap<String, u64> = ChunkedMap::new(b"m");
+//! map.insert("alice".to_string(), 5);
+//! *map.entry("bob".to_string()).or_default() += 3;
+//!
+//! assert_eq!(map.get("alice"), Some(&5));
+//! assert_eq!(map.get("bob"), Some(&3));
+//! assert_eq!(map.remove("alice"), Some(5));
+//! assert!(!map.contains_key("alice"));
+//! ```
+
+mod branches;
+mod entry;
+mod iter;
+mod node;
+
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::marker::PhantomData;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub use self::entry::{Entry, OccupiedEntry, VacantEntry};
+pub use self::iter::{Iter, Keys};
+
+use self::branches::Branches;
+use self::node::{Node, NodeKey, FANOUT, MAX_DEPTH};
+use crate::hash::{DefaultHasher, KeyHasher};
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+use crate::utils::{expect_consistent_state, panic_str};
+
+const ERR_KEY_SERIALIZATION: &str = "Cannot serialize key";
+const ERR_LENGTH_OVERFLOW: &str = "Map length overflow";
+
+/// A hash map which stores its content on the trie, with entries grouped into buckets of up to
+/// `N` entries. This implementation will load and store values in the underlying storage lazily.
+///
+/// Buckets are the nodes of a trie over the hash of each key, similar to an
+/// [IPLD HAMT](https://ipld.io/specs/advanced-data-layouts/hamt/spec/) without hash linking.
+/// Every bucket is stored under a key derived from its depth and the hash prefix shared by the
+/// entries in it. When a bucket exceeds `N` entries, it is split into 16 child buckets on the
+/// next 4 bits of the hash and replaced by a marker. The marker is never modified again and
+/// empty buckets are not stored, so the storage key of each bucket is stable and parent nodes
+/// are never rewritten when the entries below them change.
+///
+/// The branches at or below the shallowest depth that contains a bucket are tracked alongside the
+/// length of the map, so accessing an entry only reads the bucket it is in from storage. This
+/// takes about one bit per bucket, plus the few branches below that depth.
+///
+/// The hasher `H` determines which bucket a key is stored in, see [`crate::hash`].
+///
+/// This implementation will cache all changes and loads and only updates values that are changed
+/// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
+/// in storage before the variable is dropped by using [`ChunkedMap::flush`].
+///
+/// # Examples
+/// ```
+/// use near_chunked_collections::ChunkedMap;
+///
+/// let mut map: ChunkedMap<u32, String> = ChunkedMap::new(b"m");
+/// assert_eq!(map.insert(1, "a".to_string()), None);
+/// assert_eq!(map.insert(1, "b".to_string()), Some("a".to_string()));
+///
+/// if let Some(value) = map.get_mut(&1) {
+///     value.push('c');
+/// }
+/// assert_eq!(map[&1], "bc");
+/// ```
+pub struct ChunkedMap<K, V, const N: usize = 5, H = DefaultHasher, B = DefaultStorage>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(crate) len: u32,
+    /// Number of buckets at each depth of the trie.
+    pub(crate) buckets: Vec<u32>,
+    branches: Branches,
+    pub(crate) values: IndexMap<Node<K, V>, B, NodeKey>,
+    hasher: PhantomData<fn() -> H>,
+}
+
+impl<K, V, const N: usize, H> ChunkedMap<K, V, N, H, DefaultStorage>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    H: KeyHasher,
+{
+    /// Create new map with zero entries. Prefixes storage accesss with the prefix provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedMap;
+    ///
+    /// let mut map: ChunkedMap<u8, u8> = ChunkedMap::new(b"m");
+    /// ```
+    pub fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self::new_in(prefix, DefaultStorage::default())
+    }
+}
+
+impl<K, V, const N: usize, H, B> Drop for ChunkedMap<K, V, N, H, B>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+impl<K, V, const N: usize, H, B> BorshSerialize for ChunkedMap<K, V, N, H, B>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.len, writer)?;
+        BorshSerialize::serialize(&self.buckets, writer)?;
+        BorshSerialize::serialize(&self.branches, writer)?;
+        BorshSerialize::serialize(&self.values, writer)?;
+        Ok(())
+    }
+}
+
+impl<K, V, const N: usize, H, B> BorshDeserialize for ChunkedMap<K, V, N, H, B>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend + Default,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            len: BorshDeserialize::deserialize(buf)?,
+            buckets: BorshDeserialize::deserialize(buf)?,
+            branches: BorshDeserialize::deserialize(buf)?,
+            values: BorshDeserialize::deserialize(buf)?,
+            hasher: PhantomData,
+        })
+    }
+}
+
+impl<K, V, const N: usize, H, B> ChunkedMap<K, V, N, H, B>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Create new map with zero entries, which is persisted to the storage backend provided.
+    /// Prefixes storage accesss with the prefix provided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::hash::DefaultHasher;
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedMap;
+    ///
+    /// let mut map: ChunkedMap<u8, u8, 5, DefaultHasher, _> =
+    ///     ChunkedMap::new_in(b"m", InMemoryStorage::new());
+    /// ```
+    pub fn new_in<S>(prefix: S, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            len: 0,
+            // Map starts with a single empty bucket at the root.
+            buckets: alloc::vec![1],
+            branches: Branches::default(),
+            values: IndexMap::new_in(prefix, storage),
+            hasher: PhantomData,
+        }
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    ///
+    /// This operation is performed on [`Drop`], but this method can be called to persist
+    /// intermediate writes in cases where [`Drop`] is not called or to identify storage changes.
+    pub fn flush(&mut self) {
+        self.values.flush();
+    }
+
+    fn hash_key<Q>(key: &Q) -> u64
+    where
+        Q: BorshSerialize + ?Sized,
+    {
+        let bytes = key
+            .try_to_vec()
+            .unwrap_or_else(|_| panic_str(ERR_KEY_SERIALIZATION));
+        H::hash(&bytes)
+    }
+
+    /// Shallowest depth of the trie which contains a bucket. All nodes above are branches.
+    pub(crate) fn min_depth(&self) -> u8 {
+        self.buckets
+            .iter()
+            .position(|count| *count != 0)
+            .unwrap_or_default() as u8
+    }
+}
+
+impl<K, V, const N: usize, H, B> ChunkedMap<K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    /// Returns the bucket node that the hash belongs to, without reading from storage.
+    fn find_bucket(&self, hash: u64) -> NodeKey {
+        let min_depth = self.min_depth();
+        let mut node = NodeKey::new(hash, min_depth);
+        while self.branches.contains(node, min_depth) {
+            node = NodeKey::new(hash, node.depth + 1);
+        }
+        node
+    }
+
+    /// Returns the entries of the bucket node provided, if it is not empty.
+    fn bucket(&self, node: NodeKey) -> Option<&Vec<(K, V)>> {
+        match self.values.get(node)? {
+            Node::Bucket(entries) => Some(entries),
+            Node::Branch => panic_str("inconsistent state"),
+        }
+    }
+
+    /// Returns the entries of the bucket node provided, if it is not empty.
+    fn bucket_mut(&mut self, node: NodeKey) -> Option<&mut Vec<(K, V)>> {
+        match self.values.get_mut(node)? {
+            Node::Bucket(entries) => Some(entries),
+            Node::Branch => panic_str("inconsistent state"),
+        }
+    }
+
+    /// Returns the bucket and position within it of the entry with the key provided.
+    pub(crate) fn find<Q>(&self, k: &Q) -> (NodeKey, Option<usize>)
+    where
+        K: Borrow<Q>,
+        Q: BorshSerialize + Eq + ?Sized,
+    {
+        let node = self.find_bucket(Self::hash_key(k));
+        let pos = self
+            .bucket(node)
+            .and_then(|entries| entries.iter().position(|(key, _)| key.borrow() == k));
+        (node, pos)
+    }
+
+    /// Appends a new entry to the bucket provided, splitting it if it exceeds the capacity.
+    fn insert_new(&mut self, node: NodeKey, key: K, value: V) {
+        self.len = self
+            .len
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_LENGTH_OVERFLOW));
+        match self.bucket_mut(node) {
+            Some(entries) => {
+                entries.push((key, value));
+                if entries.len() > N && node.depth < MAX_DEPTH {
+                    self.split(node);
+                }
+            }
+            None => self
+                .values
+                .set(node, Some(Node::Bucket(alloc::vec![(key, value)]))),
+        }
+    }
+
+    /// Moves the entries of a bucket into its children and replaces it with a branch marker.
+    ///
+    /// The relative order of entries is kept within each child.
+    fn split(&mut self, node: NodeKey) {
+        let entries = match self
+            .values
+            .get_mut(node)
+            .map(|n| core::mem::replace(n, Node::Branch))
+        {
+            Some(Node::Bucket(entries)) => entries,
+            _ => panic_str("inconsistent state"),
+        };
+
+        let min_depth = self.min_depth();
+        self.branches.insert(node, min_depth);
+        let child_depth = node.depth + 1;
+        self.buckets[node.depth as usize] -= 1;
+        if self.buckets.len() <= child_depth as usize {
+            self.buckets.push(0);
+        }
+        self.buckets[child_depth as usize] += FANOUT as u32;
+        if self.min_depth() != min_depth {
+            self.branches.descend(min_depth);
+        }
+
+        let mut children: Vec<Vec<(K, V)>> = (0..FANOUT).map(|_| Vec::new()).collect();
+        for (key, value) in entries {
+            let child = NodeKey::new(Self::hash_key(&key), child_depth);
+            children[(child.path % FANOUT) as usize].push((key, value));
+        }
+        for (i, entries) in children.into_iter().enumerate() {
+            if entries.is_empty() {
+                continue;
+            }
+            let child = node.child(i as u64);
+            let overflow = entries.len() > N;
+            self.values.set(child, Some(Node::Bucket(entries)));
+            if overflow && child.depth < MAX_DEPTH {
+                // All entries landed in the same child, keep splitting.
+                self.split(child);
+            }
+        }
+    }
+
+    /// Removes the entry at the position of the bucket provided.
+    fn remove_at(&mut self, node: NodeKey, pos: usize) -> (K, V) {
+        let entries = expect_consistent_state(self.bucket_mut(node));
+        let entry = entries.swap_remove(pos);
+        if entries.is_empty() {
+            // Empty buckets are not stored.
+            self.values.set(node, None);
+        }
+        self.len -= 1;
+        entry
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the Borsh serialization of
+    /// the borrowed form must match that of the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedMap;
+    ///
+    /// let mut map: ChunkedMap<u32, u32> = ChunkedMap::new(b"m");
+    /// map.insert(1, 2);
+    ///
+    /// assert_eq!(map.get(&1), Some(&2));
+    /// assert_eq!(map.get(&2), None);
+    /// ```
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BorshSerialize + ToOwned<Owned = K> + Eq + ?Sized,
+    {
+        let (node, pos) = self.find(k);
+        let entries = self.bucket(node)?;
+        entries.get(pos?).map(|(_, value)| value)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the Borsh serialization of
+    /// the borrowed form must match that of the key type.
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: BorshSerialize + ToOwned<Owned = K> + Eq + ?Sized,
+    {
+        let (node, pos) = self.find(k);
+        let entries = self.bucket_mut(node)?;
+        entries.get_mut(pos?).map(|(_, value)| value)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: BorshSerialize + ToOwned<Owned = K> + Eq + ?Sized,
+    {
+        self.find(k).1.is_some()
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned. Otherwise the value is
+    /// updated and the old value is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedMap;
+    ///
+    /// let mut map: ChunkedMap<u32, u32> = ChunkedMap::new(b"m");
+    /// assert_eq!(map.insert(37, 1), None);
+    /// assert_eq!(map.insert(37, 2), Some(1));
+    /// assert_eq!(map[&37], 2);
+    /// ```
+    pub fn insert(&mut self, k: K, v: V) -> Option<V>
+    where
+        K: Eq,
+    {
+        let (node, pos) = self.find(&k);
+        match pos {
+            Some(pos) => {
+                let entries = expect_consistent_state(self.bucket_mut(node));
+                Some(core::mem::replace(&mut entries[pos].1, v))
+            }
+            None => {
+                self.insert_new(node, k, v);
+                None
+            }
+        }
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key was previously in
+    /// the map.
+    ///
+    /// The bucket the key was in is removed from storage once it is empty, but buckets are never
+    /// merged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedMap;
+    ///
+    /// let mut map: ChunkedMap<u32, u32> = ChunkedMap::new(b"m");
+    /// map.insert(1, 2);
+    ///
+    /// assert_eq!(map.remove(&1), Some(2));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BorshSerialize + ToOwned<Owned = K> + Eq + ?Sized,
+    {
+        let (node, pos) = self.find(k);
+        Some(self.remove_at(node, pos?).1)
+    }
+
+    /// Returns an iterator over the entries of the map. This iterator will lazily load any
+    /// buckets iterated over from storage.
+    ///
+    /// Entries are yielded in the order of the buckets they are in, which depends on the hash
+    /// of their keys. Every bucket is loaded once, including branch markers and empty buckets
+    /// below the shallowest depth of the trie.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedMap;
+    ///
+    /// let mut map: ChunkedMap<u32, u32> = ChunkedMap::new(b"m");
+    /// map.insert(1, 2);
+    /// map.insert(3, 4);
+    ///
+    /// let mut entries: Vec<_> = map.iter().collect();
+    /// entries.sort();
+    /// assert_eq!(entries, [(&1, &2), (&3, &4)]);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V, N, H, B> {
+        Iter::new(self)
+    }
+
+    /// Returns an iterator over the keys of the map, in the same order as
+    /// [`ChunkedMap::iter`].
+    pub fn keys(&self) -> Keys<'_, K, V, N, H, B> {
+        Keys::new(self)
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedMap;
+    ///
+    /// let mut count: ChunkedMap<String, u32> = ChunkedMap::new(b"m");
+    /// for word in ["a", "b", "a"] {
+    ///     *count.entry(word.to_string()).or_insert(0) += 1;
+    /// }
+    ///
+    /// assert_eq!(count["a"], 2);
+    /// assert_eq!(count["b"], 1);
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, N, H, B>
+    where
+        K: Eq,
+    {
+        let (node, pos) = self.find(&key);
+        match pos {
+            Some(pos) => Entry::Occupied(OccupiedEntry::new(self, node, pos)),
+            None => Entry::Vacant(VacantEntry::new(self, node, key)),
+        }
+    }
+}
+
+impl<'a, K, V, const N: usize, H, B> IntoIterator for &'a ChunkedMap<K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, N, H, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, const N: usize, H, B, Q> core::ops::Index<&Q> for ChunkedMap<K, V, N, H, B>
+where
+    K: BorshSerialize + BorshDeserialize + Borrow<Q>,
+    V: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+    Q: BorshSerialize + ToOwned<Owned = K> + Eq + ?Sized,
+{
+    type Output = V;
+
+    fn index(&self, index: &Q) -> &Self::Output {
+        self.get(index)
+            .unwrap_or_else(|| panic_str("key does not exist"))
+    }
+}
+
+impl<K, V, const N: usize, H, B> fmt::Debug for ChunkedMap<K, V, N, H, B>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Map")
+            .field("len", &self.len)
+            .field("buckets", &self.buckets)
+            .field("prefix", &self.values.prefix)
+            .finish()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{Rng, RngCore, SeedableRng};
+    use std::collections::HashMap;
+
+    use super::node::{Node, NodeKey};
+    use super::{ChunkedMap, Entry};
+    use crate::utils::test_iters;
+    use near_sdk::test_utils::test_env::setup_free;
+
+    #[test]
+    fn test_split_buckets() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(1);
+        let mut map = ChunkedMap::<u64, u64, 4>::new(b"m");
+        let mut baseline = HashMap::new();
+        for _ in 0..test_iters(1000) {
+            let (k, v) = (rng.gen::<u64>(), rng.gen::<u64>());
+            assert_eq!(map.insert(k, v), baseline.insert(k, v));
+        }
+        assert_eq!(map.len() as usize, baseline.len());
+        assert!(map.buckets.len() > 1);
+        assert!(matches!(
+            map.values.get(NodeKey::new(0, 0)),
+            Some(Node::Branch)
+        ));
+        for (k, v) in baseline.iter() {
+            assert_eq!(map.get(k), Some(v));
+        }
+
+        // Branch nodes are never rewritten after being split.
+        map.flush();
+        let root_key = [&b"m"[..], &[0]].concat();
+        let root = near_sdk::env::storage_read(&root_key).unwrap();
+        for k in baseline.keys() {
+            map.remove(k);
+        }
+        map.flush();
+        assert_eq!(near_sdk::env::storage_read(&root_key).unwrap(), root);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut map = ChunkedMap::<u32, u32, 2>::new(b"m");
+        for i in 0..50 {
+            *map.entry(i % 10).or_insert(0) += 1;
+        }
+        for i in 0..10 {
+            assert_eq!(map[&i], 5);
+        }
+        match map.entry(3) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 5),
+            Entry::Vacant(_) => panic!("entry should be occupied"),
+        }
+        assert_eq!(map.len(), 9);
+
+        // Inserting into a vacant entry which splits its bucket gives the inserted value.
+        let mut map = ChunkedMap::<u32, u32, 1>::new(b"n");
+        for i in 0..20 {
+            let value = map.entry(i).or_insert(i * 2);
+            assert_eq!(*value, i * 2);
+            *value += 1;
+        }
+        for i in 0..20 {
+            assert_eq!(map[&i], i * 2 + 1);
+        }
+    }
+
+    #[test]
+    fn test_remove_empty_bucket() {
+        let mut map = ChunkedMap::<u8, u8>::new(b"m");
+        map.insert(1, 1);
+        map.flush();
+        assert_eq!(map.remove(&1), Some(1));
+        map.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[derive(Arbitrary, Debug)]
+    enum Op {
+        Insert(u8, u8),
+        Remove(u8),
+        Flush,
+        Reset,
+        Get(u8),
+        EntryInsert(u8, u8),
+        EntryRemove(u8),
+    }
+
+    #[test]
+    fn arbitrary() {
+        setup_free();
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut buf = vec![0; 4096];
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 1024 };
+        for _ in 0..runs {
+            // Clear storage in-between runs
+            near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+            rng.fill_bytes(&mut buf);
+
+            let mut sm = ChunkedMap::<_, _, 2>::new(b"m");
+            let mut mm = HashMap::new();
+            let u = Unstructured::new(&buf);
+            if let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) {
+                for op in ops {
+                    match op {
+                        Op::Insert(k, v) => {
+                            assert_eq!(sm.insert(k, v), mm.insert(k, v));
+                        }
+                        Op::Remove(k) => {
+                            assert_eq!(sm.remove(&k), mm.remove(&k));
+                        }
+                        Op::Flush => {
+                            sm.flush();
+                        }
+                        Op::Reset => {
+                            let serialized = sm.try_to_vec().unwrap();
+                            sm = ChunkedMap::deserialize(&mut serialized.as_slice()).unwrap();
+                        }
+                        Op::Get(k) => {
+                            assert_eq!(sm.get(&k), mm.get(&k));
+                        }
+                        Op::EntryInsert(k, v) => {
+                            let value = sm.entry(k).or_insert(v);
+                            *value = value.wrapping_add(1);
+                            let value = mm.entry(k).or_insert(v);
+                            *value = value.wrapping_add(1);
+                        }
+                        Op::EntryRemove(k) => {
+                            let removed = match sm.entry(k) {
+                                Entry::Occupied(entry) => Some(entry.remove()),
+                                Entry::Vacant(_) => None,
+                            };
+                            assert_eq!(removed, mm.remove(&k));
+                        }
+                    }
+                    assert_eq!(sm.len() as usize, mm.len());
+                }
+            }
+
+            // After all operations, compare both maps
+            for k in 0..=u8::MAX {
+                assert_eq!(sm.get(&k), mm.get(&k));
+            }
+            let mut entries: Vec<_> = sm.iter().map(|(k, v)| (*k, *v)).collect();
+            entries.sort();
+            let mut expected: Vec<_> = mm.into_iter().collect();
+            expected.sort();
+            assert_eq!(entries, expected);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use alloc::vec::Vec;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::index_map::MapKey;
+
+/// Number of hash bits consumed by each level of the trie.
+pub(crate) const BITS_PER_LEVEL: u32 = 4;
+/// Number of children a bucket is split into.
+pub(crate) const FANOUT: u64 = 1 << BITS_PER_LEVEL;
+/// Depth at which all hash bits are consumed, buckets at this depth are never split.
+pub(crate) const MAX_DEPTH: u8 = (u64::BITS / BITS_PER_LEVEL) as u8;
+
+/// Location of a node in the trie, which is the hash prefix shared by all keys under it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, BorshSerialize, BorshDeserialize)]
+pub(crate) struct NodeKey {
+    pub(crate) depth: u8,
+    /// Leading `depth * BITS_PER_LEVEL` bits of the hash.
+    pub(crate) path: u64,
+}
+
+impl NodeKey {
+    /// Node at `depth` on the path of the hash provided.
+    pub(crate) fn new(hash: u64, depth: u8) -> Self {
+        let path = if depth == 0 {
+            0
+        } else {
+            hash >> (u64::BITS - BITS_PER_LEVEL * depth as u32)
+        };
+        Self { depth, path }
+    }
+
+    /// Returns the `i`th child of this node.
+    pub(crate) fn child(&self, i: u64) -> Self {
+        Self {
+            depth: self.depth + 1,
+            path: (self.path << BITS_PER_LEVEL) | i,
+        }
+    }
+}
+
+impl MapKey for NodeKey {
+    const MAX_LEN: usize = 9;
+
+    fn append_to(&self, buf: &mut Vec<u8>) {
+        // Depth is included to avoid paths of different depths mapping to the same key.
+        buf.push(self.depth);
+        let len = (self.depth as u32 * BITS_PER_LEVEL).div_ceil(8) as usize;
+        buf.extend_from_slice(&self.path.to_be_bytes()[8 - len..]);
+    }
+}
+
+/// Node of the trie. A node which is not present in storage is an empty bucket.
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub(crate) enum Node<K, V> {
+    /// Entries whose hash starts with the node's path.
+    Bucket(Vec<(K, V)>),
+    /// Node has been split, entries are in the child nodes. This node is never modified again.
+    Branch,
+}
@@ -0,0 +1,231 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::iter::FusedIterator;
+
+use super::ChunkedSet;
+use crate::hash::{DefaultHasher, KeyHasher};
+use crate::map::Keys;
+use crate::storage::{DefaultStorage, StorageBackend};
+
+/// An iterator over the members of a stored set, in the order of their buckets.
+#[derive(Debug)]
+pub struct Iter<'a, T, const N: usize, H = DefaultHasher, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    keys: Keys<'a, T, (), N, H, B>,
+}
+
+impl<'a, T, const N: usize, H, B> Iter<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(super) fn new(set: &'a ChunkedSet<T, N, H, B>) -> Self {
+        Self {
+            keys: set.map.keys(),
+        }
+    }
+}
+
+impl<'a, T, const N: usize, H, B> Iterator for Iter<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.keys.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+
+    fn count(self) -> usize {
+        self.keys.count()
+    }
+}
+
+impl<'a, T, const N: usize, H, B> ExactSizeIterator for Iter<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, H, B> FusedIterator for Iter<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+}
+
+/// A lazy iterator producing elements in the difference of [`ChunkedSet`]s.
+///
+/// This `struct` is created by the [`ChunkedSet::difference`] method.
+#[derive(Debug)]
+pub struct Difference<'a, T, const N: usize, H = DefaultHasher, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    iter: Iter<'a, T, N, H, B>,
+    other: &'a ChunkedSet<T, N, H, B>,
+}
+
+impl<'a, T, const N: usize, H, B> Difference<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(super) fn new(set: &'a ChunkedSet<T, N, H, B>, other: &'a ChunkedSet<T, N, H, B>) -> Self {
+        Self {
+            iter: set.iter(),
+            other,
+        }
+    }
+}
+
+impl<'a, T, const N: usize, H, B> Iterator for Difference<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|value| !other.contains_ref(value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<'a, T, const N: usize, H, B> FusedIterator for Difference<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+}
+
+/// A lazy iterator producing elements in the intersection of [`ChunkedSet`]s.
+///
+/// This `struct` is created by the [`ChunkedSet::intersection`] method.
+#[derive(Debug)]
+pub struct Intersection<'a, T, const N: usize, H = DefaultHasher, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    iter: Iter<'a, T, N, H, B>,
+    other: &'a ChunkedSet<T, N, H, B>,
+}
+
+impl<'a, T, const N: usize, H, B> Intersection<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(super) fn new(set: &'a ChunkedSet<T, N, H, B>, other: &'a ChunkedSet<T, N, H, B>) -> Self {
+        Self {
+            iter: set.iter(),
+            other,
+        }
+    }
+}
+
+impl<'a, T, const N: usize, H, B> Iterator for Intersection<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|value| other.contains_ref(value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<'a, T, const N: usize, H, B> FusedIterator for Intersection<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+}
+
+/// A lazy iterator producing elements in the union of [`ChunkedSet`]s.
+///
+/// This `struct` is created by the [`ChunkedSet::union`] method.
+#[derive(Debug)]
+pub struct Union<'a, T, const N: usize, H = DefaultHasher, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    /// Iterator over all members of the first set.
+    iter: Iter<'a, T, N, H, B>,
+    /// Members of the second set which are not in the first.
+    rest: Difference<'a, T, N, H, B>,
+}
+
+impl<'a, T, const N: usize, H, B> Union<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(super) fn new(set: &'a ChunkedSet<T, N, H, B>, other: &'a ChunkedSet<T, N, H, B>) -> Self {
+        Self {
+            iter: set.iter(),
+            rest: Difference::new(other, set),
+        }
+    }
+}
+
+impl<'a, T, const N: usize, H, B> Iterator for Union<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().or_else(|| self.rest.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (len, rest_upper) = (self.iter.len(), self.rest.size_hint().1);
+        (len, rest_upper.and_then(|upper| upper.checked_add(len)))
+    }
+}
+
+impl<'a, T, const N: usize, H, B> FusedIterator for Union<'a, T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+}
@@ -0,0 +1,500 @@
+//! A hash set with members persisted to storage in buckets grouped by hash prefix.
+//!
+//! Values in the [`ChunkedSet`] are kept in an in-memory cache and are only persisted on
+//! [`Drop`].
+//!
+//! # Examples
+//!
+//! ```
+//! use near_chunked_collections::ChunkedSet;
+//!
+//! let mut allowlist: ChunkedSet<String> = ChunkedSet::new(b"s");
+//! allowlist.insert("alice.near".to_string());
+//!
+//! assert!(allowlist.contains("alice.near"));
+//! assert!(!allowlist.contains("bob.near"));
+//! ```
+
+mod iter;
+
+use alloc::borrow::ToOwned;
+use core::borrow::Borrow;
+use core::fmt;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub use self::iter::{Difference, Intersection, Iter, Union};
+
+use crate::hash::{DefaultHasher, KeyHasher};
+use crate::map::ChunkedMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+
+/// A hash set which stores its content on the trie, with members grouped into buckets of up to
+/// `N` members. This implementation will load and store values in the underlying storage lazily.
+///
+/// Members are stored in the same layout as the keys of a [`ChunkedMap`]: buckets are the nodes
+/// of a trie over the hash of each member, and are split when they exceed `N` members. Checking
+/// if a value is in the set only reads the bucket it would be in from storage.
+///
+/// This implementation will cache all changes and loads and only updates values that are changed
+/// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
+/// in storage before the variable is dropped by using [`ChunkedSet::flush`].
+///
+/// # Examples
+/// ```
+/// use near_chunked_collections::ChunkedSet;
+///
+/// let mut set: ChunkedSet<u32> = ChunkedSet::new(b"s");
+/// assert!(set.insert(1));
+/// assert!(!set.insert(1));
+/// assert!(set.contains(&1));
+///
+/// assert!(set.remove(&1));
+/// assert!(set.is_empty());
+/// ```
+pub struct ChunkedSet<T, const N: usize = 5, H = DefaultHasher, B = DefaultStorage>
+where
+    T: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    pub(crate) map: ChunkedMap<T, (), N, H, B>,
+}
+
+impl<T, const N: usize, H> ChunkedSet<T, N, H, DefaultStorage>
+where
+    T: BorshSerialize,
+    H: KeyHasher,
+{
+    /// Create new set with zero members. Prefixes storage accesss with the prefix provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedSet;
+    ///
+    /// let mut set: ChunkedSet<u8> = ChunkedSet::new(b"s");
+    /// ```
+    pub fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self::new_in(prefix, DefaultStorage::default())
+    }
+}
+
+impl<T, const N: usize, H, B> BorshSerialize for ChunkedSet<T, N, H, B>
+where
+    T: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.map, writer)
+    }
+}
+
+impl<T, const N: usize, H, B> BorshDeserialize for ChunkedSet<T, N, H, B>
+where
+    T: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend + Default,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            map: BorshDeserialize::deserialize(buf)?,
+        })
+    }
+}
+
+impl<T, const N: usize, H, B> ChunkedSet<T, N, H, B>
+where
+    T: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    /// Returns the number of members in the set.
+    pub fn len(&self) -> u32 {
+        self.map.len()
+    }
+
+    /// Returns `true` if the set contains no members.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Create new set with zero members, which is persisted to the storage backend provided.
+    /// Prefixes storage accesss with the prefix provided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::hash::DefaultHasher;
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedSet;
+    ///
+    /// let mut set: ChunkedSet<u8, 5, DefaultHasher, _> =
+    ///     ChunkedSet::new_in(b"s", InMemoryStorage::new());
+    /// ```
+    pub fn new_in<S>(prefix: S, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            map: ChunkedMap::new_in(prefix, storage),
+        }
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    ///
+    /// This operation is performed on [`Drop`], but this method can be called to persist
+    /// intermediate writes in cases where [`Drop`] is not called or to identify storage changes.
+    pub fn flush(&mut self) {
+        self.map.flush();
+    }
+}
+
+impl<T, const N: usize, H, B> ChunkedSet<T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    /// Adds a value to the set.
+    ///
+    /// Returns whether the value was newly inserted, `false` if the set already contained it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    pub fn insert(&mut self, value: T) -> bool
+    where
+        T: Eq,
+    {
+        self.map.insert(value, ()).is_none()
+    }
+
+    /// Removes a value from the set. Returns whether the value was present in the set.
+    ///
+    /// The value may be any borrowed form of the set's value type, but the Borsh serialization
+    /// of the borrowed form must match that of the value type.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: BorshSerialize + ToOwned<Owned = T> + Eq + ?Sized,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    /// Returns `true` if the set contains the value.
+    ///
+    /// The value may be any borrowed form of the set's value type, but the Borsh serialization
+    /// of the borrowed form must match that of the value type.
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: BorshSerialize + ToOwned<Owned = T> + Eq + ?Sized,
+    {
+        self.map.contains_key(value)
+    }
+
+    /// Returns an iterator over the members of the set. This iterator will lazily load any
+    /// buckets iterated over from storage.
+    ///
+    /// Members are yielded in the order of the buckets they are in, which depends on their hash.
+    pub fn iter(&self) -> Iter<'_, T, N, H, B> {
+        Iter::new(self)
+    }
+
+    /// Visits the values representing the union, i.e., all the values in `self` or `other`,
+    /// without duplicates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedSet;
+    ///
+    /// let mut a: ChunkedSet<u32> = ChunkedSet::new(b"a");
+    /// a.extend([1, 2, 3]);
+    /// let mut b: ChunkedSet<u32> = ChunkedSet::new(b"b");
+    /// b.extend([4, 2, 3, 4]);
+    ///
+    /// let mut union: Vec<_> = a.union(&b).copied().collect();
+    /// union.sort();
+    /// assert_eq!(union, [1, 2, 3, 4]);
+    /// ```
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T, N, H, B>
+    where
+        T: Eq,
+    {
+        Union::new(self, other)
+    }
+
+    /// Visits the values representing the intersection, i.e., the values that are both in
+    /// `self` and `other`.
+    ///
+    /// The smaller of the two sets is iterated, with each value looked up in the larger one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedSet;
+    ///
+    /// let mut a: ChunkedSet<u32> = ChunkedSet::new(b"a");
+    /// a.extend([1, 2, 3]);
+    /// let mut b: ChunkedSet<u32> = ChunkedSet::new(b"b");
+    /// b.extend([4, 2, 3, 4]);
+    ///
+    /// let mut intersection: Vec<_> = a.intersection(&b).copied().collect();
+    /// intersection.sort();
+    /// assert_eq!(intersection, [2, 3]);
+    /// ```
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, T, N, H, B>
+    where
+        T: Eq,
+    {
+        if self.len() <= other.len() {
+            Intersection::new(self, other)
+        } else {
+            Intersection::new(other, self)
+        }
+    }
+
+    /// Visits the values representing the difference, i.e., the values that are in `self` but
+    /// not in `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedSet;
+    ///
+    /// let mut a: ChunkedSet<u32> = ChunkedSet::new(b"a");
+    /// a.extend([1, 2, 3]);
+    /// let mut b: ChunkedSet<u32> = ChunkedSet::new(b"b");
+    /// b.extend([4, 2, 3, 4]);
+    ///
+    /// assert_eq!(a.difference(&b).copied().collect::<Vec<_>>(), [1]);
+    /// assert_eq!(b.difference(&a).copied().collect::<Vec<_>>(), [4]);
+    /// ```
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T, N, H, B>
+    where
+        T: Eq,
+    {
+        Difference::new(self, other)
+    }
+
+    /// Returns `true` if the set contains the value, without requiring the value to be owned.
+    fn contains_ref(&self, value: &T) -> bool
+    where
+        T: Eq,
+    {
+        self.map.find(value).1.is_some()
+    }
+}
+
+impl<'a, T, const N: usize, H, B> IntoIterator for &'a ChunkedSet<T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, N, H, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, const N: usize, H, B> Extend<T> for ChunkedSet<T, N, H, B>
+where
+    T: BorshSerialize + BorshDeserialize + Eq,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<T, const N: usize, H, B> fmt::Debug for ChunkedSet<T, N, H, B>
+where
+    T: BorshSerialize,
+    H: KeyHasher,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Set")
+            .field("len", &self.map.len)
+            .field("prefix", &self.map.values.prefix)
+            .finish()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{Rng, RngCore, SeedableRng};
+    use std::collections::HashSet;
+
+    use super::ChunkedSet;
+    use crate::hash::DefaultHasher;
+    use crate::storage::{NearStorage, StorageBackend};
+    use crate::utils::test_iters;
+    use near_sdk::test_utils::test_env::setup_free;
+
+    #[test]
+    fn test_single_read_lookup() {
+        thread_local! {
+            static READS: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+        }
+
+        /// NEAR storage which counts the values read from it.
+        #[derive(Default)]
+        struct CountingStorage;
+
+        impl StorageBackend for CountingStorage {
+            fn read(&self, key: &[u8]) -> Option<Vec<u8>> {
+                READS.with(|reads| reads.set(reads.get() + 1));
+                NearStorage.read(key)
+            }
+
+            fn write(&mut self, key: &[u8], value: &[u8]) {
+                NearStorage.write(key, value)
+            }
+
+            fn remove(&mut self, key: &[u8]) {
+                NearStorage.remove(key)
+            }
+        }
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(2);
+        let mut set = ChunkedSet::<u64, 16, DefaultHasher, _>::new_in(b"s", CountingStorage);
+        let values: Vec<u64> = (0..test_iters(2000)).map(|_| rng.gen()).collect();
+        set.extend(values.iter().copied());
+        assert_eq!(set.len() as usize, values.len());
+        let serialized = set.try_to_vec().unwrap();
+        drop(set);
+
+        // Every lookup reads only the bucket the member is in, whatever its depth.
+        for value in values.iter() {
+            let set =
+                ChunkedSet::<u64, 16, DefaultHasher, CountingStorage>::try_from_slice(&serialized)
+                    .unwrap();
+            READS.with(|reads| reads.set(0));
+            assert!(set.contains(value));
+            assert_eq!(READS.with(|reads| reads.get()), 1);
+        }
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let mut a = ChunkedSet::<u32, 2>::new(b"a");
+        let mut b = ChunkedSet::<u32, 2>::new(b"b");
+        a.extend(0..40);
+        b.extend((20..60).step_by(2));
+        let ha: HashSet<u32> = (0..40).collect();
+        let hb: HashSet<u32> = (20..60).step_by(2).collect();
+
+        let sorted = |iter: &mut dyn Iterator<Item = u32>| {
+            let mut values: Vec<_> = iter.collect();
+            values.sort();
+            values
+        };
+        assert_eq!(
+            sorted(&mut a.union(&b).copied()),
+            sorted(&mut ha.union(&hb).copied())
+        );
+        assert_eq!(
+            sorted(&mut b.union(&a).copied()),
+            sorted(&mut ha.union(&hb).copied())
+        );
+        assert_eq!(
+            sorted(&mut a.intersection(&b).copied()),
+            sorted(&mut ha.intersection(&hb).copied())
+        );
+        assert_eq!(
+            sorted(&mut b.intersection(&a).copied()),
+            sorted(&mut ha.intersection(&hb).copied())
+        );
+        assert_eq!(
+            sorted(&mut a.difference(&b).copied()),
+            sorted(&mut ha.difference(&hb).copied())
+        );
+        assert_eq!(
+            sorted(&mut b.difference(&a).copied()),
+            sorted(&mut hb.difference(&ha).copied())
+        );
+    }
+
+    #[derive(Arbitrary, Debug)]
+    enum Op {
+        Insert(u8),
+        Remove(u8),
+        Flush,
+        Reset,
+        Contains(u8),
+    }
+
+    #[test]
+    fn arbitrary() {
+        setup_free();
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut buf = vec![0; 4096];
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 1024 };
+        for _ in 0..runs {
+            // Clear storage in-between runs
+            near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+            rng.fill_bytes(&mut buf);
+
+            let mut ss = ChunkedSet::<_, 2>::new(b"s");
+            let mut ms = HashSet::new();
+            let u = Unstructured::new(&buf);
+            if let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) {
+                for op in ops {
+                    match op {
+                        Op::Insert(v) => {
+                            assert_eq!(ss.insert(v), ms.insert(v));
+                        }
+                        Op::Remove(v) => {
+                            assert_eq!(ss.remove(&v), ms.remove(&v));
+                        }
+                        Op::Flush => {
+                            ss.flush();
+                        }
+                        Op::Reset => {
+                            let serialized = ss.try_to_vec().unwrap();
+                            ss = ChunkedSet::deserialize(&mut serialized.as_slice()).unwrap();
+                        }
+                        Op::Contains(v) => {
+                            assert_eq!(ss.contains(&v), ms.contains(&v));
+                        }
+                    }
+                    assert_eq!(ss.len() as usize, ms.len());
+                }
+            }
+
+            // After all operations, compare both sets
+            let mut members: Vec<_> = ss.iter().copied().collect();
+            members.sort();
+            let mut expected: Vec<_> = ms.into_iter().collect();
+            expected.sort();
+            assert_eq!(members, expected);
+        }
+    }
+}
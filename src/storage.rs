@@ -0,0 +1,166 @@
+//! Storage backends that collections persist their values to.
+//!
+//! Collections only require a raw bytes key-value store, which is described by the
+//! [`StorageBackend`] trait. With the `near` feature enabled, `NearStorage` persists values to
+//! the NEAR contract storage and is the default for all collections. [`InMemoryStorage`] keeps
+//! values in memory, which is useful for using the collections outside of a contract and for
+//! testing, and is the default when the `near` feature is disabled.
+
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+#[cfg(feature = "near")]
+use near_sdk::env;
+
+#[cfg(feature = "near")]
+pub use near_sdk::IntoStorageKey;
+
+/// Converts Self into a [`Vec<u8>`] that is used for a storage prefix.
+#[cfg(not(feature = "near"))]
+pub trait IntoStorageKey {
+    /// Consumes self and returns the storage key.
+    fn into_storage_key(self) -> Vec<u8>;
+}
+
+#[cfg(not(feature = "near"))]
+impl IntoStorageKey for Vec<u8> {
+    fn into_storage_key(self) -> Vec<u8> {
+        self
+    }
+}
+
+#[cfg(not(feature = "near"))]
+impl IntoStorageKey for &[u8] {
+    fn into_storage_key(self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+#[cfg(not(feature = "near"))]
+impl<const N: usize> IntoStorageKey for &[u8; N] {
+    fn into_storage_key(self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+#[cfg(not(feature = "near"))]
+impl IntoStorageKey for u8 {
+    fn into_storage_key(self) -> Vec<u8> {
+        alloc::vec![self]
+    }
+}
+
+/// Storage backend used by collections when none is specified.
+#[cfg(feature = "near")]
+pub type DefaultStorage = NearStorage;
+
+/// Storage backend used by collections when none is specified.
+#[cfg(not(feature = "near"))]
+pub type DefaultStorage = InMemoryStorage;
+
+/// A key-value store of raw bytes that collections read from and write to.
+///
+/// Collections only read from the backend when a value is first accessed and only write to it
+/// when flushed, so implementations do not need to do any caching of their own.
+pub trait StorageBackend {
+    /// Reads the value stored at `key`, if one exists.
+    fn read(&self, key: &[u8]) -> Option<Vec<u8>>;
+
+    /// Writes `value` to `key`, replacing any existing value.
+    fn write(&mut self, key: &[u8], value: &[u8]);
+
+    /// Removes the value stored at `key`, if one exists.
+    fn remove(&mut self, key: &[u8]);
+
+    /// Returns `true` if a value is stored at `key`.
+    fn has(&self, key: &[u8]) -> bool {
+        self.read(key).is_some()
+    }
+}
+
+/// Storage backend which persists values to the NEAR contract storage through [`env`].
+///
+/// This is the default backend for all collections.
+#[cfg(feature = "near")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NearStorage;
+
+#[cfg(feature = "near")]
+impl StorageBackend for NearStorage {
+    fn read(&self, key: &[u8]) -> Option<Vec<u8>> {
+        env::storage_read(key)
+    }
+
+    fn write(&mut self, key: &[u8], value: &[u8]) {
+        env::storage_write(key, value);
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        env::storage_remove(key);
+    }
+
+    fn has(&self, key: &[u8]) -> bool {
+        env::storage_has_key(key)
+    }
+}
+
+/// Storage backend which keeps values in a [`BTreeMap`] in memory.
+///
+/// Clones of this type share the same underlying map, so multiple collections can be backed by
+/// the same storage and values can be inspected after the collections are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use near_chunked_collections::storage::InMemoryStorage;
+/// use near_chunked_collections::ChunkedVector;
+///
+/// let storage = InMemoryStorage::new();
+/// let mut vec: ChunkedVector<u8, 5, _> = ChunkedVector::new_in(b"v", storage.clone());
+/// vec.extend([1, 2, 3]);
+/// drop(vec);
+///
+/// // One chunk was written on drop.
+/// assert_eq!(storage.len(), 1);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct InMemoryStorage {
+    map: Rc<RefCell<BTreeMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl InMemoryStorage {
+    /// Creates an empty in-memory storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of values stored.
+    pub fn len(&self) -> usize {
+        self.map.borrow().len()
+    }
+
+    /// Returns `true` if no values are stored.
+    pub fn is_empty(&self) -> bool {
+        self.map.borrow().is_empty()
+    }
+}
+
+impl StorageBackend for InMemoryStorage {
+    fn read(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.map.borrow().get(key).cloned()
+    }
+
+    fn write(&mut self, key: &[u8], value: &[u8]) {
+        self.map.borrow_mut().insert(key.to_vec(), value.to_vec());
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.map.borrow_mut().remove(key);
+    }
+
+    fn has(&self, key: &[u8]) -> bool {
+        self.map.borrow().contains_key(key)
+    }
+}
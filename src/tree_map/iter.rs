@@ -0,0 +1,203 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::iter::FusedIterator;
+
+use super::ChunkedTreeMap;
+use crate::storage::{DefaultStorage, StorageBackend};
+
+/// Entries of a loaded leaf, along with the separator keys bounding it in its ancestors.
+#[derive(Debug)]
+pub(crate) struct LeafRef<'a, K, V> {
+    pub(crate) keys: &'a [K],
+    pub(crate) values: &'a [V],
+    /// Separator before the leaf, every key of the previous leaf is less than it.
+    pub(crate) lower: Option<&'a K>,
+    /// Separator after the leaf, every key of the next leaf is greater or equal to it.
+    pub(crate) upper: Option<&'a K>,
+}
+
+impl<'a, K, V> LeafRef<'a, K, V> {
+    pub(crate) fn entry(&self, pos: usize) -> (&'a K, &'a V) {
+        (&self.keys[pos], &self.values[pos])
+    }
+}
+
+/// Position of an entry within a loaded leaf.
+pub(crate) type Cursor<'a, K, V> = (LeafRef<'a, K, V>, usize);
+
+/// An iterator over a sub-range of the entries of a stored tree map, in ascending key order.
+#[derive(Debug)]
+pub struct Range<'a, K, V, const N: usize, B = DefaultStorage>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Underlying map to iterate through
+    map: &'a ChunkedTreeMap<K, V, N, B>,
+    /// Leaf of the next entry to yield from the front, and its position.
+    front: Option<Cursor<'a, K, V>>,
+    /// Leaf of the next entry to yield from the back, and the position after it.
+    back: Option<Cursor<'a, K, V>>,
+}
+
+impl<'a, K, V, const N: usize, B> Range<'a, K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(
+        map: &'a ChunkedTreeMap<K, V, N, B>,
+        front: Option<Cursor<'a, K, V>>,
+        back: Option<Cursor<'a, K, V>>,
+    ) -> Self {
+        let mut range = Self { map, front, back };
+        let empty = match range.bounds() {
+            Some((first, last)) => first > last,
+            None => true,
+        };
+        if empty {
+            range.finish();
+        }
+        range
+    }
+
+    /// Returns the keys of the next entries to yield from the front and back.
+    fn bounds(&self) -> Option<(&'a K, &'a K)> {
+        let (front, pos) = self.front.as_ref()?;
+        let (back, end) = self.back.as_ref()?;
+        Some((&front.keys[*pos], &back.keys[*end - 1]))
+    }
+
+    fn finish(&mut self) {
+        self.front = None;
+        self.back = None;
+    }
+}
+
+impl<'a, K, V, const N: usize, B> Iterator for Range<'a, K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (first, last) = self.bounds()?;
+        let (leaf, pos) = self.front.take()?;
+        let entry = leaf.entry(pos);
+        if first == last {
+            self.finish();
+        } else {
+            self.front = self.map.seek_front(leaf, pos + 1);
+        }
+        Some(entry)
+    }
+}
+
+impl<'a, K, V, const N: usize, B> DoubleEndedIterator for Range<'a, K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (first, last) = self.bounds()?;
+        let (leaf, end) = self.back.take()?;
+        let entry = leaf.entry(end - 1);
+        if first == last {
+            self.finish();
+        } else {
+            self.back = self.map.seek_back(leaf, end - 1);
+        }
+        Some(entry)
+    }
+}
+
+impl<'a, K, V, const N: usize, B> FusedIterator for Range<'a, K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+/// An iterator over the entries of a stored tree map, in ascending key order.
+#[derive(Debug)]
+pub struct Iter<'a, K, V, const N: usize, B = DefaultStorage>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    range: Range<'a, K, V, N, B>,
+    /// Number of entries left to yield.
+    remaining: u32,
+}
+
+impl<'a, K, V, const N: usize, B> Iter<'a, K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(map: &'a ChunkedTreeMap<K, V, N, B>) -> Self {
+        Self {
+            range: map.range::<K, _>(..),
+            remaining: map.len(),
+        }
+    }
+}
+
+impl<'a, K, V, const N: usize, B> Iterator for Iter<'a, K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.range.next()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining as usize;
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining as usize
+    }
+}
+
+impl<'a, K, V, const N: usize, B> DoubleEndedIterator for Iter<'a, K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.range.next_back()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+}
+
+impl<'a, K, V, const N: usize, B> ExactSizeIterator for Iter<'a, K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, K, V, const N: usize, B> FusedIterator for Iter<'a, K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
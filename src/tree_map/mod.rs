@@ -0,0 +1,964 @@
+//! An ordered map based on a B+ tree, with every node persisted to storage as a single entry and
+//! lazily loaded.
+//!
+//! Values in the [`ChunkedTreeMap`] are kept in an in-memory cache and are only persisted on
+//! [`Drop`].
+//!
+//! # Examples
+//!
+//! ```
+//! use near_chunked_collections::ChunkedTreeMap;
+//!
+//! let mut map: ChunkedTreeMap<u64, String> = ChunkedTreeMap::new(b"t");
+//! map.insert(30, "c".to_string());
+//! map.insert(10, "a".to_string());
+//! map.insert(20, "b".to_string());
+//!
+//! assert_eq!(map.min(), Some((&10, &"a".to_string())));
+//! assert_eq!(map.floor(&25), Some((&20, &"b".to_string())));
+//! assert_eq!(map.range(15..).map(|(k, _)| *k).collect::<Vec<_>>(), &[20, 30]);
+//! ```
+
+mod iter;
+mod node;
+
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::ops::{Bound, RangeBounds};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub use self::iter::{Iter, Range};
+
+use self::iter::{Cursor, LeafRef};
+use self::node::Node;
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+use crate::utils::{expect_consistent_state, panic_str};
+
+const ERR_LENGTH_OVERFLOW: &str = "Map length overflow";
+const ERR_NODE_CAPACITY: &str = "Tree nodes must hold at least 2 keys";
+
+/// An ordered map implemented as a B+ tree, which stores its content on the trie. This
+/// implementation will load and store values in the underlying storage lazily.
+///
+/// Uses the following map: node id -> node of up to `N` keys. Entries are only stored in leaf
+/// nodes, sorted by key, and internal nodes hold copies of keys which separate their children.
+/// Nodes are split when they exceed `N` keys and are merged with a sibling, or take keys from
+/// it, when they fall below `N / 2` keys. Looking up a key reads `O(log_N(len))` nodes, and
+/// iterating over a range only reads the leaves the range spans once the internal nodes above
+/// them are cached.
+///
+/// Ids of nodes which are merged away are not reused. `N` must be at least 2.
+///
+/// This implementation will cache all changes and loads and only updates values that are changed
+/// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
+/// in storage before the variable is dropped by using [`ChunkedTreeMap::flush`].
+///
+/// # Examples
+/// ```
+/// use near_chunked_collections::ChunkedTreeMap;
+///
+/// let mut map: ChunkedTreeMap<u32, u32> = ChunkedTreeMap::new(b"t");
+/// for i in 0..100 {
+///     map.insert(i * 10, i);
+/// }
+///
+/// assert_eq!(map.ceil(&55), Some((&60, &6)));
+/// assert_eq!(map.remove(&60), Some(6));
+/// assert_eq!(map.ceil(&55), Some((&70, &7)));
+/// assert_eq!(map.range(100..=120).count(), 3);
+/// assert_eq!(map.max(), Some((&990, &99)));
+/// ```
+pub struct ChunkedTreeMap<K, V, const N: usize = 5, B = DefaultStorage>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    B: StorageBackend,
+{
+    pub(crate) len: u32,
+    /// Id of the root node, if the map is not empty.
+    pub(crate) root: Option<u32>,
+    /// Id to assign to the next node created.
+    pub(crate) next_node: u32,
+    pub(crate) nodes: IndexMap<Node<K, V>, B>,
+}
+
+impl<K, V, const N: usize> ChunkedTreeMap<K, V, N, DefaultStorage>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+{
+    /// Create new map with zero entries. Prefixes storage accesss with the prefix provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedTreeMap;
+    ///
+    /// let mut map: ChunkedTreeMap<u8, u8> = ChunkedTreeMap::new(b"t");
+    /// ```
+    pub fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self::new_in(prefix, DefaultStorage::default())
+    }
+}
+
+impl<K, V, const N: usize, B> Drop for ChunkedTreeMap<K, V, N, B>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+impl<K, V, const N: usize, B> BorshSerialize for ChunkedTreeMap<K, V, N, B>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    B: StorageBackend,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.len, writer)?;
+        BorshSerialize::serialize(&self.root, writer)?;
+        BorshSerialize::serialize(&self.next_node, writer)?;
+        BorshSerialize::serialize(&self.nodes, writer)?;
+        Ok(())
+    }
+}
+
+impl<K, V, const N: usize, B> BorshDeserialize for ChunkedTreeMap<K, V, N, B>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    B: StorageBackend + Default,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            len: BorshDeserialize::deserialize(buf)?,
+            root: BorshDeserialize::deserialize(buf)?,
+            next_node: BorshDeserialize::deserialize(buf)?,
+            nodes: BorshDeserialize::deserialize(buf)?,
+        })
+    }
+}
+
+impl<K, V, const N: usize, B> ChunkedTreeMap<K, V, N, B>
+where
+    K: BorshSerialize,
+    V: BorshSerialize,
+    B: StorageBackend,
+{
+    /// Minimum number of keys of every node other than the root.
+    const MIN_LEN: usize = N / 2;
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Create new map with zero entries, which is persisted to the storage backend provided.
+    /// Prefixes storage accesss with the prefix provided.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is less than 2.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedTreeMap;
+    ///
+    /// let mut map: ChunkedTreeMap<u8, u8, 5, _> =
+    ///     ChunkedTreeMap::new_in(b"t", InMemoryStorage::new());
+    /// ```
+    pub fn new_in<S>(prefix: S, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        if N < 2 {
+            panic_str(ERR_NODE_CAPACITY);
+        }
+        Self {
+            len: 0,
+            root: None,
+            next_node: 0,
+            nodes: IndexMap::new_in(prefix, storage),
+        }
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    ///
+    /// This operation is performed on [`Drop`], but this method can be called to persist
+    /// intermediate writes in cases where [`Drop`] is not called or to identify storage changes.
+    pub fn flush(&mut self) {
+        self.nodes.flush();
+    }
+
+    /// Stores a new node, returning its id.
+    fn create_node(&mut self, node: Node<K, V>) -> u32 {
+        let id = self.next_node;
+        self.next_node = self
+            .next_node
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_LENGTH_OVERFLOW));
+        self.nodes.set(id, Some(node));
+        id
+    }
+}
+
+impl<K, V, const N: usize, B> ChunkedTreeMap<K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn node(&self, id: u32) -> &Node<K, V> {
+        expect_consistent_state(self.nodes.get(id))
+    }
+
+    /// Descends from the root to a leaf, choosing the child at the position returned by `child`
+    /// from the keys of each internal node.
+    fn descend(&self, child: impl Fn(&[K]) -> usize) -> Option<LeafRef<'_, K, V>> {
+        let mut id = self.root?;
+        let mut lower = None;
+        let mut upper = None;
+        loop {
+            match self.node(id) {
+                Node::Internal { keys, children } => {
+                    let i = child(keys);
+                    // Separators deeper in the tree are always tighter bounds.
+                    if i > 0 {
+                        lower = Some(&keys[i - 1]);
+                    }
+                    if i < keys.len() {
+                        upper = Some(&keys[i]);
+                    }
+                    id = children[i];
+                }
+                Node::Leaf { keys, values } => {
+                    return Some(LeafRef {
+                        keys,
+                        values,
+                        lower,
+                        upper,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Returns the ids of the internal nodes on the path to the leaf which would contain `key`,
+    /// along with the position of the child taken, and the id of the leaf.
+    fn path<Q>(&self, key: &Q) -> Option<(Vec<(u32, usize)>, u32)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut id = self.root?;
+        let mut path = Vec::new();
+        while let Node::Internal { keys, children } = self.node(id) {
+            let i = keys.partition_point(|k| k.borrow() <= key);
+            path.push((id, i));
+            id = children[i];
+        }
+        Some((path, id))
+    }
+
+    /// Returns the cursor at `pos` of the leaf, moving to the following leaves if the position
+    /// is past the end of it.
+    pub(crate) fn seek_front<'a>(
+        &'a self,
+        mut leaf: LeafRef<'a, K, V>,
+        mut pos: usize,
+    ) -> Option<Cursor<'a, K, V>> {
+        while pos == leaf.keys.len() {
+            let separator = leaf.upper?;
+            leaf = expect_consistent_state(
+                self.descend(|keys| keys.partition_point(|k| k <= separator)),
+            );
+            pos = 0;
+        }
+        Some((leaf, pos))
+    }
+
+    /// Returns the cursor ending at `end` of the leaf, moving to the preceding leaves if there
+    /// are no entries before the position.
+    pub(crate) fn seek_back<'a>(
+        &'a self,
+        mut leaf: LeafRef<'a, K, V>,
+        mut end: usize,
+    ) -> Option<Cursor<'a, K, V>> {
+        while end == 0 {
+            let separator = leaf.lower?;
+            leaf = expect_consistent_state(
+                self.descend(|keys| keys.partition_point(|k| k < separator)),
+            );
+            end = leaf.keys.len();
+        }
+        Some((leaf, end))
+    }
+
+    /// Returns the cursor at the first entry within the bound.
+    fn lower_cursor<Q>(&self, bound: Bound<&Q>) -> Option<Cursor<'_, K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (leaf, pos) = match bound {
+            Bound::Included(key) => {
+                let leaf = self.descend(|keys| keys.partition_point(|k| k.borrow() <= key))?;
+                let pos = leaf.keys.partition_point(|k| k.borrow() < key);
+                (leaf, pos)
+            }
+            Bound::Excluded(key) => {
+                let leaf = self.descend(|keys| keys.partition_point(|k| k.borrow() <= key))?;
+                let pos = leaf.keys.partition_point(|k| k.borrow() <= key);
+                (leaf, pos)
+            }
+            Bound::Unbounded => (self.descend(|_| 0)?, 0),
+        };
+        self.seek_front(leaf, pos)
+    }
+
+    /// Returns the cursor ending after the last entry within the bound.
+    fn upper_cursor<Q>(&self, bound: Bound<&Q>) -> Option<Cursor<'_, K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (leaf, end) = match bound {
+            Bound::Included(key) => {
+                let leaf = self.descend(|keys| keys.partition_point(|k| k.borrow() <= key))?;
+                let end = leaf.keys.partition_point(|k| k.borrow() <= key);
+                (leaf, end)
+            }
+            Bound::Excluded(key) => {
+                let leaf = self.descend(|keys| keys.partition_point(|k| k.borrow() < key))?;
+                let end = leaf.keys.partition_point(|k| k.borrow() < key);
+                (leaf, end)
+            }
+            Bound::Unbounded => {
+                let leaf = self.descend(|keys| keys.len())?;
+                let end = leaf.keys.len();
+                (leaf, end)
+            }
+        };
+        self.seek_back(leaf, end)
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedTreeMap;
+    ///
+    /// let mut map: ChunkedTreeMap<u32, u32> = ChunkedTreeMap::new(b"t");
+    /// map.insert(1, 2);
+    ///
+    /// assert_eq!(map.get(&1), Some(&2));
+    /// assert_eq!(map.get(&2), None);
+    /// ```
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let leaf = self.descend(|keys| keys.partition_point(|k| k.borrow() <= key))?;
+        let pos = leaf.keys.binary_search_by(|k| k.borrow().cmp(key)).ok()?;
+        Some(&leaf.values[pos])
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// Only the leaf containing the key is marked as modified.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (_, leaf) = self.path(key)?;
+        match self.nodes.get_mut(leaf)? {
+            Node::Leaf { keys, values } => {
+                let pos = keys.binary_search_by(|k| k.borrow().cmp(key)).ok()?;
+                Some(&mut values[pos])
+            }
+            Node::Internal { .. } => panic_str("inconsistent state"),
+        }
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned. Otherwise the value is
+    /// updated and the old value is returned.
+    ///
+    /// Internal nodes on the path to the leaf are only modified if the leaf is split.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedTreeMap;
+    ///
+    /// let mut map: ChunkedTreeMap<u32, u32> = ChunkedTreeMap::new(b"t");
+    /// assert_eq!(map.insert(37, 1), None);
+    /// assert_eq!(map.insert(37, 2), Some(1));
+    /// assert_eq!(map[&37], 2);
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (path, leaf) = match self.path(&key) {
+            Some(path) => path,
+            None => {
+                let root = self.create_node(Node::Leaf {
+                    keys: alloc::vec![key],
+                    values: alloc::vec![value],
+                });
+                self.root = Some(root);
+                self.len = 1;
+                return None;
+            }
+        };
+
+        match expect_consistent_state(self.nodes.get_mut(leaf)) {
+            Node::Leaf { keys, values } => match keys.binary_search(&key) {
+                Ok(pos) => return Some(core::mem::replace(&mut values[pos], value)),
+                Err(pos) => {
+                    keys.insert(pos, key);
+                    values.insert(pos, value);
+                }
+            },
+            Node::Internal { .. } => panic_str("inconsistent state"),
+        }
+        self.len = self
+            .len
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_LENGTH_OVERFLOW));
+
+        // Split overflowing nodes, moving up the tree until a node has space for the separator.
+        let mut id = leaf;
+        for (parent, i) in path.into_iter().rev() {
+            // Key was newly inserted, so `None` is returned once nothing is left to split.
+            let (separator, right) = self.split_overflow(id)?;
+            match expect_consistent_state(self.nodes.get_mut(parent)) {
+                Node::Internal { keys, children } => {
+                    keys.insert(i, separator);
+                    children.insert(i + 1, right);
+                }
+                Node::Leaf { .. } => panic_str("inconsistent state"),
+            }
+            id = parent;
+        }
+        if let Some((separator, right)) = self.split_overflow(id) {
+            // Root was split, the tree grows by one level.
+            let root = self.create_node(Node::Internal {
+                keys: alloc::vec![separator],
+                children: alloc::vec![id, right],
+            });
+            self.root = Some(root);
+        }
+        None
+    }
+
+    /// Splits the node if it exceeds the capacity, returning the separator key and the id of
+    /// the new node after it.
+    fn split_overflow(&mut self, id: u32) -> Option<(K, u32)> {
+        if self.node(id).len() <= N {
+            return None;
+        }
+        let (separator, right) = expect_consistent_state(self.nodes.get_mut(id)).split();
+        Some((separator, self.create_node(right)))
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key was previously in
+    /// the map.
+    ///
+    /// If the leaf falls below the minimum number of keys, keys are taken from a sibling or the
+    /// leaf is merged into it, which can propagate up the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedTreeMap;
+    ///
+    /// let mut map: ChunkedTreeMap<u32, u32> = ChunkedTreeMap::new(b"t");
+    /// map.insert(1, 2);
+    ///
+    /// assert_eq!(map.remove(&1), Some(2));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (path, leaf) = self.path(key)?;
+        let Node::Leaf { keys, .. } = self.node(leaf) else {
+            panic_str("inconsistent state");
+        };
+        let pos = keys.binary_search_by(|k| k.borrow().cmp(key)).ok()?;
+        let value = match expect_consistent_state(self.nodes.get_mut(leaf)) {
+            Node::Leaf { keys, values } => {
+                keys.remove(pos);
+                values.remove(pos)
+            }
+            Node::Internal { .. } => panic_str("inconsistent state"),
+        };
+        self.len -= 1;
+
+        // Rebalance underflowing nodes, moving up the tree while parents lose a key.
+        let mut id = leaf;
+        for (parent, i) in path.into_iter().rev() {
+            if self.node(id).len() >= Self::MIN_LEN {
+                break;
+            }
+            self.rebalance(parent, i);
+            id = parent;
+        }
+
+        let root = expect_consistent_state(self.root);
+        match self.node(root) {
+            Node::Internal { keys, children } if keys.is_empty() => {
+                // Root has a single child left, the tree shrinks by one level.
+                self.root = Some(children[0]);
+                self.nodes.set(root, None);
+            }
+            Node::Leaf { keys, .. } if keys.is_empty() => {
+                self.root = None;
+                self.nodes.set(root, None);
+            }
+            _ => (),
+        }
+        Some(value)
+    }
+
+    /// Restores the minimum length of the child at position `i` of the parent, by taking a key
+    /// from a sibling with spare keys or merging it with a sibling.
+    fn rebalance(&mut self, parent: u32, i: usize) {
+        let Node::Internal { children, .. } = self.node(parent) else {
+            panic_str("inconsistent state");
+        };
+        let left = i.checked_sub(1).map(|l| children[l]);
+        let right = children.get(i + 1).copied();
+        let child = children[i];
+
+        if let Some(left) = left.filter(|left| self.node(*left).len() > Self::MIN_LEN) {
+            self.shift(parent, i - 1, left, child, Node::shift_right);
+        } else if let Some(right) = right.filter(|right| self.node(*right).len() > Self::MIN_LEN) {
+            self.shift(parent, i, child, right, Node::shift_left);
+        } else if let Some(left) = left {
+            self.merge(parent, i - 1, left, child);
+        } else {
+            self.merge(parent, i, child, expect_consistent_state(right));
+        }
+    }
+
+    /// Moves a key between two siblings, which are separated by the key at position `sep` of the
+    /// parent.
+    fn shift(
+        &mut self,
+        parent: u32,
+        sep: usize,
+        left: u32,
+        right: u32,
+        f: fn(&mut Node<K, V>, &mut Node<K, V>, &mut K),
+    ) {
+        // Take the siblings out of the cache to be able to hold them mutably along with the
+        // parent, then put them back after.
+        let mut left_node = expect_consistent_state(self.nodes.remove(left));
+        let mut right_node = expect_consistent_state(self.nodes.remove(right));
+        match expect_consistent_state(self.nodes.get_mut(parent)) {
+            Node::Internal { keys, .. } => f(&mut left_node, &mut right_node, &mut keys[sep]),
+            Node::Leaf { .. } => panic_str("inconsistent state"),
+        }
+        self.nodes.set(left, Some(left_node));
+        self.nodes.set(right, Some(right_node));
+    }
+
+    /// Merges two siblings, which are separated by the key at position `sep` of the parent, into
+    /// the left one and removes the right one.
+    fn merge(&mut self, parent: u32, sep: usize, left: u32, right: u32) {
+        let right_node = expect_consistent_state(self.nodes.remove(right));
+        let separator = match expect_consistent_state(self.nodes.get_mut(parent)) {
+            Node::Internal { keys, children } => {
+                children.remove(sep + 1);
+                keys.remove(sep)
+            }
+            Node::Leaf { .. } => panic_str("inconsistent state"),
+        };
+        expect_consistent_state(self.nodes.get_mut(left)).merge(separator, right_node);
+    }
+
+    /// Returns the entry with the smallest key in the map.
+    pub fn min(&self) -> Option<(&K, &V)> {
+        let (leaf, pos) = self.lower_cursor::<K>(Bound::Unbounded)?;
+        Some(leaf.entry(pos))
+    }
+
+    /// Returns the entry with the largest key in the map.
+    pub fn max(&self) -> Option<(&K, &V)> {
+        let (leaf, end) = self.upper_cursor::<K>(Bound::Unbounded)?;
+        Some(leaf.entry(end - 1))
+    }
+
+    /// Returns the entry with the largest key less than or equal to `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedTreeMap;
+    ///
+    /// let mut map: ChunkedTreeMap<u32, ()> = ChunkedTreeMap::new(b"t");
+    /// map.insert(10, ());
+    /// map.insert(20, ());
+    ///
+    /// assert_eq!(map.floor(&5), None);
+    /// assert_eq!(map.floor(&10), Some((&10, &())));
+    /// assert_eq!(map.floor(&15), Some((&10, &())));
+    /// ```
+    pub fn floor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (leaf, end) = self.upper_cursor(Bound::Included(key))?;
+        Some(leaf.entry(end - 1))
+    }
+
+    /// Returns the entry with the smallest key greater than or equal to `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedTreeMap;
+    ///
+    /// let mut map: ChunkedTreeMap<u32, ()> = ChunkedTreeMap::new(b"t");
+    /// map.insert(10, ());
+    /// map.insert(20, ());
+    ///
+    /// assert_eq!(map.ceil(&15), Some((&20, &())));
+    /// assert_eq!(map.ceil(&20), Some((&20, &())));
+    /// assert_eq!(map.ceil(&25), None);
+    /// ```
+    pub fn ceil<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (leaf, pos) = self.lower_cursor(Bound::Included(key))?;
+        Some(leaf.entry(pos))
+    }
+
+    /// Returns a double-ended iterator over a sub-range of the entries in the map, in ascending
+    /// key order. The leaves at both ends of the range are loaded when the iterator is created,
+    /// and the leaves between them are lazily loaded while iterating.
+    ///
+    /// Unlike [`BTreeMap::range`](alloc::collections::BTreeMap::range), this does not panic if
+    /// the start of the range is greater than the end, the iterator is empty instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedTreeMap;
+    ///
+    /// let mut map: ChunkedTreeMap<u64, u32> = ChunkedTreeMap::new(b"t");
+    /// for (timestamp, amount) in [(100, 1), (150, 2), (200, 3), (250, 4)] {
+    ///     map.insert(timestamp, amount);
+    /// }
+    ///
+    /// let in_window: Vec<_> = map.range(150..250).map(|(_, v)| *v).collect();
+    /// assert_eq!(in_window, &[2, 3]);
+    /// assert_eq!(map.range(..=150).next_back(), Some((&150, &2)));
+    /// ```
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V, N, B>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let front = self.lower_cursor(range.start_bound());
+        let back = self.upper_cursor(range.end_bound());
+        Range::new(self, front, back)
+    }
+
+    /// Returns an iterator over the entries of the map, in ascending key order. This iterator
+    /// will lazily load any leaves iterated over from storage.
+    pub fn iter(&self) -> Iter<'_, K, V, N, B> {
+        Iter::new(self)
+    }
+
+    /// Removes all entries from the map. Every internal node is loaded to find the nodes below
+    /// it, and every node is removed from storage.
+    pub fn clear(&mut self) {
+        let mut stack: Vec<u32> = self.root.take().into_iter().collect();
+        while let Some(id) = stack.pop() {
+            if let Node::Internal { children, .. } = self.node(id) {
+                stack.extend_from_slice(children);
+            }
+            self.nodes.set(id, None);
+        }
+        self.len = 0;
+        self.next_node = 0;
+    }
+}
+
+impl<'a, K, V, const N: usize, B> IntoIterator for &'a ChunkedTreeMap<K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, N, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, const N: usize, B, Q> core::ops::Index<&Q> for ChunkedTreeMap<K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone + Borrow<Q>,
+    V: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+    Q: Ord + ?Sized,
+{
+    type Output = V;
+
+    fn index(&self, index: &Q) -> &Self::Output {
+        self.get(index)
+            .unwrap_or_else(|| panic_str("key does not exist"))
+    }
+}
+
+impl<K, V, const N: usize, B> fmt::Debug for ChunkedTreeMap<K, V, N, B>
+where
+    K: BorshSerialize + BorshDeserialize + Ord + Clone + fmt::Debug,
+    V: BorshSerialize + BorshDeserialize + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if cfg!(feature = "expensive-debug") {
+            f.debug_map().entries(self.iter()).finish()
+        } else {
+            f.debug_struct("TreeMap")
+                .field("len", &self.len)
+                .field("root", &self.root)
+                .field("prefix", &self.nodes.prefix)
+                .finish()
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{Rng, RngCore, SeedableRng};
+    use std::collections::BTreeMap;
+    use std::ops::{Bound, RangeBounds};
+
+    use super::ChunkedTreeMap;
+    use crate::utils::test_iters;
+    use near_sdk::test_utils::test_env::setup_free;
+
+    #[test]
+    fn test_insert_remove() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut map = ChunkedTreeMap::<u32, u64, 3>::new(b"t");
+        let mut baseline = BTreeMap::new();
+        for _ in 0..test_iters(1000) {
+            let key = rng.gen::<u32>() % 500;
+            let value = rng.gen::<u64>();
+            assert_eq!(map.insert(key, value), baseline.insert(key, value));
+        }
+        assert!(Iterator::eq(map.iter(), baseline.iter()));
+        assert!(Iterator::eq(map.iter().rev(), baseline.iter().rev()));
+
+        for _ in 0..test_iters(1000) {
+            let key = rng.gen::<u32>() % 500;
+            assert_eq!(map.remove(&key), baseline.remove(&key));
+        }
+        assert!(Iterator::eq(map.iter(), baseline.iter()));
+
+        // Removing every entry merges all nodes away.
+        let keys: Vec<_> = baseline.keys().copied().collect();
+        for key in keys {
+            assert_eq!(map.remove(&key), baseline.remove(&key));
+        }
+        assert!(map.is_empty());
+        map.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[test]
+    fn test_range() {
+        let mut map = ChunkedTreeMap::<u32, u32, 2>::new(b"t");
+        let mut baseline = BTreeMap::new();
+        for i in 0..50 {
+            map.insert(i * 2, i);
+            baseline.insert(i * 2, i);
+        }
+        let bounds = [
+            Bound::Included(10),
+            Bound::Excluded(10),
+            Bound::Included(11),
+            Bound::Excluded(57),
+            Bound::Included(200),
+            Bound::Unbounded,
+        ];
+        for start in bounds {
+            for end in bounds {
+                // `BTreeMap::range` panics on inverted ranges, so filter the entries instead.
+                let expected: Vec<_> = baseline
+                    .iter()
+                    .filter(|(k, _)| (start, end).contains(*k))
+                    .collect();
+                assert_eq!(map.range((start, end)).collect::<Vec<_>>(), expected);
+                let mut reversed: Vec<_> = map.range((start, end)).rev().collect();
+                reversed.reverse();
+                assert_eq!(reversed, expected);
+            }
+        }
+
+        // Iterating from both ends meets in the middle.
+        let mut range = map.range(20..30);
+        assert_eq!(range.next(), Some((&20, &10)));
+        assert_eq!(range.next_back(), Some((&28, &14)));
+        assert_eq!(
+            range.collect::<Vec<_>>(),
+            [(&22, &11), (&24, &12), (&26, &13)]
+        );
+    }
+
+    #[test]
+    fn test_floor_ceil() {
+        let mut map = ChunkedTreeMap::<u32, (), 3>::new(b"t");
+        assert_eq!(map.min(), None);
+        assert_eq!(map.max(), None);
+        assert_eq!(map.floor(&10), None);
+        for i in 1..100 {
+            map.insert(i * 10, ());
+        }
+        assert_eq!(map.min(), Some((&10, &())));
+        assert_eq!(map.max(), Some((&990, &())));
+        assert_eq!(map.floor(&5), None);
+        assert_eq!(map.floor(&500), Some((&500, &())));
+        assert_eq!(map.floor(&509), Some((&500, &())));
+        assert_eq!(map.ceil(&501), Some((&510, &())));
+        assert_eq!(map.ceil(&991), None);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut map = ChunkedTreeMap::<u64, u64, 4>::new(b"t");
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        map.flush();
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        map.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[derive(Arbitrary, Debug)]
+    enum Op {
+        Insert(u8, u8),
+        Remove(u8),
+        Flush,
+        Reset,
+        Get(u8),
+        Floor(u8),
+        Ceil(u8),
+        Range(u8, u8),
+    }
+
+    #[test]
+    fn arbitrary() {
+        setup_free();
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut buf = vec![0; 4096];
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 1024 };
+        for _ in 0..runs {
+            // Clear storage in-between runs
+            near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+            rng.fill_bytes(&mut buf);
+
+            let mut sm = ChunkedTreeMap::<_, _, 2>::new(b"t");
+            let mut mm = BTreeMap::new();
+            let u = Unstructured::new(&buf);
+            if let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) {
+                for op in ops {
+                    match op {
+                        Op::Insert(k, v) => {
+                            assert_eq!(sm.insert(k, v), mm.insert(k, v));
+                        }
+                        Op::Remove(k) => {
+                            assert_eq!(sm.remove(&k), mm.remove(&k));
+                        }
+                        Op::Flush => {
+                            sm.flush();
+                        }
+                        Op::Reset => {
+                            let serialized = sm.try_to_vec().unwrap();
+                            sm = ChunkedTreeMap::deserialize(&mut serialized.as_slice()).unwrap();
+                        }
+                        Op::Get(k) => {
+                            assert_eq!(sm.get(&k), mm.get(&k));
+                        }
+                        Op::Floor(k) => {
+                            assert_eq!(sm.floor(&k), mm.range(..=k).next_back());
+                        }
+                        Op::Ceil(k) => {
+                            assert_eq!(sm.ceil(&k), mm.range(k..).next());
+                        }
+                        Op::Range(start, end) => {
+                            if start <= end {
+                                assert!(Iterator::eq(sm.range(start..end), mm.range(start..end)));
+                            }
+                        }
+                    }
+                    assert_eq!(sm.len() as usize, mm.len());
+                }
+            }
+
+            // After all operations, compare both maps
+            assert!(Iterator::eq(sm.iter(), mm.iter()));
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use alloc::vec::Vec;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::utils::{expect_consistent_state, panic_str};
+
+/// Node of the tree, which is stored under a single storage key.
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub(crate) enum Node<K, V> {
+    /// Entries of the tree, sorted by key.
+    Leaf { keys: Vec<K>, values: Vec<V> },
+    /// Separator keys and the ids of the nodes between them. All keys in `children[i]` are less
+    /// than `keys[i]`, and all keys in `children[i + 1]` are greater or equal to it.
+    Internal { keys: Vec<K>, children: Vec<u32> },
+}
+
+impl<K, V> Node<K, V> {
+    /// Returns the number of keys in the node.
+    pub(crate) fn len(&self) -> usize {
+        self.keys().len()
+    }
+
+    pub(crate) fn keys(&self) -> &[K] {
+        match self {
+            Node::Leaf { keys, .. } | Node::Internal { keys, .. } => keys,
+        }
+    }
+
+    /// Splits off the upper half of an overflowing node, returning the separator key to insert in
+    /// the parent along with the new node.
+    pub(crate) fn split(&mut self) -> (K, Self)
+    where
+        K: Clone,
+    {
+        match self {
+            Node::Leaf { keys, values } => {
+                let at = keys.len() / 2;
+                let keys = keys.split_off(at);
+                let values = values.split_off(at);
+                (keys[0].clone(), Node::Leaf { keys, values })
+            }
+            Node::Internal { keys, children } => {
+                // Middle key is moved up to the parent rather than copied.
+                let at = keys.len() / 2;
+                let right_keys = keys.split_off(at + 1);
+                let children = children.split_off(at + 1);
+                let separator = expect_consistent_state(keys.pop());
+                (
+                    separator,
+                    Node::Internal {
+                        keys: right_keys,
+                        children,
+                    },
+                )
+            }
+        }
+    }
+
+    /// Moves the last entry of this node to the front of `right`, which is its next sibling.
+    /// `separator` is the parent key between the two nodes.
+    pub(crate) fn shift_right(&mut self, right: &mut Self, separator: &mut K)
+    where
+        K: Clone,
+    {
+        match (self, right) {
+            (
+                Node::Leaf { keys, values },
+                Node::Leaf {
+                    keys: right_keys,
+                    values: right_values,
+                },
+            ) => {
+                right_keys.insert(0, expect_consistent_state(keys.pop()));
+                right_values.insert(0, expect_consistent_state(values.pop()));
+                *separator = right_keys[0].clone();
+            }
+            (
+                Node::Internal { keys, children },
+                Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                },
+            ) => {
+                let key = core::mem::replace(separator, expect_consistent_state(keys.pop()));
+                right_keys.insert(0, key);
+                right_children.insert(0, expect_consistent_state(children.pop()));
+            }
+            _ => panic_str("inconsistent state"),
+        }
+    }
+
+    /// Moves the first entry of `right`, which is the next sibling of this node, to the end of
+    /// this node. `separator` is the parent key between the two nodes.
+    pub(crate) fn shift_left(&mut self, right: &mut Self, separator: &mut K)
+    where
+        K: Clone,
+    {
+        match (self, right) {
+            (
+                Node::Leaf { keys, values },
+                Node::Leaf {
+                    keys: right_keys,
+                    values: right_values,
+                },
+            ) => {
+                keys.push(right_keys.remove(0));
+                values.push(right_values.remove(0));
+                *separator = right_keys[0].clone();
+            }
+            (
+                Node::Internal { keys, children },
+                Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                },
+            ) => {
+                keys.push(core::mem::replace(separator, right_keys.remove(0)));
+                children.push(right_children.remove(0));
+            }
+            _ => panic_str("inconsistent state"),
+        }
+    }
+
+    /// Appends all entries of `right`, which is the next sibling of this node. `separator` is the
+    /// parent key between the two nodes, which has been removed from the parent.
+    pub(crate) fn merge(&mut self, separator: K, right: Self) {
+        match (self, right) {
+            (
+                Node::Leaf { keys, values },
+                Node::Leaf {
+                    keys: right_keys,
+                    values: right_values,
+                },
+            ) => {
+                // Separators are only copies of leaf keys, so it can be dropped.
+                keys.extend(right_keys);
+                values.extend(right_values);
+            }
+            (
+                Node::Internal { keys, children },
+                Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                },
+            ) => {
+                keys.push(separator);
+                keys.extend(right_keys);
+                children.extend(right_children);
+            }
+            _ => panic_str("inconsistent state"),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use alloc::boxed::Box;
+use core::cell::Cell;
+
+/// Cached value loaded from storage, along with whether it has been modified since.
+#[derive(Clone, Debug)]
+pub(crate) struct CacheEntry<T> {
+    value: Option<T>,
+    state: EntryState,
+    /// Value in storage when the entry was last unmodified, if known. A modified value which
+    /// matches it does not need to be written.
+    stored: Stored,
+    usage: Usage,
+}
+
+impl<T> CacheEntry<T> {
+    pub(crate) fn new(value: Option<T>, state: EntryState) -> Self {
+        Self {
+            value,
+            state,
+            stored: Stored::Unknown,
+            usage: Usage::default(),
+        }
+    }
+
+    /// Creates a modified entry for a value at an index which is known to have no value in
+    /// storage, so that nothing is removed from storage if the value is removed before flushing.
+    pub(crate) fn new_vacant(value: Option<T>) -> Self {
+        Self {
+            value,
+            state: EntryState::Modified,
+            stored: Stored::Absent,
+            usage: Usage::default(),
+        }
+    }
+
+    pub(crate) fn new_modified(value: Option<T>) -> Self {
+        Self::new(value, EntryState::Modified)
+    }
+
+    pub(crate) fn value(&self) -> &Option<T> {
+        &self.value
+    }
+
+    pub(crate) fn value_mut(&mut self) -> &mut Option<T> {
+        self.mark_modified();
+        &mut self.value
+    }
+
+    /// Replaces the current value with a new one. This changes the state of the cell to mutated
+    /// if either the old or new value is [`Some<T>`].
+    pub(crate) fn replace(&mut self, value: Option<T>) -> Option<T> {
+        if self.value.is_some() || value.is_some() {
+            // Set modified if both values are not `None`
+            self.mark_modified();
+        }
+
+        core::mem::replace(&mut self.value, value)
+    }
+
+    /// Marks the entry as modified. If it was unmodified and no value is cached, there is no
+    /// value in storage either.
+    fn mark_modified(&mut self) {
+        if !self.is_modified() && self.value.is_none() {
+            self.stored = Stored::Absent;
+        }
+        self.state = EntryState::Modified;
+    }
+
+    /// Returns the value in storage when the entry was last unmodified, if known.
+    pub(crate) fn stored(&self) -> &Stored {
+        &self.stored
+    }
+
+    /// Keeps the serialized bytes of the value in storage, before the unmodified value is
+    /// accessed mutably.
+    pub(crate) fn set_stored(&mut self, stored: Stored) {
+        debug_assert!(!self.is_modified());
+        self.stored = stored;
+    }
+
+    /// Marks the entry as unmodified, after its value has been written to storage.
+    pub(crate) fn mark_written(&mut self) {
+        self.state = EntryState::Cached;
+        self.stored = Stored::Unknown;
+    }
+
+    /// Returns how the entry has been used, to decide whether it can be evicted.
+    pub(crate) fn usage(&self) -> &Usage {
+        &self.usage
+    }
+
+    /// Returns true if the entry has been modified
+    pub(crate) fn is_modified(&self) -> bool {
+        matches!(self.state, EntryState::Modified)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum EntryState {
+    Modified,
+    Cached,
+}
+
+/// Value in storage of a cached entry, as far as it is known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Stored {
+    Unknown,
+    Absent,
+    Bytes(Box<[u8]>),
+}
+
+/// Position of a value in the eviction queue of a cache. Values are only queued when the cache
+/// has a capacity.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Queue {
+    #[default]
+    None,
+    /// Queued by the tick the value was last used at.
+    Queued,
+    /// Referenced when it was about to be evicted, and queued again once released.
+    Held,
+}
+
+/// Tracks the accesses to a cached value, to know which values can be evicted from the cache and
+/// which one was least recently used.
+///
+/// References handed out through a shared reference to the cache keep the value from being
+/// evicted until the cache is accessed mutably again, which starts a new epoch of the cache.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Usage {
+    /// Tick of the cache when the value was last accessed.
+    last_used: Cell<u64>,
+    /// Epoch of the cache in which an unguarded reference to the value was handed out.
+    borrowed: Cell<u64>,
+    /// Number of guards referencing the value in `pin_epoch`.
+    pins: Cell<u32>,
+    pin_epoch: Cell<u64>,
+    /// Eviction queue of the cache the value is in, keyed by `last_used` unless held.
+    queue: Cell<Queue>,
+}
+
+impl Usage {
+    pub(crate) fn last_used(&self) -> u64 {
+        self.last_used.get()
+    }
+
+    pub(crate) fn touch(&self, tick: u64) {
+        self.last_used.set(tick);
+    }
+
+    /// Marks the value as referenced until the end of `epoch`.
+    pub(crate) fn borrow(&self, epoch: u64) {
+        self.borrowed.set(epoch);
+    }
+
+    /// Marks the value as referenced by a guard, until it is unpinned.
+    pub(crate) fn pin(&self, epoch: u64) {
+        if self.pin_epoch.replace(epoch) != epoch {
+            // Guards of previous epochs can't be alive, as the cache was accessed mutably since.
+            self.pins.set(0);
+        }
+        self.pins.set(self.pins.get() + 1);
+    }
+
+    pub(crate) fn queue(&self) -> Queue {
+        self.queue.get()
+    }
+
+    pub(crate) fn set_queue(&self, queue: Queue) {
+        self.queue.set(queue);
+    }
+
+    /// Releases a reference of a guard created in `epoch`.
+    pub(crate) fn unpin(&self, epoch: u64) {
+        if self.pin_epoch.get() == epoch {
+            self.pins.set(self.pins.get().saturating_sub(1));
+        }
+    }
+
+    /// Returns `true` if the value may be referenced in `epoch`.
+    pub(crate) fn is_referenced(&self, epoch: u64) -> bool {
+        self.borrowed.get() == epoch || (self.pin_epoch.get() == epoch && self.pins.get() > 0)
+    }
+}
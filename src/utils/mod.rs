@@ -0,0 +1,34 @@
+mod cache_entry;
+mod stable_map;
+
+pub(crate) use self::cache_entry::{CacheEntry, EntryState, Queue, Stored};
+pub(crate) use self::stable_map::StableMap;
+
+/// Aborts execution with the message provided.
+#[cfg(feature = "near")]
+pub(crate) fn panic_str(message: &str) -> ! {
+    near_sdk::env::panic_str(message)
+}
+
+/// Aborts execution with the message provided.
+#[cfg(not(feature = "near"))]
+pub(crate) fn panic_str(message: &str) -> ! {
+    panic!("{}", message)
+}
+
+/// Unwraps a value which is expected to be present given the collection's invariants, aborting
+/// if it is missing.
+pub(crate) fn expect_consistent_state<T>(val: Option<T>) -> T {
+    val.unwrap_or_else(|| panic_str("inconsistent state"))
+}
+
+/// Scales down the number of iterations of a randomized test when running under Miri, which is
+/// too slow to go through all of them.
+#[cfg(test)]
+pub(crate) const fn test_iters(iters: usize) -> usize {
+    if cfg!(miri) {
+        iters / 10
+    } else {
+        iters
+    }
+}
@@ -0,0 +1,68 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::cell::RefCell;
+
+/// Map which can be appended to through a shared reference, while keeping references to existing
+/// values valid.
+pub(crate) struct StableMap<K, V> {
+    map: RefCell<BTreeMap<K, Box<V>>>,
+}
+
+impl<K: Ord, V> Default for StableMap<K, V> {
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+        }
+    }
+}
+
+impl<K, V> StableMap<K, V> {
+    /// Gets reference to value if it exists in the map. If it does not exist, the default value
+    /// will be used to initialize before returning a reference to it.
+    pub(crate) fn get(&self, k: K) -> &V
+    where
+        K: Ord,
+        V: Default,
+    {
+        let mut map = self.map.borrow_mut();
+        let v: &mut Box<V> = map.entry(k).or_default();
+        let v: &V = v;
+        //* SAFETY: The lifetime of `V` is extended from the local `RefCell` borrow to `&self`.
+        //*         This is valid because values are only appended to the map through a shared
+        //*         reference, and the values are boxed, so their addresses are stable.
+        unsafe { &*(v as *const V) }
+    }
+
+    /// Gets mutable reference to value if it exists in the map. If it does not exist, the default
+    /// value will be used to initialize before returning a reference to it.
+    pub(crate) fn get_mut(&mut self, k: K) -> &mut V
+    where
+        K: Ord,
+        V: Default,
+    {
+        self.map.get_mut().entry(k).or_default()
+    }
+
+    /// Removes the value from the map, returning it if it existed.
+    pub(crate) fn remove(&mut self, k: &K) -> Option<V>
+    where
+        K: Ord,
+    {
+        self.map.get_mut().remove(k).map(|v| *v)
+    }
+
+    /// Returns the number of values in the map.
+    pub(crate) fn len(&self) -> usize {
+        self.map.borrow().len()
+    }
+
+    /// Calls `f` with a reference to the underlying map.
+    pub(crate) fn with_inner<R>(&self, f: impl FnOnce(&BTreeMap<K, Box<V>>) -> R) -> R {
+        f(&self.map.borrow())
+    }
+
+    /// Returns a mutable reference to the underlying map.
+    pub(crate) fn inner(&mut self) -> &mut BTreeMap<K, Box<V>> {
+        self.map.get_mut()
+    }
+}
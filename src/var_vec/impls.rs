@@ -0,0 +1,58 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::iter::Iter;
+use super::{ChunkedVarVector, ERR_INDEX_OUT_OF_BOUNDS};
+use crate::storage::StorageBackend;
+use crate::utils::panic_str;
+
+impl<'a, T, B> IntoIterator for &'a ChunkedVarVector<T, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, B> Extend<T> for ChunkedVarVector<T, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for item in iter {
+            self.push(item)
+        }
+    }
+}
+
+impl<T, B> core::ops::Index<u32> for ChunkedVarVector<T, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Output = T;
+
+    fn index(&self, index: u32) -> &Self::Output {
+        self.get(index)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS))
+    }
+}
+
+impl<T, B> core::ops::IndexMut<u32> for ChunkedVarVector<T, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn index_mut(&mut self, index: u32) -> &mut Self::Output {
+        self.get_mut(index)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS))
+    }
+}
@@ -0,0 +1,84 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::iter::FusedIterator;
+use core::slice;
+
+use super::ChunkedVarVector;
+use crate::storage::{DefaultStorage, StorageBackend};
+use crate::utils::expect_consistent_state;
+
+/// An iterator over references to each element in the stored vector.
+#[derive(Debug)]
+pub struct Iter<'a, T, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Underlying vector to iterate through
+    vec: &'a ChunkedVarVector<T, B>,
+    /// Index of the next chunk to load.
+    next_chunk: u32,
+    /// Elements of the current chunk which have not been yielded yet.
+    current: slice::Iter<'a, T>,
+    /// Number of elements left to yield.
+    remaining: u32,
+}
+
+impl<'a, T, B> Iter<'a, T, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(vec: &'a ChunkedVarVector<T, B>) -> Self {
+        Self {
+            vec,
+            next_chunk: 0,
+            current: [].iter(),
+            remaining: vec.len(),
+        }
+    }
+}
+
+impl<'a, T, B> Iterator for Iter<'a, T, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(element) = self.current.next() {
+                self.remaining -= 1;
+                return Some(element);
+            }
+            let chunk = expect_consistent_state(self.vec.values.get(self.next_chunk));
+            self.current = chunk.iter();
+            self.next_chunk += 1;
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining as usize;
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining as usize
+    }
+}
+
+impl<'a, T, B> ExactSizeIterator for Iter<'a, T, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, B> FusedIterator for Iter<'a, T, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
@@ -0,0 +1,548 @@
+//! A growable array type for variable size elements, with values persisted to storage in chunks
+//! bounded by their serialized size and lazily loaded.
+//!
+//! Values in the [`ChunkedVarVector`] are kept in an in-memory cache and are only persisted on
+//! [`Drop`].
+//!
+//! # Examples
+//!
+//! ```
+//! use near_chunked_collections::ChunkedVarVector;
+//!
+//! // Chunks are closed once they are at least 64 bytes.
+//! let mut vec: ChunkedVarVector<String> = ChunkedVarVector::new(b"v", 64);
+//! vec.push("short".to_string());
+//! vec.push("a much longer string which fills the chunk by itself".repeat(2));
+//!
+//! assert_eq!(vec.get(0).map(String::as_str), Some("short"));
+//! assert_eq!(vec.chunk_count(), 1);
+//!
+//! vec.push("next".to_string());
+//! assert_eq!(vec.chunk_count(), 2);
+//! ```
+
+mod impls;
+mod iter;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use borsh::maybestd::io::{Error, Write};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub use self::iter::Iter;
+
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+use crate::utils::{expect_consistent_state, panic_str};
+use crate::ChunkedVector;
+
+const ERR_INDEX_OUT_OF_BOUNDS: &str = "Index out of bounds";
+const ERR_ELEMENT_SERIALIZATION: &str = "Cannot serialize element";
+
+/// Number of chunk start positions stored under a single key of the index.
+const INDEX_CHUNK_SIZE: usize = 64;
+
+/// Byte appended to the prefix of the vector for the index of chunk start positions. Chunk keys
+/// are the prefix followed by a `u32`, so they can't collide with the longer index keys.
+const INDEX_SUFFIX: u8 = b'i';
+
+/// Length of the Borsh encoded length of a chunk.
+const CHUNK_HEADER_LEN: u32 = 4;
+
+/// Value of the size of the last chunk when an element of it has been accessed mutably, so it
+/// has to be computed again.
+const UNKNOWN_CHUNK_LEN: u32 = u32::MAX;
+
+/// Writer which only counts the number of bytes written to it.
+#[derive(Default)]
+struct ByteCounter(u32);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.0 = self.0.saturating_add(buf.len() as u32);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Returns the number of bytes the value is serialized as, without allocating.
+fn serialized_len<T: BorshSerialize + ?Sized>(value: &T) -> u32 {
+    let mut counter = ByteCounter::default();
+    value
+        .serialize(&mut counter)
+        .unwrap_or_else(|_| panic_str(ERR_ELEMENT_SERIALIZATION));
+    counter.0
+}
+
+/// An iterable implementation of vector for elements of variable serialized size, which stores
+/// its content on the trie. This implementation will load and store values in the underlying
+/// storage lazily.
+///
+/// Uses the following map: chunk index -> chunk of elements. Rather than holding a fixed number
+/// of elements as in [`ChunkedVector`], elements are pushed to the last chunk until its
+/// serialized size reaches the byte budget of the vector, after which it is closed and the next
+/// element starts a new chunk. Every chunk is then close to the budget in size, no matter the
+/// size of each element, so the cost of loading a chunk stays predictable.
+///
+/// The index of the first element of each chunk is kept in a [`ChunkedVector`] under the prefix
+/// of the vector followed by `b'i'`, which [`get`](Self::get) binary searches to find the chunk of
+/// an element. Chunk sizes are only checked when pushing elements, so modifying elements in
+/// place can grow a chunk past the budget.
+///
+/// This implementation will cache all changes and loads and only updates values that are changed
+/// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
+/// in storage before the variable is dropped by using [`ChunkedVarVector::flush`].
+///
+/// # Examples
+/// ```
+/// use near_chunked_collections::ChunkedVarVector;
+///
+/// let mut vec: ChunkedVarVector<Vec<u8>> = ChunkedVarVector::new(b"v", 1024);
+/// vec.push(vec![0; 300]);
+/// vec.push(vec![1; 2000]);
+/// vec.push(vec![2; 10]);
+///
+/// assert_eq!(vec.len(), 3);
+/// assert_eq!(vec[1].len(), 2000);
+/// // Second element closed the first chunk, the third one starts a new chunk.
+/// assert_eq!(vec.chunk_count(), 2);
+/// ```
+pub struct ChunkedVarVector<T, B = DefaultStorage>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    pub(crate) len: u32,
+    /// Serialized size at which chunks are closed.
+    pub(crate) byte_budget: u32,
+    /// Serialized size of the last chunk, to know whether it is closed without loading it. Set
+    /// to [`UNKNOWN_CHUNK_LEN`] if an element of the chunk may have been modified.
+    pub(crate) last_chunk_len: u32,
+    /// Index of the first element of every chunk.
+    pub(crate) starts: ChunkedVector<u32, INDEX_CHUNK_SIZE, B>,
+    pub(crate) values: IndexMap<Vec<T>, B>,
+}
+
+impl<T> ChunkedVarVector<T, DefaultStorage>
+where
+    T: BorshSerialize,
+{
+    /// Create new vector with zero elements, which closes chunks once their serialized size
+    /// reaches `byte_budget`. Prefixes storage accesss with the prefix provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVarVector;
+    ///
+    /// let mut vec: ChunkedVarVector<String> = ChunkedVarVector::new(b"v", 2048);
+    /// ```
+    pub fn new<S>(prefix: S, byte_budget: u32) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self::new_in(prefix, byte_budget, DefaultStorage::default())
+    }
+}
+
+impl<T, B> Drop for ChunkedVarVector<T, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+impl<T, B> BorshSerialize for ChunkedVarVector<T, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.len, writer)?;
+        BorshSerialize::serialize(&self.byte_budget, writer)?;
+        BorshSerialize::serialize(&self.last_chunk_len, writer)?;
+        BorshSerialize::serialize(&self.starts, writer)?;
+        BorshSerialize::serialize(&self.values, writer)?;
+        Ok(())
+    }
+}
+
+impl<T, B> BorshDeserialize for ChunkedVarVector<T, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend + Default,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            len: BorshDeserialize::deserialize(buf)?,
+            byte_budget: BorshDeserialize::deserialize(buf)?,
+            last_chunk_len: BorshDeserialize::deserialize(buf)?,
+            starts: BorshDeserialize::deserialize(buf)?,
+            values: BorshDeserialize::deserialize(buf)?,
+        })
+    }
+}
+
+impl<T, B> ChunkedVarVector<T, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    /// Returns the number of elements in the vector.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns `true` if the vector contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the serialized size at which chunks are closed.
+    pub fn byte_budget(&self) -> u32 {
+        self.byte_budget
+    }
+
+    /// Returns the number of chunks the elements are stored in.
+    pub fn chunk_count(&self) -> u32 {
+        self.starts.len()
+    }
+
+    /// Create new vector with zero elements, which closes chunks once their serialized size
+    /// reaches `byte_budget` and is persisted to the storage backend provided. Prefixes storage
+    /// accesss with the prefix provided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedVarVector;
+    ///
+    /// let mut vec: ChunkedVarVector<String, _> =
+    ///     ChunkedVarVector::new_in(b"v", 2048, InMemoryStorage::new());
+    /// ```
+    pub fn new_in<S>(prefix: S, byte_budget: u32, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+        B: Clone,
+    {
+        let prefix = prefix.into_storage_key();
+        let mut index_prefix = prefix.clone();
+        index_prefix.push(INDEX_SUFFIX);
+        Self {
+            len: 0,
+            byte_budget,
+            last_chunk_len: 0,
+            starts: ChunkedVector::new_in(index_prefix, storage.clone()),
+            values: IndexMap::new_in(prefix, storage),
+        }
+    }
+
+    /// Removes all elements from the vector. This will remove the storage value of every chunk,
+    /// without loading any of them.
+    pub fn clear(&mut self) {
+        for chunk_idx in 0..self.starts.len() {
+            self.values.set(chunk_idx, None);
+        }
+        self.starts.clear();
+        self.last_chunk_len = 0;
+        self.len = 0;
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    ///
+    /// This operation is performed on [`Drop`], but this method can be called to persist
+    /// intermediate writes in cases where [`Drop`] is not called or to identify storage changes.
+    pub fn flush(&mut self) {
+        self.values.flush();
+        self.starts.flush();
+    }
+}
+
+impl<T, B> ChunkedVarVector<T, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Returns the serialized size of the last chunk, at index `last`, computing it if an element
+    /// of it was accessed mutably.
+    fn last_chunk_len(&mut self, last: u32) -> u32 {
+        if self.last_chunk_len == UNKNOWN_CHUNK_LEN {
+            self.last_chunk_len = serialized_len(expect_consistent_state(self.values.get(last)));
+        }
+        self.last_chunk_len
+    }
+
+    /// Returns the index of the chunk holding the element at `index`, and the index of the
+    /// first element of that chunk.
+    fn locate(&self, index: u32) -> (u32, u32) {
+        let chunk_idx = expect_consistent_state(
+            self.starts
+                .partition_point(|&start| start <= index)
+                .checked_sub(1),
+        );
+        (chunk_idx, self.starts[chunk_idx])
+    }
+
+    /// Appends an element to the back of the collection. The element is pushed to the last
+    /// chunk, unless that chunk has reached the byte budget.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    pub fn push(&mut self, element: T) {
+        let index = self.len;
+        self.len = self
+            .len
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+
+        let element_len = serialized_len(&element);
+        let chunk_count = self.starts.len();
+        match chunk_count.checked_sub(1) {
+            Some(last) if self.last_chunk_len(last) < self.byte_budget => {
+                expect_consistent_state(self.values.get_mut(last)).push(element);
+                self.last_chunk_len = self.last_chunk_len.saturating_add(element_len);
+            }
+            _ => {
+                // Last chunk is closed, start a new one.
+                self.starts.push(index);
+                self.values.set(chunk_count, Some(alloc::vec![element]));
+                self.last_chunk_len = CHUNK_HEADER_LEN.saturating_add(element_len);
+            }
+        }
+    }
+
+    /// Removes the last element from the vector and returns it, or [`None`] if it is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        let last = self.starts.len().checked_sub(1)?;
+        let chunk = expect_consistent_state(self.values.get_mut(last));
+        let element = expect_consistent_state(chunk.pop());
+        self.len -= 1;
+
+        if chunk.is_empty() {
+            self.values.set(last, None);
+            self.starts.pop();
+            // Previous chunk becomes the last one, its size is needed to know if it is closed.
+            self.last_chunk_len = match last.checked_sub(1) {
+                Some(prev) => serialized_len(expect_consistent_state(self.values.get(prev))),
+                None => 0,
+            };
+        } else if self.last_chunk_len != UNKNOWN_CHUNK_LEN {
+            self.last_chunk_len -= serialized_len(&element);
+        }
+        Some(element)
+    }
+
+    /// Returns the element by index or `None` if it is not present.
+    ///
+    /// The chunk of the element is found by binary searching the index of chunk start
+    /// positions, which only loads the index chunks probed, before loading the chunk itself.
+    pub fn get(&self, index: u32) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        let (chunk_idx, start) = self.locate(index);
+        let chunk = expect_consistent_state(self.values.get(chunk_idx));
+        chunk.get((index - start) as usize)
+    }
+
+    /// Returns a mutable reference to the element by index or `None` if it is not present.
+    ///
+    /// The size of the chunk is not checked against the byte budget after modifying an element.
+    pub fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+        let (chunk_idx, start) = self.locate(index);
+        if chunk_idx + 1 == self.starts.len() {
+            // Element can change size, the size of the last chunk is computed again if needed.
+            self.last_chunk_len = UNKNOWN_CHUNK_LEN;
+        }
+        let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+        chunk.get_mut((index - start) as usize)
+    }
+
+    /// Returns an iterator over the vector, which loads each chunk once as it is reached.
+    pub fn iter(&self) -> Iter<'_, T, B> {
+        Iter::new(self)
+    }
+}
+
+impl<T, B> fmt::Debug for ChunkedVarVector<T, B>
+where
+    T: BorshSerialize + BorshDeserialize + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if cfg!(feature = "expensive-debug") {
+            fmt::Debug::fmt(&self.iter().collect::<Vec<_>>(), f)
+        } else {
+            f.debug_struct("VarVector")
+                .field("len", &self.len)
+                .field("byte_budget", &self.byte_budget)
+                .field("prefix", &self.values.prefix)
+                .finish()
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{Rng, RngCore, SeedableRng};
+
+    use super::{serialized_len, ChunkedVarVector};
+    use crate::utils::test_iters;
+    use near_sdk::test_utils::test_env::setup_free;
+
+    #[test]
+    fn test_push_pop() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut vec = ChunkedVarVector::<Vec<u8>>::new(b"v", 100);
+        let mut baseline = vec![];
+        for _ in 0..test_iters(500) {
+            let value = vec![rng.gen::<u8>(); rng.gen::<usize>() % 64];
+            vec.push(value.clone());
+            baseline.push(value);
+        }
+        assert!(Iterator::eq(vec.iter(), baseline.iter()));
+        for i in (0..test_iters(500) as u32).step_by(7) {
+            assert_eq!(vec.get(i), baseline.get(i as usize));
+        }
+        assert_eq!(vec.get(test_iters(500) as u32), None);
+
+        for _ in 0..=test_iters(500) {
+            assert_eq!(vec.pop(), baseline.pop());
+        }
+        vec.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[test]
+    fn test_chunk_budget() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(1);
+        let mut vec = ChunkedVarVector::<String>::new(b"v", 256);
+        for _ in 0..300 {
+            let len = rng.gen::<usize>() % 100;
+            vec.push("a".repeat(len));
+        }
+        vec.flush();
+
+        // Every chunk but the last one is closed once it reaches the budget, so removing its last
+        // element brings it back under it.
+        let count = vec.chunk_count();
+        for chunk_idx in 0..count {
+            let key = [&b"v"[..], &chunk_idx.to_le_bytes()].concat();
+            let bytes = near_sdk::env::storage_read(&key).unwrap();
+            let mut chunk = Vec::<String>::try_from_slice(&bytes).unwrap();
+            if chunk_idx + 1 < count {
+                assert!(bytes.len() >= 256);
+            }
+            chunk.pop();
+            assert!(serialized_len(&chunk) < 256);
+        }
+
+        // Index of chunk start positions is stored under its own prefix.
+        let index_key = [&b"vi"[..], &0u32.to_le_bytes()].concat();
+        let starts = Vec::<u32>::try_from_slice(&near_sdk::env::storage_read(&index_key).unwrap());
+        assert_eq!(starts.unwrap()[0], 0);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut vec = ChunkedVarVector::<String>::new(b"v", 32);
+        vec.push("a".to_string());
+        vec.push("b".to_string());
+
+        // Growing the last chunk past the budget closes it.
+        vec[1] = "b".repeat(40);
+        vec.push("c".to_string());
+        assert_eq!(vec.chunk_count(), 2);
+        assert_eq!(vec.iter().map(String::len).collect::<Vec<_>>(), [1, 40, 1]);
+    }
+
+    #[derive(Arbitrary, Debug)]
+    enum Op {
+        Push(Vec<u8>),
+        Pop,
+        Set(u32, Vec<u8>),
+        Get(u32),
+        Flush,
+        Reset,
+        Clear,
+    }
+
+    #[test]
+    fn arbitrary() {
+        setup_free();
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut buf = vec![0; 4096];
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 512 };
+        for _ in 0..runs {
+            // Clear storage in-between runs
+            near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+            rng.fill_bytes(&mut buf);
+
+            let mut sv = ChunkedVarVector::new(b"v", 48);
+            let mut mv = Vec::new();
+            let u = Unstructured::new(&buf);
+            if let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) {
+                for op in ops {
+                    match op {
+                        Op::Push(v) => {
+                            sv.push(v.clone());
+                            mv.push(v);
+                        }
+                        Op::Pop => {
+                            assert_eq!(sv.pop(), mv.pop());
+                        }
+                        Op::Set(i, v) => {
+                            if sv.is_empty() {
+                                continue;
+                            }
+                            let i = i % sv.len();
+                            sv[i] = v.clone();
+                            mv[i as usize] = v;
+                        }
+                        Op::Get(i) => {
+                            assert_eq!(sv.get(i), mv.get(i as usize));
+                        }
+                        Op::Flush => {
+                            sv.flush();
+                        }
+                        Op::Reset => {
+                            let serialized = sv.try_to_vec().unwrap();
+                            sv = ChunkedVarVector::deserialize(&mut serialized.as_slice()).unwrap();
+                        }
+                        Op::Clear => {
+                            sv.clear();
+                            mv.clear();
+                        }
+                    }
+                    assert_eq!(sv.len() as usize, mv.len());
+                }
+            }
+
+            // After all operations, compare both vectors
+            assert!(Iterator::eq(sv.iter(), mv.iter()));
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use borsh::BorshDeserialize;
+
+use super::{ChunkedVector, ERR_INDEX_OUT_OF_BOUNDS};
+use crate::chunk::{chunk_index, chunk_pos};
+use crate::fixed::FixedSize;
+use crate::storage::StorageBackend;
+use crate::utils::{expect_consistent_state, panic_str};
+
+const ERR_ELEMENT_DESERIALIZATION: &str = "Cannot deserialize element";
+const ERR_ELEMENT_SERIALIZATION: &str = "Cannot serialize element";
+
+/// Length of the Borsh encoded length which precedes the elements of a stored chunk.
+const CHUNK_HEADER_LEN: usize = 4;
+
+/// Byte range of the element at `pos` within the bytes of a stored chunk.
+fn element_range<T: FixedSize>(pos: usize) -> Range<usize> {
+    let start = CHUNK_HEADER_LEN + pos * T::SIZE;
+    start..start + T::SIZE
+}
+
+fn serialize_fixed<T: FixedSize>(value: &T) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(T::SIZE);
+    value
+        .serialize(&mut bytes)
+        .unwrap_or_else(|_| panic_str(ERR_ELEMENT_SERIALIZATION));
+    if bytes.len() != T::SIZE {
+        panic_str(ERR_ELEMENT_SERIALIZATION);
+    }
+    bytes
+}
+
+impl<T, const N: usize, B> ChunkedVector<T, N, B>
+where
+    T: FixedSize,
+    B: StorageBackend,
+{
+    /// Returns a copy of the element by index or `None` if it is not present.
+    ///
+    /// If the chunk of the element has not been loaded, only the bytes of the element are
+    /// deserialized from the stored chunk, and the chunk is not cached.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<[u8; 32]> = ChunkedVector::new(b"v");
+    /// vec.push([1; 32]);
+    ///
+    /// assert_eq!(vec.get_fixed(0), Some([1; 32]));
+    /// assert_eq!(vec.get_fixed(1), None);
+    /// ```
+    pub fn get_fixed(&self, index: u32) -> Option<T>
+    where
+        T: Clone,
+    {
+        if index >= self.len {
+            return None;
+        }
+        let chunk_idx = chunk_index::<N>(index);
+        let pos = chunk_pos::<N>(index);
+        if self.values.is_loaded(chunk_idx) {
+            return self.get(index).cloned();
+        }
+
+        self.values.with_raw(chunk_idx, |bytes| {
+            let element =
+                expect_consistent_state(bytes.and_then(|b| b.get(element_range::<T>(pos))));
+            Some(
+                T::try_from_slice(element)
+                    .unwrap_or_else(|_| panic_str(ERR_ELEMENT_DESERIALIZATION)),
+            )
+        })
+    }
+
+    /// Replaces the element at `index`, returning the previous element.
+    ///
+    /// If the chunk of the element has not been loaded, the bytes of the element are patched
+    /// within the bytes of the stored chunk, without deserializing the other elements of the
+    /// chunk. The patched bytes are kept in the cache and written on flush, like a modified
+    /// chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u64> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3]);
+    ///
+    /// assert_eq!(vec.replace_fixed(1, 5), 2);
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 5, 3]);
+    /// ```
+    pub fn replace_fixed(&mut self, index: u32, element: T) -> T {
+        if index >= self.len {
+            panic_str(ERR_INDEX_OUT_OF_BOUNDS);
+        }
+        let chunk_idx = chunk_index::<N>(index);
+        let pos = chunk_pos::<N>(index);
+        if self.values.is_loaded(chunk_idx) {
+            let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+            return core::mem::replace(&mut chunk[pos], element);
+        }
+
+        let bytes = expect_consistent_state(self.values.raw_mut(chunk_idx));
+        let slot = expect_consistent_state(bytes.get_mut(element_range::<T>(pos)));
+        let prev =
+            T::try_from_slice(slot).unwrap_or_else(|_| panic_str(ERR_ELEMENT_DESERIALIZATION));
+        slot.copy_from_slice(&serialize_fixed(&element));
+        prev
+    }
+
+    /// Appends an element to the back of the collection.
+    ///
+    /// If the last chunk has not been loaded, the bytes of the element are appended to the bytes
+    /// of the stored chunk and its length is updated, without deserializing the other elements
+    /// of the chunk. The patched bytes are kept in the cache and written on flush, like a
+    /// modified chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32> = ChunkedVector::new(b"v");
+    /// vec.push_fixed(1);
+    /// vec.push_fixed(2);
+    ///
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 2]);
+    /// ```
+    pub fn push_fixed(&mut self, element: T) {
+        let index = self.len;
+        let chunk_idx = chunk_index::<N>(index);
+        if chunk_pos::<N>(index) == 0 || self.values.is_loaded(chunk_idx) {
+            return self.push(element);
+        }
+        self.len = self
+            .len
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+
+        let bytes = expect_consistent_state(self.values.raw_mut(chunk_idx));
+        let header = expect_consistent_state(bytes.get_mut(..CHUNK_HEADER_LEN));
+        let chunk_len =
+            u32::try_from_slice(header).unwrap_or_else(|_| panic_str(ERR_ELEMENT_DESERIALIZATION));
+        header.copy_from_slice(&(chunk_len + 1).to_le_bytes());
+        bytes.extend_from_slice(&serialize_fixed(&element));
+    }
+}
@@ -2,35 +2,39 @@ use borsh::{BorshDeserialize, BorshSerialize};
 
 use super::iter::{Iter, IterMut};
 use super::{ChunkedVector, ERR_INDEX_OUT_OF_BOUNDS};
-use near_sdk::env;
+use crate::storage::StorageBackend;
+use crate::utils::panic_str;
 
-impl<'a, T, const N: usize> IntoIterator for &'a ChunkedVector<T, N>
+impl<'a, T, const N: usize, B> IntoIterator for &'a ChunkedVector<T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     type Item = &'a T;
-    type IntoIter = Iter<'a, T, N>;
+    type IntoIter = Iter<'a, T, N, B>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-impl<'a, T, const N: usize> IntoIterator for &'a mut ChunkedVector<T, N>
+impl<'a, T, const N: usize, B> IntoIterator for &'a mut ChunkedVector<T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     type Item = &'a mut T;
-    type IntoIter = IterMut<'a, T, N>;
+    type IntoIter = IterMut<'a, T, N, B>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter_mut()
     }
 }
 
-impl<T, const N: usize> Extend<T> for ChunkedVector<T, N>
+impl<T, const N: usize, B> Extend<T> for ChunkedVector<T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     fn extend<I>(&mut self, iter: I)
     where
@@ -42,24 +46,26 @@ where
     }
 }
 
-impl<T, const N: usize> core::ops::Index<u32> for ChunkedVector<T, N>
+impl<T, const N: usize, B> core::ops::Index<u32> for ChunkedVector<T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     type Output = T;
 
     fn index(&self, index: u32) -> &Self::Output {
         self.get(index)
-            .unwrap_or_else(|| env::panic_str(ERR_INDEX_OUT_OF_BOUNDS))
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS))
     }
 }
 
-impl<T, const N: usize> core::ops::IndexMut<u32> for ChunkedVector<T, N>
+impl<T, const N: usize, B> core::ops::IndexMut<u32> for ChunkedVector<T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     fn index_mut(&mut self, index: u32) -> &mut Self::Output {
         self.get_mut(index)
-            .unwrap_or_else(|| env::panic_str(ERR_INDEX_OUT_OF_BOUNDS))
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS))
     }
 }
@@ -1,33 +1,49 @@
+use alloc::vec::Vec;
 use borsh::{BorshDeserialize, BorshSerialize};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
 use core::{iter::FusedIterator, ops::Range};
 
-use super::{ChunkedVector, ERR_INDEX_OUT_OF_BOUNDS};
-use near_sdk::env;
+use super::{
+    chunk_count, chunk_index, chunk_pos, expect_consistent_state, ChunkedVector, ElementRef,
+    ElementRefMut, ERR_INDEX_OUT_OF_BOUNDS,
+};
+use crate::chunk::Chunk;
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, StorageBackend};
+use crate::utils::panic_str;
 
 /// An iterator over references to each element in the stored vector.
 #[derive(Debug)]
-pub struct Iter<'a, T, const N: usize>
+pub struct Iter<'a, T, const N: usize, B = DefaultStorage>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     /// Underlying vector to iterate through
-    vec: &'a ChunkedVector<T, N>,
+    vec: &'a ChunkedVector<T, N, B>,
     /// Range of indices to iterate.
     range: Range<u32>,
 }
 
-impl<'a, T, const N: usize> Iter<'a, T, N>
+impl<'a, T, const N: usize, B> Iter<'a, T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
-    pub(super) fn new(vec: &'a ChunkedVector<T, N>) -> Self {
-        Self {
+    pub(super) fn new(vec: &'a ChunkedVector<T, N, B>) -> Self {
+        Self::with_range(
             vec,
-            range: Range {
+            Range {
                 start: 0,
                 end: vec.len(),
             },
-        }
+        )
+    }
+
+    /// Creates an iterator over the elements of `vec` within `range`, which must be in bounds.
+    pub(super) fn with_range(vec: &'a ChunkedVector<T, N, B>, range: Range<u32>) -> Self {
+        Self { vec, range }
     }
 
     /// Returns number of elements left to iterate.
@@ -36,9 +52,10 @@ where
     }
 }
 
-impl<'a, T, const N: usize> Iterator for Iter<'a, T, N>
+impl<'a, T, const N: usize, B> Iterator for Iter<'a, T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     type Item = &'a T;
 
@@ -60,23 +77,28 @@ where
         Some(
             self.vec
                 .get(idx)
-                .unwrap_or_else(|| env::panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
         )
     }
 }
 
-impl<'a, T, const N: usize> ExactSizeIterator for Iter<'a, T, N> where
-    T: BorshSerialize + BorshDeserialize
+impl<'a, T, const N: usize, B> ExactSizeIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
 }
-impl<'a, T, const N: usize> FusedIterator for Iter<'a, T, N> where
-    T: BorshSerialize + BorshDeserialize
+impl<'a, T, const N: usize, B> FusedIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
 }
 
-impl<'a, T, const N: usize> DoubleEndedIterator for Iter<'a, T, N>
+impl<'a, T, const N: usize, B> DoubleEndedIterator for Iter<'a, T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     fn next_back(&mut self) -> Option<Self::Item> {
         <Self as DoubleEndedIterator>::nth_back(self, 0)
@@ -87,34 +109,38 @@ where
         Some(
             self.vec
                 .get(idx)
-                .unwrap_or_else(|| env::panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
         )
     }
 }
 
 /// An iterator over exclusive references to each element of a stored vector.
 #[derive(Debug)]
-pub struct IterMut<'a, T, const N: usize>
+pub struct IterMut<'a, T, const N: usize, B = DefaultStorage>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     /// Mutable reference to vector used to iterate through.
-    vec: &'a mut ChunkedVector<T, N>,
+    vec: &'a mut ChunkedVector<T, N, B>,
     /// Range of indices to iterate.
     range: Range<u32>,
 }
 
-impl<'a, T, const N: usize> IterMut<'a, T, N>
+impl<'a, T, const N: usize, B> IterMut<'a, T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     /// Creates a new iterator for the given storage vector.
-    pub(crate) fn new(vec: &'a mut ChunkedVector<T, N>) -> Self {
+    pub(crate) fn new(vec: &'a mut ChunkedVector<T, N, B>) -> Self {
         let end = vec.len();
-        Self {
-            vec,
-            range: Range { start: 0, end },
-        }
+        Self::with_range(vec, Range { start: 0, end })
+    }
+
+    /// Creates an iterator over the elements of `vec` within `range`, which must be in bounds.
+    pub(super) fn with_range(vec: &'a mut ChunkedVector<T, N, B>, range: Range<u32>) -> Self {
+        Self { vec, range }
     }
 
     /// Returns the amount of remaining elements to yield by the iterator.
@@ -123,24 +149,35 @@ where
     }
 }
 
-impl<'a, T, const N: usize> IterMut<'a, T, N>
+impl<'a, T, const N: usize, B> IterMut<'a, T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     fn get_mut<'b>(&'b mut self, at: u32) -> Option<&'a mut T> {
-        self.vec.get_mut(at).map(|value| {
-            //* SAFETY: The lifetime can be swapped here because we can assert that the iterator
-            //*         will only give out one mutable reference for every individual item
-            //*         during the iteration, and there is no overlap. This must be checked
-            //*         that no element in this iterator is ever revisited during iteration.
-            unsafe { &mut *(value as *mut T) }
-        })
+        if at >= self.vec.len() {
+            return None;
+        }
+        // Chunks of the elements yielded are kept in the cache while they are referenced.
+        let chunk = self.vec.values.get_mut_borrowed(chunk_index::<N>(at))?;
+        let pos = chunk_pos::<N>(at);
+        if pos >= chunk.len() {
+            return None;
+        }
+        //* SAFETY: The lifetime can be swapped here because we can assert that the iterator
+        //*         will only give out one mutable reference for every individual item
+        //*         during the iteration, and there is no overlap. This must be checked
+        //*         that no element in this iterator is ever revisited during iteration.
+        //*         The reference is created from a pointer to the elements rather than a
+        //*         slice, which would invalidate the references to the other elements.
+        Some(unsafe { &mut *chunk.as_mut_ptr().add(pos) })
     }
 }
 
-impl<'a, T, const N: usize> Iterator for IterMut<'a, T, N>
+impl<'a, T, const N: usize, B> Iterator for IterMut<'a, T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     type Item = &'a mut T;
 
@@ -161,23 +198,28 @@ where
         let idx = self.range.nth(n)?;
         Some(
             self.get_mut(idx)
-                .unwrap_or_else(|| env::panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
         )
     }
 }
 
-impl<'a, T, const N: usize> ExactSizeIterator for IterMut<'a, T, N> where
-    T: BorshSerialize + BorshDeserialize
+impl<'a, T, const N: usize, B> ExactSizeIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
 }
-impl<'a, T, const N: usize> FusedIterator for IterMut<'a, T, N> where
-    T: BorshSerialize + BorshDeserialize
+impl<'a, T, const N: usize, B> FusedIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
 }
 
-impl<'a, T, const N: usize> DoubleEndedIterator for IterMut<'a, T, N>
+impl<'a, T, const N: usize, B> DoubleEndedIterator for IterMut<'a, T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     fn next_back(&mut self) -> Option<Self::Item> {
         <Self as DoubleEndedIterator>::nth_back(self, 0)
@@ -187,140 +229,642 @@ where
         let idx = self.range.nth_back(n)?;
         Some(
             self.get_mut(idx)
-                .unwrap_or_else(|| env::panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
         )
     }
 }
 
-// TODO drain is possible, it's just complex to do efficiently
-// /// A draining iterator for [`Vector<T>`].
-// #[derive(Debug)]
-// pub struct Drain<'a, T, const N: usize>
-// where
-//     T: BorshSerialize + BorshDeserialize,
-// {
-//     /// Mutable reference to vector used to iterate through.
-//     vec: &'a mut ChunkedVector<T, N>,
-//     /// Range of indices to iterate.
-//     range: Range<u32>,
-//     /// Range of elements to delete.
-//     delete_range: Range<u32>,
-// }
-
-// impl<'a, T, const N: usize> Drain<'a, T, N>
-// where
-//     T: BorshSerialize + BorshDeserialize,
-// {
-//     /// Creates a new iterator for the given storage vector.
-//     pub(crate) fn new(vec: &'a mut ChunkedVector<T, N>, range: Range<u32>) -> Self {
-//         Self {
-//             vec,
-//             delete_range: range.clone(),
-//             range,
-//         }
-//     }
-
-//     /// Returns the amount of remaining elements to yield by the iterator.
-//     fn remaining(&self) -> usize {
-//         self.range.len()
-//     }
-//     fn remove(&mut self, index: u32) -> T {
-//         // TODO this is unsafe and should be fixed when underlying array is MaybeUninit
-//         let zeroed = unsafe { MaybeUninit::<T>::zeroed().assume_init() };
-//         core::mem::replace(
-//             super::expect_consistent_state(self.vec.get_mut(index)),
-//             zeroed,
-//         )
-//     }
-// }
-
-// impl<'a, T, const N: usize> Drop for Drain<'a, T, N>
-// where
-//     T: BorshSerialize + BorshDeserialize,
-// {
-//     fn drop(&mut self) {
-//         // TODO this is broken for sure
-//         let delete_indices = (self.delete_range.start..self.range.start)
-//             .chain(self.range.end..self.delete_range.end);
-
-//         // Delete any non-deleted elements from iterator (not loading from storage)
-//         for i in delete_indices {
-//             self.vec.values.set(i, None);
-//         }
-
-//         // Shift values after delete into slots deleted.
-//         let shift_len = self.delete_range.len() as u32;
-//         for i in self.delete_range.end..self.vec.len() {
-//             self.vec.swap(i, i - shift_len);
-//         }
-
-//         // Adjust length of vector.
-//         self.vec.len -= self.delete_range.len() as u32;
-//     }
-// }
-
-// impl<'a, T, const N: usize> Iterator for Drain<'a, T, N>
-// where
-//     T: BorshSerialize + BorshDeserialize,
-// {
-//     type Item = T;
-
-//     fn next(&mut self) -> Option<Self::Item> {
-//         // Load and replace value at next index
-//         let delete_idx = self.range.next()?;
-//         let prev = self.remove(delete_idx);
-
-//         Some(prev)
-//     }
-
-//     fn nth(&mut self, n: usize) -> Option<Self::Item> {
-//         for _ in 0..n {
-//             let next = self.range.next()?;
-//             // Delete all values in advance, values will be shifted over on drop.
-//             // This avoids having to load and deserialize any elements skipped over.
-//             self.vec.values.set(next, None);
-//         }
-//         self.next()
-//     }
-
-//     fn size_hint(&self) -> (usize, Option<usize>) {
-//         let remaining = self.remaining();
-//         (remaining, Some(remaining))
-//     }
-
-//     fn count(self) -> usize {
-//         self.remaining()
-//     }
-// }
-
-// impl<'a, T, const N: usize> ExactSizeIterator for Drain<'a, T, N> where
-//     T: BorshSerialize + BorshDeserialize
-// {
-// }
-// impl<'a, T, const N: usize> FusedIterator for Drain<'a, T, N> where
-//     T: BorshSerialize + BorshDeserialize
-// {
-// }
-
-// impl<'a, T, const N: usize> DoubleEndedIterator for Drain<'a, T, N>
-// where
-//     T: BorshSerialize + BorshDeserialize,
-// {
-//     fn next_back(&mut self) -> Option<Self::Item> {
-//         let delete_idx = self.range.next_back()?;
-//         let prev = self.remove(delete_idx);
-
-//         Some(prev)
-//     }
-
-//     fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
-//         // Only delete and don't load any values before n
-//         for _ in 0..n {
-//             let next = self.range.next_back()?;
-//             // Delete all values in advance, values will be shifted over on drop.
-//             // This avoids having to load and deserialize any elements skipped over.
-//             self.vec.values.set(next, None);
-//         }
-//         self.next_back()
-//     }
-// }
+/// An iterator over guards of each element in the stored vector, which release the chunk of
+/// their element when dropped.
+///
+/// Created by [`ChunkedVector::iter_guarded`].
+#[derive(Debug)]
+pub struct GuardedIter<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Values of the vector to iterate through, which are only accessed through this pointer
+    /// while the vector is borrowed, so that the references held by guards stay valid.
+    values: NonNull<IndexMap<Chunk<T, N>, B>>,
+    /// Range of indices to iterate.
+    range: Range<u32>,
+    marker: PhantomData<&'a mut ChunkedVector<T, N, B>>,
+}
+
+impl<'a, T, const N: usize, B> GuardedIter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(vec: &'a mut ChunkedVector<T, N, B>) -> Self {
+        let range = 0..vec.len();
+        // References handed out before can't be alive anymore, so their chunks can be evicted.
+        vec.values.next_epoch();
+        Self {
+            values: NonNull::from(&mut vec.values),
+            range,
+            marker: PhantomData,
+        }
+    }
+
+    fn get(&mut self, at: u32) -> ElementRef<'a, T, N, B> {
+        let chunk_idx = chunk_index::<N>(at);
+        //* SAFETY: The pointer was created from the exclusive reference to the vector, which is
+        //*         borrowed by the iterator and the guards it yields.
+        let values = unsafe { self.values.as_mut() };
+        // Chunks of the elements yielded are kept in the cache while their guards are alive.
+        let chunk = expect_consistent_state(values.get_pinned(chunk_idx));
+        let element = chunk
+            .get(chunk_pos::<N>(at))
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+        //* SAFETY: The chunk is pinned in the cache until the guard is dropped, and is only
+        //*         accessed mutably again once the vector is no longer borrowed.
+        let element = unsafe { &*(element as *const T) };
+        ElementRef {
+            values: self.values,
+            chunk_idx,
+            element,
+        }
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for GuardedIter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = ElementRef<'a, T, N, B>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        <Self as Iterator>::nth(self, 0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.range.len();
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.range.len()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth(n)?;
+        Some(self.get(idx))
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for GuardedIter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for GuardedIter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+impl<'a, T, const N: usize, B> DoubleEndedIterator for GuardedIter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        <Self as DoubleEndedIterator>::nth_back(self, 0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth_back(n)?;
+        Some(self.get(idx))
+    }
+}
+
+/// An iterator over guards of exclusive references to each element of a stored vector, which
+/// release the chunk of their element when dropped.
+///
+/// Created by [`ChunkedVector::iter_mut_guarded`].
+#[derive(Debug)]
+pub struct GuardedIterMut<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Values of the vector to iterate through, which are only accessed through this pointer
+    /// while the vector is borrowed, so that the references held by guards stay valid.
+    values: NonNull<IndexMap<Chunk<T, N>, B>>,
+    /// Range of indices to iterate.
+    range: Range<u32>,
+    marker: PhantomData<&'a mut ChunkedVector<T, N, B>>,
+}
+
+impl<'a, T, const N: usize, B> GuardedIterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(vec: &'a mut ChunkedVector<T, N, B>) -> Self {
+        let range = 0..vec.len();
+        // References handed out before can't be alive anymore, so their chunks can be evicted.
+        vec.values.next_epoch();
+        Self {
+            values: NonNull::from(&mut vec.values),
+            range,
+            marker: PhantomData,
+        }
+    }
+
+    fn get_mut(&mut self, at: u32) -> ElementRefMut<'a, T, N, B> {
+        let chunk_idx = chunk_index::<N>(at);
+        //* SAFETY: The pointer was created from the exclusive reference to the vector, which is
+        //*         borrowed by the iterator and the guards it yields.
+        let values = unsafe { self.values.as_mut() };
+        // Chunks of the elements yielded are kept in the cache while their guards are alive.
+        let chunk = expect_consistent_state(values.get_mut_pinned(chunk_idx));
+        let pos = chunk_pos::<N>(at);
+        if pos >= chunk.len() {
+            panic_str(ERR_INDEX_OUT_OF_BOUNDS);
+        }
+        //* SAFETY: The iterator only gives out one mutable reference for every individual item,
+        //*         and no element is ever revisited. The reference is created from a pointer to
+        //*         the elements rather than a slice, which would invalidate the references to
+        //*         the other elements of the chunk.
+        let element = unsafe { &mut *chunk.as_mut_ptr().add(pos) };
+        ElementRefMut {
+            values: self.values,
+            chunk_idx,
+            element,
+        }
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for GuardedIterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = ElementRefMut<'a, T, N, B>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        <Self as Iterator>::nth(self, 0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.range.len();
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.range.len()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth(n)?;
+        Some(self.get_mut(idx))
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for GuardedIterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for GuardedIterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+impl<'a, T, const N: usize, B> DoubleEndedIterator for GuardedIterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        <Self as DoubleEndedIterator>::nth_back(self, 0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth_back(n)?;
+        Some(self.get_mut(idx))
+    }
+}
+
+/// An iterator over the stored chunks of a vector, as slices of up to `N` elements.
+#[derive(Debug)]
+pub struct Chunks<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Underlying vector to iterate through
+    vec: &'a ChunkedVector<T, N, B>,
+    /// Range of chunk indices to iterate.
+    range: Range<u32>,
+}
+
+impl<'a, T, const N: usize, B> Chunks<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(vec: &'a ChunkedVector<T, N, B>) -> Self {
+        Self {
+            vec,
+            range: 0..chunk_count::<N>(vec.len()),
+        }
+    }
+
+    fn get(&self, chunk_idx: u32) -> &'a [T] {
+        let chunk = expect_consistent_state(self.vec.values.get(chunk_idx));
+        chunk
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for Chunks<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a [T];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        <Self as Iterator>::nth(self, 0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.range.len();
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.range.len()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let chunk_idx = self.range.nth(n)?;
+        Some(self.get(chunk_idx))
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for Chunks<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for Chunks<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+impl<'a, T, const N: usize, B> DoubleEndedIterator for Chunks<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        <Self as DoubleEndedIterator>::nth_back(self, 0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let chunk_idx = self.range.nth_back(n)?;
+        Some(self.get(chunk_idx))
+    }
+}
+
+/// An iterator over the stored chunks of a vector, as mutable slices of up to `N` elements.
+#[derive(Debug)]
+pub struct ChunksMut<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Mutable reference to vector used to iterate through.
+    vec: &'a mut ChunkedVector<T, N, B>,
+    /// Range of chunk indices to iterate.
+    range: Range<u32>,
+}
+
+impl<'a, T, const N: usize, B> ChunksMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(vec: &'a mut ChunkedVector<T, N, B>) -> Self {
+        let end = chunk_count::<N>(vec.len());
+        Self { vec, range: 0..end }
+    }
+
+    fn get_mut<'b>(&'b mut self, chunk_idx: u32) -> &'a mut [T] {
+        let chunk = expect_consistent_state(self.vec.values.get_mut_borrowed(chunk_idx));
+        //* SAFETY: The lifetime can be swapped here because the iterator only gives out one
+        //*         mutable reference for every chunk, and chunks are never revisited.
+        unsafe { &mut *(&mut **chunk as *mut [T]) }
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for ChunksMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a mut [T];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        <Self as Iterator>::nth(self, 0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.range.len();
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.range.len()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let chunk_idx = self.range.nth(n)?;
+        Some(self.get_mut(chunk_idx))
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for ChunksMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for ChunksMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+impl<'a, T, const N: usize, B> DoubleEndedIterator for ChunksMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        <Self as DoubleEndedIterator>::nth_back(self, 0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let chunk_idx = self.range.nth_back(n)?;
+        Some(self.get_mut(chunk_idx))
+    }
+}
+
+/// Drained elements of a single chunk which have been taken out of the vector.
+#[derive(Debug)]
+struct DrainedChunk<T> {
+    /// Indices of the elements left in `items`.
+    indices: Range<u32>,
+    items: alloc::vec::IntoIter<T>,
+}
+
+impl<T> DrainedChunk<T> {
+    fn contains(&self, index: u32) -> bool {
+        self.indices.contains(&index)
+    }
+
+    /// Takes the element at `index`, dropping any elements before it.
+    fn take_front(&mut self, index: u32) -> T {
+        let skip = index - self.indices.start;
+        self.indices.start = index + 1;
+        expect_consistent_state(self.items.nth(skip as usize))
+    }
+
+    /// Takes the element at `index`, dropping any elements after it.
+    fn take_back(&mut self, index: u32) -> T {
+        let skip = self.indices.end - index - 1;
+        self.indices.end = index;
+        expect_consistent_state(self.items.nth_back(skip as usize))
+    }
+}
+
+/// A draining iterator for [`ChunkedVector<T, N, B>`].
+///
+/// Chunks are only loaded when an element in them is yielded, or when they contain elements
+/// which are kept after the drained range. Chunks which are fully drained and skipped over are
+/// removed without being loaded.
+#[derive(Debug)]
+pub struct Drain<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Mutable reference to vector used to iterate through.
+    vec: &'a mut ChunkedVector<T, N, B>,
+    /// Range of indices to iterate.
+    range: Range<u32>,
+    /// Range of elements to delete.
+    delete_range: Range<u32>,
+    /// Elements before the drained range in the first chunk, if that chunk has been taken.
+    head: Option<Vec<T>>,
+    /// Elements after the drained range in the last chunk, if that chunk has been taken.
+    tail: Option<Vec<T>>,
+    /// Chunk being drained from the front.
+    front: Option<DrainedChunk<T>>,
+    /// Chunk being drained from the back.
+    back: Option<DrainedChunk<T>>,
+}
+
+impl<'a, T, const N: usize, B> Drain<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Creates a new iterator for the given storage vector.
+    pub(crate) fn new(vec: &'a mut ChunkedVector<T, N, B>, range: Range<u32>) -> Self {
+        Self {
+            vec,
+            delete_range: range.clone(),
+            range,
+            head: None,
+            tail: None,
+            front: None,
+            back: None,
+        }
+    }
+
+    /// Returns the amount of remaining elements to yield by the iterator.
+    fn remaining(&self) -> usize {
+        self.range.len()
+    }
+
+    /// Takes a chunk out of the vector, keeping the elements outside of the drained range and
+    /// returning the elements within it.
+    fn take_chunk(&mut self, chunk_idx: u32) -> DrainedChunk<T> {
+        let Range { start, end } = self.delete_range;
+        let chunk_start = chunk_idx * N as u32;
+        let mut items = expect_consistent_state(self.vec.values.remove(chunk_idx)).into_vec();
+
+        let keep_after = core::cmp::min(end - chunk_start, items.len() as u32);
+        let tail = items.split_off(keep_after as usize);
+        let keep_before = start.saturating_sub(chunk_start);
+        let drained = items.split_off(keep_before as usize);
+
+        if chunk_idx == chunk_index::<N>(start) {
+            self.head = Some(items);
+        }
+        if chunk_idx == chunk_index::<N>(end) {
+            self.tail = Some(tail);
+        }
+
+        DrainedChunk {
+            indices: chunk_start + keep_before..chunk_start + keep_after,
+            items: drained.into_iter(),
+        }
+    }
+
+    /// Removes and returns the element at `index`, iterating from the front.
+    fn remove_front(&mut self, index: u32) -> T {
+        if let Some(back) = self.back.as_mut().filter(|back| back.contains(index)) {
+            return back.take_front(index);
+        }
+        if !self
+            .front
+            .as_ref()
+            .is_some_and(|front| front.contains(index))
+        {
+            self.front = Some(self.take_chunk(chunk_index::<N>(index)));
+        }
+        expect_consistent_state(self.front.as_mut()).take_front(index)
+    }
+
+    /// Removes and returns the element at `index`, iterating from the back.
+    fn remove_back(&mut self, index: u32) -> T {
+        if let Some(front) = self.front.as_mut().filter(|front| front.contains(index)) {
+            return front.take_back(index);
+        }
+        if !self.back.as_ref().is_some_and(|back| back.contains(index)) {
+            self.back = Some(self.take_chunk(chunk_index::<N>(index)));
+        }
+        expect_consistent_state(self.back.as_mut()).take_back(index)
+    }
+}
+
+impl<'a, T, const N: usize, B> Drop for Drain<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        let Range { start, end } = self.delete_range;
+        if start == end {
+            return;
+        }
+        let len = self.vec.len();
+
+        // Take the chunks on the boundaries of the drained range, if they weren't already, to
+        // split out the elements which are kept.
+        if self.head.is_none() && chunk_pos::<N>(start) != 0 {
+            self.take_chunk(chunk_index::<N>(start));
+        }
+        if self.tail.is_none() && chunk_pos::<N>(end) != 0 && end < len {
+            self.take_chunk(chunk_index::<N>(end));
+        }
+
+        // Rewrite the chunks after the drained range, shifting elements back by whole chunks.
+        let chunk_count = chunk_count::<N>(len);
+        let mut write_idx = chunk_index::<N>(start);
+        let mut pending = self.head.take().unwrap_or_default();
+        let mut tail = self.tail.take();
+        let read_start = if end < len {
+            chunk_index::<N>(end)
+        } else {
+            chunk_count
+        };
+        for read_idx in read_start..chunk_count {
+            let items = match tail.take() {
+                Some(items) => items,
+                None => expect_consistent_state(self.vec.values.remove(read_idx)).into_vec(),
+            };
+            for item in items {
+                pending.push(item);
+                if pending.len() == N {
+                    let chunk = core::mem::replace(&mut pending, Vec::with_capacity(N));
+                    self.vec.values.set(write_idx, Some(chunk.into()));
+                    write_idx += 1;
+                }
+            }
+        }
+        if !pending.is_empty() {
+            self.vec.values.set(write_idx, Some(pending.into()));
+            write_idx += 1;
+        }
+
+        // Remove any chunks past the new length, this avoids loading fully drained chunks.
+        for chunk_idx in write_idx..chunk_count {
+            self.vec.values.set(chunk_idx, None);
+        }
+
+        self.vec.len = len - (end - start);
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for Drain<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        <Self as Iterator>::nth(self, 0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // Skipped elements are not loaded, they are removed when the iterator is dropped.
+        let idx = self.range.nth(n)?;
+        Some(self.remove_front(idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining()
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for Drain<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for Drain<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+impl<'a, T, const N: usize, B> DoubleEndedIterator for Drain<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        <Self as DoubleEndedIterator>::nth_back(self, 0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth_back(n)?;
+        Some(self.remove_back(idx))
+    }
+}
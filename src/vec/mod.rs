@@ -52,40 +52,36 @@
 //! [`Index`]: std::ops::Index
 //! [`IndexMut`]: std::ops::IndexMut
 
+mod fixed;
 mod impls;
 mod iter;
+mod retain;
+mod sort;
 
-use core::mem::MaybeUninit;
-use std::fmt;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Bound, Deref, DerefMut, Range, RangeBounds};
+use core::ptr::NonNull;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
-// pub use self::iter::{Drain, Iter, IterMut};
-pub use self::iter::{Iter, IterMut};
-use near_sdk::{env, IntoStorageKey};
+pub use self::iter::{Chunks, ChunksMut, Drain, GuardedIter, GuardedIterMut, Iter, IterMut};
 
-use near_sdk::store::index_map::IndexMap;
+use crate::chunk::{chunk_count, chunk_index, chunk_pos, Chunk};
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+use crate::utils::{expect_consistent_state, panic_str};
 
 const ERR_INDEX_OUT_OF_BOUNDS: &str = "Index out of bounds";
 
-fn expect_consistent_state<T>(val: Option<T>) -> T {
-    val.unwrap_or_else(|| env::panic_str("inconsistent state"))
-}
-
-fn chunk_index<const N: usize>(index: u32) -> u32 {
-    // TODO yeah this is a bit unsafe if N is > 32 bits range. Fix
-    (index as usize / N) as u32
-}
-
-fn chunk_pos<const N: usize>(index: u32) -> usize {
-    index as usize % N
-}
-
 /// An iterable implementation of vector that stores its content on the trie. This implementation
 /// will load and store values in the underlying storage lazily.
 ///
-/// Uses the following map: index -> element. Because the data is sharded to avoid reading/writing
-/// large chunks of data, the values cannot be accessed as a contiguous piece of memory.
+/// Uses the following map: chunk index -> chunk of up to `N` elements. Because the data is sharded
+/// to avoid reading/writing large chunks of data, the values cannot be accessed as a contiguous
+/// piece of memory. Only the last chunk can be partially filled, and it only persists the
+/// elements it contains.
 ///
 /// This implementation will cache all changes and loads and only updates values that are changed
 /// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
@@ -119,18 +115,49 @@ fn chunk_pos<const N: usize>(index: u32) -> usize {
 /// assert!(Iterator::eq(vec.into_iter(), [7, 1, 2, 3].iter()));
 /// ```
 // TODO decide on a default chunk size
-pub struct ChunkedVector<T, const N: usize = 5>
+pub struct ChunkedVector<T, const N: usize = 5, B = DefaultStorage>
 where
     T: BorshSerialize,
+    B: StorageBackend,
 {
     pub(crate) len: u32,
-    // TODO this can theoretically be IndexMap<[MaybeUninit<T>; N]> to avoid using Default
-    pub(crate) values: IndexMap<[T; N]>,
+    pub(crate) values: IndexMap<Chunk<T, N>, B>,
+}
+
+impl<T, const N: usize> ChunkedVector<T, N, DefaultStorage>
+where
+    T: BorshSerialize,
+{
+    /// Create new vector with zero elements. Prefixes storage accesss with the prefix provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// Values are persisted to the [`DefaultStorage`], use [`ChunkedVector::new_in`] to provide
+    /// a different backend.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_sdk::store::Vector;
+    ///
+    /// let mut vec: Vector<u8> = Vector::new(b"a");
+    /// ```
+    pub fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            len: 0,
+            values: IndexMap::new_in(prefix, DefaultStorage::default()),
+        }
+    }
 }
 
-impl<T, const N: usize> Drop for ChunkedVector<T, N>
+impl<T, const N: usize, B> Drop for ChunkedVector<T, N, B>
 where
     T: BorshSerialize,
+    B: StorageBackend,
 {
     fn drop(&mut self) {
         self.flush()
@@ -139,9 +166,10 @@ where
 
 //? Manual implementations needed only because borsh derive is leaking field types
 // https://github.com/near/borsh-rs/issues/41
-impl<T, const N: usize> BorshSerialize for ChunkedVector<T, N>
+impl<T, const N: usize, B> BorshSerialize for ChunkedVector<T, N, B>
 where
     T: BorshSerialize,
+    B: StorageBackend,
 {
     fn serialize<W: borsh::maybestd::io::Write>(
         &self,
@@ -153,9 +181,10 @@ where
     }
 }
 
-impl<T, const N: usize> BorshDeserialize for ChunkedVector<T, N>
+impl<T, const N: usize, B> BorshDeserialize for ChunkedVector<T, N, B>
 where
     T: BorshSerialize,
+    B: StorageBackend + Default,
 {
     fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
         Ok(Self {
@@ -165,9 +194,10 @@ where
     }
 }
 
-impl<T, const N: usize> ChunkedVector<T, N>
+impl<T, const N: usize, B> ChunkedVector<T, N, B>
 where
     T: BorshSerialize,
+    B: StorageBackend,
 {
     /// Returns the number of elements in the vector, also referred to as its size.
     /// This function returns a `u32` rather than the [`Vec`] equivalent of `usize` to have
@@ -204,7 +234,8 @@ where
         self.len == 0
     }
 
-    /// Create new vector with zero elements. Prefixes storage accesss with the prefix provided.
+    /// Create new vector with zero elements, which is persisted to the storage backend provided.
+    /// Prefixes storage accesss with the prefix provided.
     ///
     /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
     /// storing and looking up values in storage to ensure no collisions with other collections.
@@ -212,22 +243,71 @@ where
     /// # Examples
     ///
     /// ```
-    /// use near_sdk::store::Vector;
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedVector;
     ///
-    /// let mut vec: Vector<u8> = Vector::new(b"a");
+    /// let mut vec: ChunkedVector<u8, 5, _> = ChunkedVector::new_in(b"a", InMemoryStorage::new());
     /// ```
-    pub fn new<S>(prefix: S) -> Self
+    pub fn new_in<S>(prefix: S, storage: B) -> Self
     where
         S: IntoStorageKey,
     {
         Self {
             len: 0,
-            values: IndexMap::new(prefix),
+            values: IndexMap::new_in(prefix, storage),
         }
     }
 
-    /// Removes all elements from the collection. This will remove all storage values for the
-    /// length of the [`Vector`].
+    /// Returns a reference to the storage backend of the vector.
+    pub fn storage(&self) -> &B {
+        &self.values.storage
+    }
+
+    /// Sets the maximum number of chunks kept in memory, or `None` to keep every chunk loaded
+    /// until the vector is dropped, which is the default.
+    ///
+    /// Once more chunks are cached than the capacity, the least recently used chunks are
+    /// evicted, and written to storage if they were modified. Chunks are only evicted when the
+    /// vector is accessed mutably, so chunks loaded through a shared reference, such as by
+    /// [`get`](Self::get) or [`iter`](Self::iter), are kept until then. Chunks referenced by
+    /// [`iter_mut`](Self::iter_mut) are kept until the vector is accessed mutably again, while
+    /// chunks referenced by the guards yielded by [`iter_guarded`](Self::iter_guarded) and
+    /// [`iter_mut_guarded`](Self::iter_mut_guarded) are only kept while the guards are alive,
+    /// so iterating with guards keeps memory bounded by the capacity.
+    ///
+    /// The capacity only applies to this vector, the chunks of the other collections are
+    /// cached until flushed. It is not persisted when the vector is serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u64, 4> = ChunkedVector::new(b"v");
+    /// vec.set_cache_capacity(Some(2));
+    /// vec.extend(0..100);
+    /// assert_eq!(vec.cached_chunks(), 2);
+    ///
+    /// let sum: u64 = vec.iter_guarded().map(|e| *e).sum();
+    /// assert_eq!(sum, 4950);
+    /// assert_eq!(vec.cached_chunks(), 2);
+    /// ```
+    pub fn set_cache_capacity(&mut self, capacity: Option<u32>) {
+        self.values.set_capacity(capacity);
+    }
+
+    /// Returns the maximum number of chunks kept in memory, if any.
+    pub fn cache_capacity(&self) -> Option<u32> {
+        self.values.capacity()
+    }
+
+    /// Returns the number of chunks currently held in memory.
+    pub fn cached_chunks(&self) -> u32 {
+        self.values.cached_len()
+    }
+
+    /// Removes all elements from the collection. This will remove the storage value of every
+    /// chunk of the [`Vector`], without loading any of them.
     ///
     /// # Examples
     ///
@@ -242,8 +322,8 @@ where
     /// assert!(vec.is_empty());
     /// ```
     pub fn clear(&mut self) {
-        for i in 0..self.len {
-            self.values.set(i, None);
+        for chunk_idx in 0..chunk_count::<N>(self.len) {
+            self.values.set(chunk_idx, None);
         }
         self.len = 0;
     }
@@ -255,11 +335,39 @@ where
     pub fn flush(&mut self) {
         self.values.flush();
     }
+
+    /// Converts the range bounds to a range of indices, capped to the length of the vector.
+    fn clamp_range<R>(&self, range: R) -> Range<u32>
+    where
+        R: RangeBounds<u32>,
+    {
+        let start = match range.start_bound() {
+            Bound::Excluded(i) => i
+                .checked_add(1)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+            Bound::Included(i) => *i,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Excluded(i) => *i,
+            Bound::Included(i) => i
+                .checked_add(1)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+            Bound::Unbounded => self.len(),
+        };
+
+        // Note: don't need to do bounds check if end < start, the range will just be empty.
+        // This will also cap the max length at the length of the vector.
+        let end = core::cmp::min(end, self.len());
+        let start = core::cmp::min(start, end);
+        Range { start, end }
+    }
 }
 
-impl<T, const N: usize> ChunkedVector<T, N>
+impl<T, const N: usize, B> ChunkedVector<T, N, B>
 where
     T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
 {
     /// Appends an element to the back of the collection.
     ///
@@ -282,22 +390,18 @@ where
         self.len = self
             .len
             .checked_add(1)
-            .unwrap_or_else(|| env::panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
 
         let chunk_idx = chunk_index::<N>(last_idx);
         let chunk_pos = chunk_pos::<N>(last_idx);
         if chunk_pos == 0 {
             // Push is on new chunk, create new chunk
-            let chunk = MaybeUninit::<[T; N]>::zeroed();
-            // TODO this is unsafe for drop impls on zeroed data. Fix for actual use
-            let mut chunk = unsafe { chunk.assume_init() };
-            chunk[0] = element;
-            self.values.set(chunk_idx, Some(chunk));
+            self.values.set(chunk_idx, Some(Chunk::with_first(element)));
         } else {
-            // Chunk already exists, update the index in the chunk.
-            // TODO would be ideal to be able to replace the data only at the index, not deserialize
-            // TODO ..the whole chunk. This would require fixed serialization sizes, though.
-            expect_consistent_state(self.values.get_mut(chunk_idx))[chunk_pos] = element;
+            // Chunk already exists, append the element to the chunk.
+            // Note: this loads the whole chunk, `push_fixed` appends the element's bytes without
+            // deserializing the chunk for elements with a fixed serialization size.
+            expect_consistent_state(self.values.get_mut(chunk_idx)).push(element);
         }
     }
 
@@ -321,7 +425,7 @@ where
 
         self.values
             .get(chunk_index::<N>(index))
-            .map(|chunk| &chunk[chunk_pos::<N>(index)])
+            .and_then(|chunk| chunk.get(chunk_pos::<N>(index)))
     }
 
     /// Returns a mutable reference to the element at the `index` provided.
@@ -349,12 +453,42 @@ where
 
         self.values
             .get_mut(chunk_index::<N>(index))
-            .map(|chunk| &mut chunk[chunk_pos::<N>(index)])
+            .and_then(|chunk| chunk.get_mut(chunk_pos::<N>(index)))
+    }
+
+    /// Returns a guard of the element by index, or `None` if it is not present.
+    ///
+    /// Unlike [`get_mut`](Self::get_mut), which marks the chunk of the element as modified right
+    /// away, the chunk is only marked as modified when the guard is dereferenced mutably. Reading
+    /// through the guard does not cause the chunk to be written on flush.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3]);
+    ///
+    /// for i in 0..vec.len() {
+    ///     let mut elem = vec.element_mut(i).unwrap();
+    ///     // Only the chunks of elements which are even are written.
+    ///     if *elem % 2 == 0 {
+    ///         *elem += 1;
+    ///     }
+    /// }
+    /// assert_eq!(vec.iter().map(|e| *e).collect::<Vec<_>>(), &[1, 3, 3]);
+    /// ```
+    pub fn element_mut(&mut self, index: u32) -> Option<ElementMut<'_, T, N, B>> {
+        if index >= self.len {
+            return None;
+        }
+        Some(ElementMut { vec: self, index })
     }
 
     fn swap(&mut self, a: u32, b: u32) {
         if a >= self.len() || b >= self.len() {
-            env::panic_str(ERR_INDEX_OUT_OF_BOUNDS);
+            panic_str(ERR_INDEX_OUT_OF_BOUNDS);
         }
 
         if a == b {
@@ -362,18 +496,21 @@ where
         }
 
         let a_idx = chunk_index::<N>(a);
-        if a_idx == chunk_index::<N>(b) {
+        let b_idx = chunk_index::<N>(b);
+        if a_idx == b_idx {
             // Values are on the same chunk, swap.
-            let chunk = self.values.get_mut(a_idx).unwrap();
+            let chunk = expect_consistent_state(self.values.get_mut(a_idx));
             chunk.swap(chunk_pos::<N>(a), chunk_pos::<N>(b));
         } else {
-            // Values are on different chunks, swap across chunks.
-            // TODO maybe a cleaner or safer way to do this.
-            let a_mut: &mut T =
-                unsafe { &mut *(expect_consistent_state(self.get_mut(a)) as *mut _) };
-            let b_mut = expect_consistent_state(self.get_mut(b));
-
-            core::mem::swap(a_mut, b_mut);
+            // Values are on different chunks. Take the first chunk out of the cache to be able to
+            // hold both chunks mutably, then put it back after swapping.
+            let mut a_chunk = expect_consistent_state(self.values.remove(a_idx));
+            let b_chunk = expect_consistent_state(self.values.get_mut(b_idx));
+            core::mem::swap(
+                &mut a_chunk[chunk_pos::<N>(a)],
+                &mut b_chunk[chunk_pos::<N>(b)],
+            );
+            self.values.set(a_idx, Some(a_chunk));
         }
     }
 
@@ -401,13 +538,127 @@ where
     /// ```
     pub fn swap_remove(&mut self, index: u32) -> T {
         if self.is_empty() {
-            env::panic_str(ERR_INDEX_OUT_OF_BOUNDS);
+            panic_str(ERR_INDEX_OUT_OF_BOUNDS);
         }
 
         self.swap(index, self.len() - 1);
         expect_consistent_state(self.pop())
     }
 
+    /// Inserts an element at position `index` within the vector, shifting all elements after it
+    /// to the right.
+    ///
+    /// Rather than moving every element individually, one element is carried over the boundary
+    /// of each chunk after `index`, so every chunk touched is only written once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len` or if the new length exceeds `u32::MAX`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u8> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3]);
+    ///
+    /// vec.insert(1, 4);
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 4, 2, 3]);
+    ///
+    /// vec.insert(4, 5);
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 4, 2, 3, 5]);
+    /// ```
+    pub fn insert(&mut self, index: u32, element: T) {
+        if index > self.len() {
+            panic_str(ERR_INDEX_OUT_OF_BOUNDS);
+        }
+        if index == self.len() {
+            return self.push(element);
+        }
+
+        let last_chunk = chunk_index::<N>(self.len() - 1);
+        self.len = self
+            .len
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+
+        let mut pos = chunk_pos::<N>(index);
+        let mut carry = Some(element);
+        for chunk_idx in chunk_index::<N>(index)..=last_chunk {
+            let element = match carry.take() {
+                Some(element) => element,
+                None => return,
+            };
+            carry = expect_consistent_state(self.values.get_mut(chunk_idx)).insert(pos, element);
+            // Elements carried to the following chunk are inserted at the start.
+            pos = 0;
+        }
+
+        if let Some(element) = carry {
+            // Last chunk was full, the carried element starts a new chunk.
+            self.values
+                .set(last_chunk + 1, Some(Chunk::with_first(element)));
+        }
+    }
+
+    /// Removes and returns the element at position `index` within the vector, shifting all
+    /// elements after it to the left.
+    ///
+    /// Like [`insert`](Self::insert), one element is carried over the boundary of each chunk
+    /// after `index`, so every chunk touched is only written once. If ordering does not need to
+    /// be preserved, [`swap_remove`](Self::swap_remove) only modifies two chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u8> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3, 4]);
+    ///
+    /// assert_eq!(vec.remove(1), 2);
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 3, 4]);
+    /// ```
+    pub fn remove(&mut self, index: u32) -> T {
+        if index >= self.len() {
+            panic_str(ERR_INDEX_OUT_OF_BOUNDS);
+        }
+
+        let first_chunk = chunk_index::<N>(index);
+        let last_chunk = chunk_index::<N>(self.len() - 1);
+
+        // Shift the first element of each following chunk to the end of the previous chunk,
+        // starting from the back.
+        let mut carry = None;
+        for chunk_idx in (first_chunk + 1..=last_chunk).rev() {
+            let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+            let first = chunk.remove(0);
+            if let Some(element) = carry.replace(first) {
+                chunk.push(element);
+            }
+            if chunk.is_empty() {
+                self.values.set(chunk_idx, None);
+            }
+        }
+
+        let chunk = expect_consistent_state(self.values.get_mut(first_chunk));
+        let removed = chunk.remove(chunk_pos::<N>(index));
+        if let Some(element) = carry {
+            chunk.push(element);
+        }
+        if chunk.is_empty() {
+            self.values.set(first_chunk, None);
+        }
+
+        self.len -= 1;
+        removed
+    }
+
     /// Removes the last element from a vector and returns it, or [`None`] if it is empty.
     ///
     /// # Examples
@@ -423,24 +674,52 @@ where
     /// ```
     pub fn pop(&mut self) -> Option<T> {
         let new_idx = self.len.checked_sub(1)?;
-        let pop_position = chunk_pos::<N>(new_idx);
-        let prev = if pop_position == 0 {
+        let prev = if chunk_pos::<N>(new_idx) == 0 {
             // The element being popped is only one in chunk, remove the chunk and return the first
             // element, which is the one being popped.
-            expect_consistent_state(self.values.remove(chunk_index::<N>(new_idx)))
-                .into_iter()
-                .next()
+            expect_consistent_state(self.values.remove(chunk_index::<N>(new_idx))).pop()
         } else {
-            // TODO this is broken to assume init for zeroed for faulty drop impls.
-            let zeroed_element = unsafe { MaybeUninit::<T>::zeroed().assume_init() };
+            // Shrink the chunk so that only the remaining elements are persisted.
             self.values
                 .get_mut(chunk_index::<N>(new_idx))
-                .map(|chunk| core::mem::replace(&mut chunk[pop_position], zeroed_element))
+                .and_then(|chunk| chunk.pop())
         };
         self.len = new_idx;
         prev
     }
 
+    /// Shortens the vector, keeping the first `len` elements and dropping the rest.
+    ///
+    /// If `len` is greater or equal to the vector's current length, this has no effect. Chunks
+    /// which are entirely past `len` are removed without being loaded, and only the chunk which
+    /// contains the new last element is loaded to be trimmed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u8> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3, 4, 5, 6, 7]);
+    ///
+    /// vec.truncate(3);
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 2, 3]);
+    /// ```
+    pub fn truncate(&mut self, len: u32) {
+        if len >= self.len {
+            return;
+        }
+
+        for chunk_idx in chunk_count::<N>(len)..chunk_count::<N>(self.len) {
+            self.values.set(chunk_idx, None);
+        }
+        let pos = chunk_pos::<N>(len);
+        if pos != 0 {
+            expect_consistent_state(self.values.get_mut(chunk_index::<N>(len))).truncate(pos);
+        }
+        self.len = len;
+    }
+
     /// Returns an iterator over the vector. This iterator will lazily load any values iterated
     /// over from storage.
     ///
@@ -458,7 +737,7 @@ where
     /// assert_eq!(iterator.next(), Some(&4));
     /// assert_eq!(iterator.next(), None);
     /// ```
-    pub fn iter(&self) -> Iter<T, N> {
+    pub fn iter(&self) -> Iter<'_, T, N, B> {
         Iter::new(self)
     }
 
@@ -478,146 +757,664 @@ where
     /// }
     /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[3u32, 4, 6]);
     /// ```
-    pub fn iter_mut(&mut self) -> IterMut<T, N> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, N, B> {
         IterMut::new(self)
     }
 
-    // /// Creates a draining iterator that removes the specified range in the vector
-    // /// and yields the removed items.
-    // ///
-    // /// When the iterator **is** dropped, all elements in the range are removed
-    // /// from the vector, even if the iterator was not fully consumed. If the
-    // /// iterator **is not** dropped (with [`mem::forget`](std::mem::forget) for example),
-    // /// the collection will be left in an inconsistent state.
-    // ///
-    // /// This will not panic on invalid ranges (`end > length` or `end < start`) and instead the
-    // /// iterator will just be empty.
-    // ///
-    // /// # Examples
-    // ///
-    // /// ```
-    // /// use near_sdk::store::Vector;
-    // ///
-    // /// let mut vec: Vector<u32> = Vector::new(b"v");
-    // /// vec.extend(vec![1, 2, 3]);
-    // ///
-    // /// let u: Vec<_> = vec.drain(1..).collect();
-    // /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1]);
-    // /// assert_eq!(u, &[2, 3]);
-    // ///
-    // /// // A full range clears the vector, like `clear()` does
-    // /// vec.drain(..);
-    // /// assert!(vec.is_empty());
-    // /// ```
-    // pub fn drain<R>(&mut self, range: R) -> Drain<T, N>
-    // where
-    //     R: RangeBounds<u32>,
-    // {
-    //     let start = match range.start_bound() {
-    //         Bound::Excluded(i) => i
-    //             .checked_add(1)
-    //             .unwrap_or_else(|| env::panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
-    //         Bound::Included(i) => *i,
-    //         Bound::Unbounded => 0,
-    //     };
-    //     let end = match range.end_bound() {
-    //         Bound::Excluded(i) => *i,
-    //         Bound::Included(i) => i
-    //             .checked_add(1)
-    //             .unwrap_or_else(|| env::panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
-    //         Bound::Unbounded => self.len(),
-    //     };
-
-    //     // Note: don't need to do bounds check if end < start, will just return None when iterating
-    //     // This will also cap the max length at the length of the vector.
-    //     Drain::new(
-    //         self,
-    //         Range {
-    //             start,
-    //             end: core::cmp::min(end, self.len()),
-    //         },
-    //     )
-    // }
-}
+    /// Returns an iterator over guards of the elements of the vector. Unlike with
+    /// [`iter`](Self::iter), the chunk of an element can be evicted from the cache as soon as
+    /// its guard is dropped, so iterating keeps memory bounded by the
+    /// [cache capacity](Self::set_cache_capacity).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 4> = ChunkedVector::new(b"v");
+    /// vec.set_cache_capacity(Some(1));
+    /// vec.extend(0..10);
+    ///
+    /// let max = vec.iter_guarded().map(|e| *e).max();
+    /// assert_eq!(max, Some(9));
+    /// assert_eq!(vec.cached_chunks(), 1);
+    /// ```
+    pub fn iter_guarded(&mut self) -> GuardedIter<'_, T, N, B> {
+        GuardedIter::new(self)
+    }
 
-impl<T, const N: usize> fmt::Debug for ChunkedVector<T, N>
-where
-    T: BorshSerialize + BorshDeserialize + fmt::Debug,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if cfg!(feature = "expensive-debug") {
-            fmt::Debug::fmt(&self.iter().collect::<Vec<_>>(), f)
-        } else {
-            f.debug_struct("Vector")
-                .field("len", &self.len)
-                .field("prefix", &self.values.prefix)
-                .finish()
-        }
+    /// Returns an iterator over guards of the elements of the vector that allow modifying each
+    /// value. See [`iter_guarded`](Self::iter_guarded) for how chunks are released.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 4> = ChunkedVector::new(b"v");
+    /// vec.set_cache_capacity(Some(1));
+    /// vec.extend(0..10);
+    ///
+    /// for mut elem in vec.iter_mut_guarded() {
+    ///     *elem *= 2;
+    /// }
+    /// assert_eq!(vec.cached_chunks(), 1);
+    /// assert_eq!(vec.get(9), Some(&18));
+    /// ```
+    pub fn iter_mut_guarded(&mut self) -> GuardedIterMut<'_, T, N, B> {
+        GuardedIterMut::new(self)
     }
-}
 
-#[cfg(not(target_arch = "wasm32"))]
-#[cfg(test)]
-mod tests {
-    use arbitrary::{Arbitrary, Unstructured};
-    use borsh::{BorshDeserialize, BorshSerialize};
-    use rand::{Rng, RngCore, SeedableRng};
+    /// Returns an iterator over the elements of the vector within the range provided. Only the
+    /// chunks of the elements yielded are loaded, including when elements are skipped with
+    /// [`Iterator::nth`] or [`Iterator::skip`].
+    ///
+    /// This will not panic on ranges past the end of the vector, the range is capped to the
+    /// length of the vector instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32> = ChunkedVector::new(b"v");
+    /// vec.extend(0..20);
+    ///
+    /// // Page of 5 elements starting at index 12.
+    /// let page: Vec<_> = vec.iter_range(12..).take(5).copied().collect();
+    /// assert_eq!(page, &[12, 13, 14, 15, 16]);
+    ///
+    /// assert_eq!(vec.iter_range(18..30).count(), 2);
+    /// ```
+    pub fn iter_range<R>(&self, range: R) -> Iter<'_, T, N, B>
+    where
+        R: RangeBounds<u32>,
+    {
+        let range = self.clamp_range(range);
+        Iter::with_range(self, range)
+    }
 
-    use super::ChunkedVector;
-    use near_sdk::{store::index_map::IndexMap, test_utils::test_env::setup_free};
+    /// Returns an iterator that allows modifying the elements of the vector within the range
+    /// provided. See [`iter_range`](Self::iter_range) for how the range is handled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3, 4]);
+    ///
+    /// for elem in vec.iter_mut_range(1..=2) {
+    ///     *elem *= 10;
+    /// }
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 20, 30, 4]);
+    /// ```
+    pub fn iter_mut_range<R>(&mut self, range: R) -> IterMut<'_, T, N, B>
+    where
+        R: RangeBounds<u32>,
+    {
+        let range = self.clamp_range(range);
+        IterMut::with_range(self, range)
+    }
 
-    #[test]
-    fn test_push_pop() {
-        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
-        let mut vec = ChunkedVector::<_>::new(b"v");
-        let mut baseline = vec![];
-        for _ in 0..500 {
-            let value = rng.gen::<u64>();
-            vec.push(value);
-            baseline.push(value);
-        }
-        let actual: Vec<u64> = vec.iter().cloned().collect();
-        assert_eq!(actual, baseline);
-        for _ in 0..501 {
-            assert_eq!(baseline.pop(), vec.pop());
-        }
+    /// Returns an iterator over the chunks of the vector, yielding the elements of each stored
+    /// chunk as a slice. Every chunk holds `N` elements, except the last one which holds the
+    /// remaining elements. Each chunk is loaded from storage when it is yielded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 2> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3, 4, 5]);
+    ///
+    /// let mut chunks = vec.chunks();
+    /// assert_eq!(chunks.next(), Some(&[1, 2][..]));
+    /// assert_eq!(chunks.next_back(), Some(&[5][..]));
+    /// ```
+    pub fn chunks(&self) -> Chunks<'_, T, N, B> {
+        Chunks::new(self)
     }
 
-    #[test]
-    pub fn test_replace() {
-        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(1);
-        let mut vec = ChunkedVector::<_>::new(b"v");
-        let mut baseline = vec![];
-        for _ in 0..500 {
-            let value = rng.gen::<u64>();
-            vec.push(value);
-            baseline.push(value);
-        }
-        for _ in 0..500 {
-            let index = rng.gen::<u32>() % vec.len();
-            let value = rng.gen::<u64>();
-            let old_value0 = vec[index];
-            let old_value1 = core::mem::replace(vec.get_mut(index).unwrap(), value);
-            let old_value2 = baseline[index as usize];
-            assert_eq!(old_value0, old_value1);
-            assert_eq!(old_value0, old_value2);
-            *baseline.get_mut(index as usize).unwrap() = value;
-        }
-        let actual: Vec<_> = vec.iter().cloned().collect();
-        assert_eq!(actual, baseline);
+    /// Returns an iterator over the chunks of the vector, yielding the elements of each stored
+    /// chunk as a mutable slice. See [`chunks`](Self::chunks) for how elements are grouped.
+    ///
+    /// Every chunk yielded is written to storage when the vector is flushed, whether it is
+    /// modified or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 2> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3]);
+    ///
+    /// for chunk in vec.chunks_mut() {
+    ///     chunk.reverse();
+    /// }
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[2, 1, 3]);
+    /// ```
+    pub fn chunks_mut(&mut self) -> ChunksMut<'_, T, N, B> {
+        ChunksMut::new(self)
     }
 
-    #[test]
-    pub fn test_swap_remove() {
-        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(2);
-        let mut vec = ChunkedVector::<_>::new(b"v");
+    /// Creates a draining iterator that removes the specified range in the vector
+    /// and yields the removed items.
+    ///
+    /// When the iterator **is** dropped, all elements in the range are removed
+    /// from the vector, even if the iterator was not fully consumed. If the
+    /// iterator **is not** dropped (with [`mem::forget`](std::mem::forget) for example),
+    /// the collection will be left in an inconsistent state.
+    ///
+    /// This will not panic on invalid ranges (`end > length` or `end < start`) and instead the
+    /// iterator will just be empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_sdk::store::Vector;
+    ///
+    /// let mut vec: Vector<u32> = Vector::new(b"v");
+    /// vec.extend(vec![1, 2, 3]);
+    ///
+    /// let u: Vec<_> = vec.drain(1..).collect();
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1]);
+    /// assert_eq!(u, &[2, 3]);
+    ///
+    /// // A full range clears the vector, like `clear()` does
+    /// vec.drain(..);
+    /// assert!(vec.is_empty());
+    /// ```
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_, T, N, B>
+    where
+        R: RangeBounds<u32>,
+    {
+        let range = self.clamp_range(range);
+        Drain::new(self, range)
+    }
+
+    /// Splits the vector into two at the given index, moving the elements `[at, len)` into a
+    /// new vector with the prefix provided, which shares a clone of the storage backend.
+    ///
+    /// If `at` is a multiple of `N`, the chunks after it are moved whole. Chunks which have not
+    /// been loaded are copied in storage as bytes, without deserializing them, and are written
+    /// immediately rather than on flush. Otherwise, the elements are drained into the new vector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 2> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3, 4, 5]);
+    ///
+    /// let cold = vec.split_off(2, b"c");
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 2]);
+    /// assert_eq!(cold.iter().copied().collect::<Vec<_>>(), &[3, 4, 5]);
+    /// ```
+    pub fn split_off<S>(&mut self, at: u32, prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+        B: Clone,
+    {
+        if at > self.len() {
+            panic_str(ERR_INDEX_OUT_OF_BOUNDS);
+        }
+
+        let mut other = Self::new_in(prefix, self.values.storage.clone());
+        if chunk_pos::<N>(at) == 0 {
+            let first_chunk = chunk_index::<N>(at);
+            for chunk_idx in first_chunk..chunk_count::<N>(self.len) {
+                self.values
+                    .move_to(chunk_idx, &mut other.values, chunk_idx - first_chunk);
+            }
+            other.len = self.len - at;
+            self.len = at;
+        } else {
+            other.extend(self.drain(at..));
+        }
+        other
+    }
+
+    /// Moves all the elements of `other` to the end of this vector, leaving `other` empty.
+    ///
+    /// If the length of this vector is a multiple of `N`, the chunks of `other` are moved whole
+    /// the same way as [`split_off`](Self::split_off). Otherwise, the elements are drained from
+    /// `other` and pushed to this vector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 2> = ChunkedVector::new(b"v");
+    /// let mut other: ChunkedVector<u32, 2> = ChunkedVector::new(b"o");
+    /// vec.extend([1, 2]);
+    /// other.extend([3, 4, 5]);
+    ///
+    /// vec.append(&mut other);
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 2, 3, 4, 5]);
+    /// assert!(other.is_empty());
+    /// ```
+    pub fn append(&mut self, other: &mut Self) {
+        let len = self
+            .len
+            .checked_add(other.len)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+
+        if chunk_pos::<N>(self.len) == 0 {
+            let first_chunk = chunk_index::<N>(self.len);
+            for chunk_idx in 0..chunk_count::<N>(other.len) {
+                other
+                    .values
+                    .move_to(chunk_idx, &mut self.values, first_chunk + chunk_idx);
+            }
+            self.len = len;
+            other.len = 0;
+        } else {
+            self.extend(other.drain(..));
+        }
+    }
+
+    /// Binary searches this sorted vector with a comparator function, with the same semantics
+    /// as [`slice::binary_search_by`].
+    ///
+    /// The chunk which can contain the element is first found by comparing the first element of
+    /// each chunk, then the search continues within that single chunk. This loads at most
+    /// `log2(len / N) + 1` chunks, rather than one chunk per probed element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 2> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 3, 5, 7, 9]);
+    ///
+    /// assert_eq!(vec.binary_search_by(|x| x.cmp(&7)), Ok(3));
+    /// assert_eq!(vec.binary_search_by(|x| x.cmp(&4)), Err(2));
+    /// ```
+    pub fn binary_search_by<F>(&self, mut f: F) -> Result<u32, u32>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        // Find the number of chunks which start with an element not greater than the target.
+        let mut lo = 0;
+        let mut hi = chunk_count::<N>(self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let chunk = expect_consistent_state(self.values.get(mid));
+            match f(expect_consistent_state(chunk.first())) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Equal => return Ok(mid * N as u32),
+                Ordering::Greater => hi = mid,
+            }
+        }
+
+        // Target can only be in the last chunk which starts before it. If the position found is
+        // past its end, the insertion point is the start of the next chunk.
+        let chunk_idx = match lo.checked_sub(1) {
+            Some(chunk_idx) => chunk_idx,
+            None => return Err(0),
+        };
+        let start = chunk_idx * N as u32;
+        let chunk = expect_consistent_state(self.values.get(chunk_idx));
+        chunk
+            .binary_search_by(f)
+            .map(|pos| start + pos as u32)
+            .map_err(|pos| start + pos as u32)
+    }
+
+    /// Binary searches this sorted vector for a given element, with the same semantics as
+    /// [`slice::binary_search`]. See [`binary_search_by`](Self::binary_search_by) for the
+    /// chunks loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32> = ChunkedVector::new(b"v");
+    /// vec.extend([0, 1, 1, 2, 3, 5, 8, 13]);
+    ///
+    /// assert_eq!(vec.binary_search(&13), Ok(7));
+    /// assert_eq!(vec.binary_search(&4), Err(5));
+    /// assert!(matches!(vec.binary_search(&1), Ok(1..=2)));
+    /// ```
+    pub fn binary_search(&self, x: &T) -> Result<u32, u32>
+    where
+        T: Ord,
+    {
+        self.binary_search_by(|p| p.cmp(x))
+    }
+
+    /// Binary searches this vector, sorted by the key extracted by `f`, with the same semantics
+    /// as [`slice::binary_search_by_key`].
+    pub fn binary_search_by_key<K, F>(&self, key: &K, mut f: F) -> Result<u32, u32>
+    where
+        F: FnMut(&T) -> K,
+        K: Ord,
+    {
+        self.binary_search_by(|p| f(p).cmp(key))
+    }
+
+    /// Returns the index of the partition point according to the given predicate, which is the
+    /// index of the first element for which it returns `false`. The vector is assumed to be
+    /// partitioned, with all elements matching the predicate before all the elements which
+    /// don't, as in [`slice::partition_point`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 3> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3, 3, 5, 6, 7]);
+    ///
+    /// assert_eq!(vec.partition_point(|&x| x < 5), 4);
+    /// ```
+    pub fn partition_point<P>(&self, mut pred: P) -> u32
+    where
+        P: FnMut(&T) -> bool,
+    {
+        self.binary_search_by(|x| {
+            if pred(x) {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        })
+        .unwrap_or_else(|i| i)
+    }
+
+    /// Inserts an element into this sorted vector, keeping it sorted, and returns the index it
+    /// was inserted at. The element is inserted after any elements equal to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 3, 5]);
+    ///
+    /// assert_eq!(vec.insert_sorted(4), 2);
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 3, 4, 5]);
+    /// ```
+    pub fn insert_sorted(&mut self, element: T) -> u32
+    where
+        T: Ord,
+    {
+        let index = self.partition_point(|x| x <= &element);
+        self.insert(index, element);
+        index
+    }
+}
+
+impl<T, const N: usize, B> fmt::Debug for ChunkedVector<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if cfg!(feature = "expensive-debug") {
+            fmt::Debug::fmt(&self.iter().collect::<Vec<_>>(), f)
+        } else {
+            f.debug_struct("Vector")
+                .field("len", &self.len)
+                .field("prefix", &self.values.prefix)
+                .finish()
+        }
+    }
+}
+
+/// Guard of a reference to an element of a [`ChunkedVector`], which keeps the chunk of the
+/// element from being evicted from the cache while it is alive.
+///
+/// Created by [`ChunkedVector::iter_guarded`].
+pub struct ElementRef<'a, T, const N: usize = 5, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Values of the vector, which are only accessed through this pointer while the vector is
+    /// borrowed by the iterator which created the guard.
+    values: NonNull<IndexMap<Chunk<T, N>, B>>,
+    chunk_idx: u32,
+    element: &'a T,
+}
+
+impl<'a, T, const N: usize, B> Deref for ElementRef<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.element
+    }
+}
+
+impl<'a, T, const N: usize, B> Drop for ElementRef<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        //* SAFETY: The vector is borrowed for the lifetime of the guard, and only accessed
+        //*         through the same pointer while it is.
+        unsafe { self.values.as_ref() }.unpin(self.chunk_idx);
+    }
+}
+
+impl<'a, T, const N: usize, B> fmt::Debug for ElementRef<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ElementRef").field(self.element).finish()
+    }
+}
+
+/// Guard of an exclusive reference to an element of a [`ChunkedVector`], which keeps the chunk
+/// of the element from being evicted from the cache while it is alive.
+///
+/// Created by [`ChunkedVector::iter_mut_guarded`].
+pub struct ElementRefMut<'a, T, const N: usize = 5, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Values of the vector, which are only accessed through this pointer while the vector is
+    /// borrowed by the iterator which created the guard.
+    values: NonNull<IndexMap<Chunk<T, N>, B>>,
+    chunk_idx: u32,
+    element: &'a mut T,
+}
+
+impl<'a, T, const N: usize, B> Deref for ElementRefMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.element
+    }
+}
+
+impl<'a, T, const N: usize, B> DerefMut for ElementRefMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        self.element
+    }
+}
+
+impl<'a, T, const N: usize, B> Drop for ElementRefMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        //* SAFETY: The vector is borrowed for the lifetime of the guard, and only accessed
+        //*         through the same pointer while it is.
+        unsafe { self.values.as_ref() }.unpin(self.chunk_idx);
+    }
+}
+
+impl<'a, T, const N: usize, B> fmt::Debug for ElementRefMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ElementRefMut").field(&self.element).finish()
+    }
+}
+
+/// Guard of a mutable reference to an element of a [`ChunkedVector`], which only marks the
+/// chunk of the element as modified when it is dereferenced mutably.
+///
+/// Created by [`ChunkedVector::element_mut`].
+pub struct ElementMut<'a, T, const N: usize = 5, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    vec: &'a mut ChunkedVector<T, N, B>,
+    index: u32,
+}
+
+impl<'a, T, const N: usize, B> Deref for ElementMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        expect_consistent_state(self.vec.get(self.index))
+    }
+}
+
+impl<'a, T, const N: usize, B> DerefMut for ElementMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        expect_consistent_state(self.vec.get_mut(self.index))
+    }
+}
+
+impl<'a, T, const N: usize, B> fmt::Debug for ElementMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ElementMut").field(&**self).finish()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{Rng, RngCore, SeedableRng};
+
+    use super::ChunkedVector;
+    use crate::index_map::IndexMap;
+    use crate::storage::{InMemoryStorage, NearStorage, StorageBackend};
+    use crate::utils::test_iters;
+    use near_sdk::test_utils::test_env::setup_free;
+
+    /// Recreates a vector from the chunks stored under `prefix`, without any cached chunks.
+    fn reload<T, const N: usize, B>(prefix: &[u8], len: u32, storage: B) -> ChunkedVector<T, N, B>
+    where
+        T: BorshSerialize,
+        B: StorageBackend,
+    {
+        ChunkedVector {
+            len,
+            values: IndexMap::new_in(prefix, storage),
+        }
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut vec = ChunkedVector::<_>::new(b"v");
+        let mut baseline = vec![];
+        for _ in 0..test_iters(500) {
+            let value = rng.gen::<u64>();
+            vec.push(value);
+            baseline.push(value);
+        }
+        let actual: Vec<u64> = vec.iter().cloned().collect();
+        assert_eq!(actual, baseline);
+        for _ in 0..=test_iters(500) {
+            assert_eq!(baseline.pop(), vec.pop());
+        }
+    }
+
+    #[test]
+    pub fn test_replace() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(1);
+        let mut vec = ChunkedVector::<_>::new(b"v");
+        let mut baseline = vec![];
+        for _ in 0..test_iters(500) {
+            let value = rng.gen::<u64>();
+            vec.push(value);
+            baseline.push(value);
+        }
+        for _ in 0..test_iters(500) {
+            let index = rng.gen::<u32>() % vec.len();
+            let value = rng.gen::<u64>();
+            let old_value0 = vec[index];
+            let old_value1 = core::mem::replace(vec.get_mut(index).unwrap(), value);
+            let old_value2 = baseline[index as usize];
+            assert_eq!(old_value0, old_value1);
+            assert_eq!(old_value0, old_value2);
+            *baseline.get_mut(index as usize).unwrap() = value;
+        }
+        let actual: Vec<_> = vec.iter().cloned().collect();
+        assert_eq!(actual, baseline);
+    }
+
+    #[test]
+    pub fn test_swap_remove() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(2);
+        let mut vec = ChunkedVector::<_>::new(b"v");
         let mut baseline = vec![];
-        for _ in 0..500 {
+        for _ in 0..test_iters(500) {
             let value = rng.gen::<u64>();
             vec.push(value);
             baseline.push(value);
         }
-        for _ in 0..500 {
+        for _ in 0..test_iters(500) {
             let index = rng.gen::<u32>() % vec.len();
             let old_value0 = vec[index];
             let old_value1 = vec.swap_remove(index);
@@ -632,6 +1429,49 @@ mod tests {
         assert_eq!(actual, baseline);
     }
 
+    #[test]
+    pub fn test_insert_remove() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(6);
+        let mut vec = ChunkedVector::<_>::new(b"v");
+        let mut baseline = vec![];
+        for _ in 0..test_iters(500) {
+            let index = rng.gen::<u32>() % (vec.len() + 1);
+            let value = rng.gen::<u64>();
+            vec.insert(index, value);
+            baseline.insert(index as usize, value);
+        }
+        let actual: Vec<_> = vec.iter().cloned().collect();
+        assert_eq!(actual, baseline);
+        for _ in 0..test_iters(500) {
+            let index = rng.gen::<u32>() % vec.len();
+            assert_eq!(vec.remove(index), baseline.remove(index as usize));
+        }
+        assert!(vec.is_empty());
+        vec.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[test]
+    pub fn test_drop_types() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(5);
+        let mut vec = ChunkedVector::<_>::new(b"v");
+        let mut baseline = vec![];
+        for _ in 0..100 {
+            let value = (rng.gen::<u64>().to_string(), vec![rng.gen::<u8>(); 3]);
+            vec.push(value.clone());
+            baseline.push(value);
+        }
+        for _ in 0..50 {
+            let index = rng.gen::<u32>() % vec.len();
+            assert_eq!(vec.swap_remove(index), baseline.swap_remove(index as usize));
+        }
+        for _ in 0..10 {
+            assert_eq!(vec.pop(), baseline.pop());
+        }
+        vec.flush();
+        assert!(Iterator::eq(vec.iter(), baseline.iter()));
+    }
+
     #[test]
     pub fn test_clear() {
         let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(3);
@@ -645,6 +1485,33 @@ mod tests {
             vec.clear();
             assert!(vec.is_empty());
         }
+        vec.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[test]
+    pub fn test_truncate() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(7);
+        let mut vec = ChunkedVector::<_>::new(b"v");
+        let mut baseline = vec![];
+        for _ in 0..50 {
+            for _ in 0..(rng.gen::<u64>() % 20) {
+                let value = rng.gen::<u64>();
+                vec.push(value);
+                baseline.push(value);
+            }
+            let len = rng.gen::<u32>() % (vec.len() + 5);
+            vec.truncate(len);
+            baseline.truncate(len as usize);
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+        }
+
+        // Only the chunks needed for the remaining elements are left in storage.
+        vec.extend(0..10);
+        vec.truncate(7);
+        vec.flush();
+        let storage = near_sdk::mock::with_mocked_blockchain(|m| m.take_storage());
+        assert_eq!(storage.len(), 2);
     }
 
     #[test]
@@ -652,13 +1519,13 @@ mod tests {
         let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
         let mut vec = ChunkedVector::<_>::new(b"v");
         let mut baseline = vec![];
-        for _ in 0..100 {
+        for _ in 0..test_iters(100) {
             let value = rng.gen::<u64>();
             vec.push(value);
             baseline.push(value);
         }
 
-        for _ in 0..100 {
+        for _ in 0..test_iters(100) {
             let mut tmp = vec![];
             for _ in 0..=(rng.gen::<u64>() % 20 + 1) {
                 let value = rng.gen::<u64>();
@@ -705,7 +1572,7 @@ mod tests {
 
         let deserialize_only_vec = ChunkedVector::<TestType> {
             len: vec.len(),
-            values: IndexMap::new(prefix),
+            values: IndexMap::new_in(prefix, NearStorage),
         };
         let baseline: Vec<_> = baseline.into_iter().map(TestType).collect();
         if cfg!(feature = "expensive-debug") {
@@ -751,58 +1618,459 @@ mod tests {
         assert_eq!(vec.iter().count(), baseline.len());
     }
 
-    // #[test]
-    // fn drain_iterator() {
-    //     let mut vec = ChunkedVector::<_>::new(b"v");
-    //     let mut baseline = vec![0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9];
-    //     vec.extend(baseline.clone());
-
-    //     assert!(Iterator::eq(vec.drain(1..=3), baseline.drain(1..=3)));
-    //     assert_eq!(
-    //         vec.iter().copied().collect::<Vec<_>>(),
-    //         vec![0, 4, 5, 6, 7, 8, 9]
-    //     );
-
-    //     // Test incomplete drain
-    //     {
-    //         let mut drain = vec.drain(0..3);
-    //         let mut b_drain = baseline.drain(0..3);
-    //         assert_eq!(drain.next(), b_drain.next());
-    //         assert_eq!(drain.next(), b_drain.next());
-    //     }
-
-    //     // 7 elements, drained 3
-    //     assert_eq!(vec.len(), 4);
-
-    //     // Test incomplete drain over limit
-    //     {
-    //         let mut drain = vec.drain(2..);
-    //         let mut b_drain = baseline.drain(2..);
-    //         assert_eq!(drain.next(), b_drain.next());
-    //     }
-
-    //     // Drain rest
-    //     assert!(Iterator::eq(vec.drain(..), baseline.drain(..)));
-
-    //     // Test double ended iterator functions
-    //     let mut vec = ChunkedVector::<_>::new(b"v");
-    //     let mut baseline = vec![0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9];
-    //     vec.extend(baseline.clone());
-
-    //     {
-    //         let mut drain = vec.drain(1..8);
-    //         let mut b_drain = baseline.drain(1..8);
-    //         assert_eq!(drain.nth(1), b_drain.nth(1));
-    //         assert_eq!(drain.nth_back(2), b_drain.nth_back(2));
-    //         assert_eq!(drain.len(), b_drain.len());
-    //     }
-
-    //     assert_eq!(vec.len() as usize, baseline.len());
-    //     assert!(Iterator::eq(vec.iter(), baseline.iter()));
-
-    //     assert!(Iterator::eq(vec.drain(..), baseline.drain(..)));
-    //     near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
-    // }
+    #[test]
+    fn drain_iterator() {
+        let mut vec = ChunkedVector::<_>::new(b"v");
+        let mut baseline = vec![0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        vec.extend(baseline.clone());
+
+        assert!(Iterator::eq(vec.drain(1..=3), baseline.drain(1..=3)));
+        assert_eq!(
+            vec.iter().copied().collect::<Vec<_>>(),
+            vec![0, 4, 5, 6, 7, 8, 9]
+        );
+
+        // Test incomplete drain
+        {
+            let mut drain = vec.drain(0..3);
+            let mut b_drain = baseline.drain(0..3);
+            assert_eq!(drain.next(), b_drain.next());
+            assert_eq!(drain.next(), b_drain.next());
+        }
+
+        // 7 elements, drained 3
+        assert_eq!(vec.len(), 4);
+
+        // Test incomplete drain over limit
+        {
+            let mut drain = vec.drain(2..);
+            let mut b_drain = baseline.drain(2..);
+            assert_eq!(drain.next(), b_drain.next());
+        }
+
+        // Drain rest
+        assert!(Iterator::eq(vec.drain(..), baseline.drain(..)));
+
+        // Test double ended iterator functions
+        let mut vec = ChunkedVector::<_>::new(b"v");
+        let mut baseline = vec![0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        vec.extend(baseline.clone());
+
+        {
+            let mut drain = vec.drain(1..8);
+            let mut b_drain = baseline.drain(1..8);
+            assert_eq!(drain.nth(1), b_drain.nth(1));
+            assert_eq!(drain.nth_back(2), b_drain.nth_back(2));
+            assert_eq!(drain.len(), b_drain.len());
+        }
+
+        assert_eq!(vec.len() as usize, baseline.len());
+        assert!(Iterator::eq(vec.iter(), baseline.iter()));
+
+        assert!(Iterator::eq(vec.drain(..), baseline.drain(..)));
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[test]
+    fn test_binary_search() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(2);
+        let mut vec = ChunkedVector::<u8, 4>::new(b"v");
+        let mut baseline: Vec<u8> = vec![];
+        for _ in 0..test_iters(200) {
+            let value = rng.gen::<u8>() % 100;
+            let index = vec.insert_sorted(value);
+            let b_index = baseline.partition_point(|x| x <= &value);
+            baseline.insert(b_index, value);
+            assert_eq!(index as usize, b_index);
+
+            let target = rng.gen::<u8>() % 110;
+            match vec.binary_search(&target) {
+                Ok(i) => assert_eq!(baseline[i as usize], target),
+                Err(i) => assert_eq!(Err(i as usize), baseline.binary_search(&target)),
+            }
+            assert_eq!(
+                vec.partition_point(|x| x < &target) as usize,
+                baseline.partition_point(|x| x < &target)
+            );
+        }
+        assert!(Iterator::eq(vec.iter(), baseline.iter()));
+
+        let empty = ChunkedVector::<u8, 4>::new(b"e");
+        assert_eq!(empty.binary_search(&1), Err(0));
+        assert_eq!(empty.partition_point(|_| true), 0);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(3);
+        // Miri is too slow to sort the longer vectors.
+        let lens: &[u32] = match cfg!(miri) {
+            true => &[0, 1, 3, 4, 5, 17],
+            false => &[0, 1, 3, 4, 5, 17, 64, 203],
+        };
+        for &len in lens {
+            let mut vec = ChunkedVector::<(u8, u32), 4>::new(b"v");
+            let mut baseline = vec![];
+            for i in 0..len {
+                let value = (rng.gen::<u8>() % 16, i);
+                vec.push(value);
+                baseline.push(value);
+            }
+
+            // Stable sort keeps the insertion order of equal keys.
+            vec.sort_by_key(|v| v.0);
+            baseline.sort_by_key(|v| v.0);
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+
+            vec.sort_by(|a, b| b.cmp(a));
+            baseline.sort_by(|a, b| b.cmp(a));
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+
+            vec.sort_unstable();
+            baseline.sort_unstable();
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+
+            // Sorting persists the same chunks as pushing the sorted elements, also when merged
+            // chunks are evicted before being moved into place.
+            vec.set_cache_capacity(Some(3));
+            vec.sort();
+            vec.flush();
+            drop(vec);
+            let vec: ChunkedVector<(u8, u32), 4> = reload(b"v", len, NearStorage);
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+            near_sdk::mock::with_mocked_blockchain(|m| {
+                assert_eq!(m.take_storage().len() as u32, len.div_ceil(4))
+            });
+        }
+    }
+
+    #[test]
+    fn test_retain_dedup() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(5);
+        let mut vec = ChunkedVector::<u32, 4>::new(b"v");
+        let mut baseline = vec![];
+        for _ in 0..20 {
+            for _ in 0..(rng.gen::<u32>() % 30) {
+                let value = rng.gen::<u32>() % 8;
+                vec.push(value);
+                baseline.push(value);
+            }
+            let m = rng.gen::<u32>() % 8 + 1;
+            vec.retain_mut(|v| {
+                *v += 1;
+                *v % m != 0
+            });
+            baseline.retain_mut(|v| {
+                *v += 1;
+                *v % m != 0
+            });
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+
+            vec.dedup();
+            baseline.dedup();
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+        }
+
+        // Chunks left empty after compaction are removed from storage.
+        vec.clear();
+        vec.extend(0..10);
+        vec.flush();
+        vec.retain(|v| *v < 3 || *v == 9);
+        vec.flush();
+        assert!(Iterator::eq(vec.iter().copied(), [0, 1, 2, 9]));
+        let storage = near_sdk::mock::with_mocked_blockchain(|m| m.take_storage());
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    fn test_split_off_append() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(6);
+        let mut vec = ChunkedVector::<u64, 4>::new(b"v");
+        let mut baseline = vec![];
+        for _ in 0..test_iters(50) {
+            for _ in 0..(rng.gen::<u32>() % 20) {
+                let value = rng.gen::<u64>();
+                vec.push(value);
+                baseline.push(value);
+            }
+            if rng.gen::<bool>() {
+                vec.flush();
+            }
+
+            // Split at chunk boundaries half of the time.
+            let at = match rng.gen::<bool>() {
+                true => rng.gen::<u32>() % (vec.len() / 4 + 1) * 4,
+                false => rng.gen::<u32>() % (vec.len() + 1),
+            };
+            let mut other = vec.split_off(at, b"o");
+            let b_other = baseline.split_off(at as usize);
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+            assert!(Iterator::eq(other.iter(), b_other.iter()));
+
+            other.flush();
+            drop(other);
+            let mut other: ChunkedVector<u64, 4> = reload(b"o", b_other.len() as u32, NearStorage);
+            vec.append(&mut other);
+            baseline.extend(b_other);
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+            assert!(other.is_empty());
+        }
+
+        // Moved chunks are copied as bytes, and removed from the previous prefix.
+        vec.clear();
+        vec.extend(0..10);
+        drop(vec);
+        let mut vec: ChunkedVector<u64, 4> = reload(b"v", 10, NearStorage);
+        let chunk_key = |prefix: &[u8], index: u32| [prefix, &index.to_le_bytes()].concat();
+        let chunk_bytes = near_sdk::env::storage_read(&chunk_key(b"v", 1)).unwrap();
+        let mut other = vec.split_off(4, b"o");
+        assert_eq!(
+            near_sdk::env::storage_read(&chunk_key(b"o", 0)),
+            Some(chunk_bytes)
+        );
+        vec.flush();
+        other.flush();
+        assert!(!near_sdk::env::storage_has_key(&chunk_key(b"v", 1)));
+        assert!(Iterator::eq(other.iter().copied(), 4..10));
+    }
+
+    #[test]
+    fn test_chunks() {
+        let mut vec = ChunkedVector::<u32, 4>::new(b"v");
+        assert_eq!(vec.chunks().next(), None);
+
+        vec.extend(0..10);
+        let chunks: Vec<_> = vec.chunks().collect();
+        assert_eq!(chunks, [&[0, 1, 2, 3][..], &[4, 5, 6, 7], &[8, 9]]);
+        assert_eq!(vec.chunks().len(), 3);
+        assert_eq!(vec.chunks().nth_back(1), Some(&[4, 5, 6, 7][..]));
+
+        let mut chunks_mut = vec.chunks_mut();
+        let first = chunks_mut.next().unwrap();
+        let last = chunks_mut.next_back().unwrap();
+        first[0] = 10;
+        last[1] = 11;
+        assert_eq!(chunks_mut.len(), 1);
+        assert!(Iterator::eq(
+            vec.iter().copied(),
+            [10, 1, 2, 3, 4, 5, 6, 7, 8, 11]
+        ));
+
+        // Only the chunks yielded are loaded and written.
+        vec.flush();
+        drop(vec);
+        let mut vec: ChunkedVector<u32, 4> = reload(b"v", 10, NearStorage);
+        vec.chunks_mut().nth(1).unwrap()[0] = 12;
+        near_sdk::mock::with_mocked_blockchain(|m| m.take_storage());
+        vec.flush();
+        let storage = near_sdk::mock::with_mocked_blockchain(|m| m.take_storage());
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    fn test_iter_range() {
+        let mut vec = ChunkedVector::<u32, 4>::new(b"v");
+        let mut baseline: Vec<u32> = (0..30).collect();
+        vec.extend(baseline.iter().copied());
+
+        assert!(Iterator::eq(vec.iter_range(5..17), baseline[5..17].iter()));
+        assert!(Iterator::eq(
+            vec.iter_range(..=3).rev(),
+            baseline[..=3].iter().rev()
+        ));
+        assert!(Iterator::eq(vec.iter_range(25..40), baseline[25..].iter()));
+        let (start, end) = (20, 10);
+        assert_eq!(vec.iter_range(start..end).len(), 0);
+        assert_eq!(vec.iter_range(3..9).nth(4), Some(&7));
+
+        for elem in vec.iter_mut_range(10..12) {
+            *elem += 100;
+        }
+        baseline[10..12].iter_mut().for_each(|elem| *elem += 100);
+        assert!(Iterator::eq(vec.iter(), baseline.iter()));
+
+        // Skipped chunks are not loaded, and only the chunk modified is written.
+        vec.flush();
+        drop(vec);
+        let mut vec: ChunkedVector<u32, 4> = reload(b"v", 30, NearStorage);
+        *vec.iter_mut_range(2..).nth(20).unwrap() = 0;
+        near_sdk::mock::with_mocked_blockchain(|m| m.take_storage());
+        vec.flush();
+        let storage = near_sdk::mock::with_mocked_blockchain(|m| m.take_storage());
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    fn test_fixed_size() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(9);
+        let mut vec = ChunkedVector::<u64, 4>::new(b"v");
+        let mut baseline = vec![];
+        for _ in 0..test_iters(50) {
+            // Rebuild from storage half of the time, so elements are accessed in place.
+            if rng.gen::<bool>() {
+                vec.flush();
+                drop(vec);
+                vec = reload(b"v", baseline.len() as u32, NearStorage);
+            }
+            for _ in 0..(rng.gen::<u32>() % 10) {
+                let value = rng.gen::<u64>();
+                vec.push_fixed(value);
+                baseline.push(value);
+            }
+            for _ in 0..5 {
+                let index = rng.gen::<u32>() % (vec.len() + 1);
+                assert_eq!(vec.get_fixed(index), baseline.get(index as usize).copied());
+                if index < vec.len() {
+                    let value = rng.gen::<u64>();
+                    let prev = core::mem::replace(&mut baseline[index as usize], value);
+                    assert_eq!(vec.replace_fixed(index, value), prev);
+                }
+            }
+            assert!(Iterator::eq(vec.iter(), baseline.iter()));
+        }
+
+        // Elements patched in place are written to storage on flush without loading the chunk.
+        vec.flush();
+        drop(vec);
+        let mut vec: ChunkedVector<u64, 4> = reload(b"v", baseline.len() as u32, NearStorage);
+        let key = [b"v" as &[u8], &0u32.to_le_bytes()].concat();
+        let prev = vec.replace_fixed(1, 7);
+        assert_eq!(vec.replace_fixed(1, 8), 7);
+        assert_eq!(vec.get_fixed(1), Some(8));
+        assert!(!vec.values.is_loaded(0));
+        let chunk = near_sdk::env::storage_read(&key);
+        assert_eq!(&chunk.unwrap()[12..20], &prev.to_le_bytes());
+        vec.flush();
+        let chunk = near_sdk::env::storage_read(&key);
+        assert_eq!(&chunk.unwrap()[12..20], &8u64.to_le_bytes());
+        assert_eq!(vec[1], 8);
+    }
+
+    #[test]
+    fn test_unchanged_chunks() {
+        /// Storage which counts the values written to it.
+        #[derive(Clone, Default)]
+        struct CountingStorage {
+            inner: InMemoryStorage,
+            writes: std::rc::Rc<core::cell::Cell<usize>>,
+        }
+
+        impl StorageBackend for CountingStorage {
+            fn read(&self, key: &[u8]) -> Option<Vec<u8>> {
+                self.inner.read(key)
+            }
+
+            fn write(&mut self, key: &[u8], value: &[u8]) {
+                self.writes.set(self.writes.get() + 1);
+                self.inner.write(key, value)
+            }
+
+            fn remove(&mut self, key: &[u8]) {
+                self.inner.remove(key)
+            }
+        }
+
+        let storage = CountingStorage::default();
+        let writes = storage.writes.clone();
+        let mut vec = ChunkedVector::<u64, 5, _>::new_in(b"v", storage.clone());
+        vec.extend(0..20);
+        vec.flush();
+        assert_eq!(writes.replace(0), 4);
+
+        // Chunks accessed mutably but not changed are not written.
+        for elem in vec.iter_mut() {
+            assert!(*elem < 20);
+        }
+        vec.flush();
+        assert_eq!(writes.replace(0), 0);
+
+        vec[7] += 1;
+        vec.flush();
+        assert_eq!(writes.replace(0), 1);
+        vec[7] -= 1;
+        vec.flush();
+        assert_eq!(writes.replace(0), 1);
+
+        // Loaded chunks are compared to the bytes they were loaded from.
+        drop(vec);
+        let mut vec: ChunkedVector<u64, 5, _> = reload(b"v", 20, storage);
+        for i in 0..vec.len() {
+            let mut elem = vec.element_mut(i).unwrap();
+            if i == 12 {
+                *elem = 0;
+            }
+            assert!(*elem < 20);
+        }
+        vec.flush();
+        assert_eq!(writes.replace(0), 1);
+        assert_eq!(vec[12], 0);
+
+        // Chunks before the first duplicate are not written when deduplicating.
+        vec[17] = 16;
+        vec.flush();
+        assert_eq!(writes.replace(0), 1);
+        vec.dedup();
+        vec.flush();
+        assert_eq!(writes.replace(0), 1);
+        assert!(Iterator::eq(
+            vec.iter_range(15..).copied(),
+            [15, 16, 18, 19]
+        ));
+    }
+
+    #[test]
+    fn test_cache_capacity() {
+        let storage = InMemoryStorage::new();
+        let mut vec = ChunkedVector::<u64, 4, _>::new_in(b"v", storage.clone());
+        vec.set_cache_capacity(Some(2));
+        vec.extend(0..100);
+
+        // Chunks over the capacity are written to storage when evicted.
+        assert_eq!(vec.cached_chunks(), 2);
+        assert_eq!(storage.len(), 23);
+
+        // Chunks are released as guards are dropped, while the ones still referenced are kept.
+        let mut refs = vec.iter_guarded();
+        let first = refs.next().unwrap();
+        let last = refs.next_back().unwrap();
+        assert!(Iterator::eq(refs.map(|e| *e), 1..99));
+        assert_eq!((*first, *last), (0, 99));
+        drop((first, last));
+        assert_eq!(vec.cached_chunks(), 2);
+
+        let mut refs = vec.iter_mut_guarded();
+        let mut first = refs.next().unwrap();
+        for mut e in refs {
+            *e += 1;
+        }
+        *first = 100;
+        drop(first);
+        assert_eq!(vec.cached_chunks(), 2);
+        assert!(Iterator::eq(
+            vec.iter().copied(),
+            [100].into_iter().chain(2..101)
+        ));
+
+        // Chunks loaded through a shared reference are kept until the vector is accessed
+        // mutably.
+        assert_eq!(vec.cached_chunks(), 25);
+        vec[0] = 1;
+        assert_eq!(vec.cached_chunks(), 2);
+
+        // Chunks are not evicted while iterating mutably.
+        let refs: Vec<&mut u64> = vec.iter_mut().collect();
+        for e in refs {
+            *e -= 1;
+        }
+        assert_eq!(vec.cached_chunks(), 25);
+        assert_eq!(vec.pop(), Some(99));
+        assert_eq!(vec.cached_chunks(), 2);
+
+        vec.set_cache_capacity(None);
+        assert!(Iterator::eq(vec.iter().copied(), 0..99));
+        assert_eq!(vec.cache_capacity(), None);
+        assert_eq!(vec.cached_chunks(), 25);
+    }
 
     #[derive(Arbitrary, Debug)]
     enum Op {
@@ -813,6 +2081,14 @@ mod tests {
         Reset,
         Get(u32),
         Swap(u32, u32),
+        Drain(u32, u32),
+        Insert(u32, u8),
+        OrderedRemove(u32),
+        Sort,
+        Retain(u8),
+        Dedup(u8),
+        Capacity(Option<u8>),
+        IterGuarded,
     }
 
     #[test]
@@ -821,7 +2097,9 @@ mod tests {
 
         let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
         let mut buf = vec![0; 4096];
-        for _ in 0..1024 {
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 1024 };
+        for _ in 0..runs {
             // Clear storage in-between runs
             near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
             rng.fill_bytes(&mut buf);
@@ -872,6 +2150,53 @@ mod tests {
                             sv.swap(i1, i2);
                             mv.swap(i1 as usize, i2 as usize)
                         }
+                        Op::Drain(start, len) => {
+                            let start = start % (sv.len() + 1);
+                            let end = core::cmp::min(start.saturating_add(len % 16), sv.len());
+                            let r1: Vec<_> = sv.drain(start..end).collect();
+                            let r2: Vec<_> = mv.drain(start as usize..end as usize).collect();
+                            assert_eq!(r1, r2);
+                            assert_eq!(sv.len() as usize, mv.len());
+                        }
+                        Op::Insert(i, v) => {
+                            let i = i % (sv.len() + 1);
+                            sv.insert(i, v);
+                            mv.insert(i as usize, v);
+                            assert_eq!(sv.len() as usize, mv.len());
+                        }
+                        Op::OrderedRemove(i) => {
+                            if sv.is_empty() {
+                                continue;
+                            }
+                            let i = i % sv.len();
+                            assert_eq!(sv.remove(i), mv.remove(i as usize));
+                            assert_eq!(sv.len() as usize, mv.len());
+                        }
+                        Op::Sort => {
+                            sv.sort();
+                            mv.sort();
+                        }
+                        Op::Retain(m) => {
+                            let m = m % 4 + 1;
+                            sv.retain(|v| v % m != 0);
+                            mv.retain(|v| v % m != 0);
+                            assert_eq!(sv.len() as usize, mv.len());
+                        }
+                        Op::Dedup(m) => {
+                            let m = m % 16 + 1;
+                            sv.dedup_by_key(|v| *v / m);
+                            mv.dedup_by_key(|v| *v / m);
+                            assert_eq!(sv.len() as usize, mv.len());
+                        }
+                        Op::Capacity(c) => {
+                            sv.set_cache_capacity(c.map(|c| c as u32 % 4 + 1));
+                        }
+                        Op::IterGuarded => {
+                            assert!(Iterator::eq(
+                                sv.iter_guarded().map(|e| *e),
+                                mv.iter().copied()
+                            ));
+                        }
                     }
                 }
             }
@@ -881,6 +2206,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn partial_chunk_bytes() {
+        let mut vec = ChunkedVector::<u64, 5>::new(b"v");
+        vec.extend(0..7);
+        vec.flush();
+
+        let chunk_key = |index: u32| [&b"v"[..], &index.to_le_bytes()].concat();
+        let stored = |index: u32| near_sdk::env::storage_read(&chunk_key(index)).unwrap();
+
+        // Full chunk stores all elements, tail chunk stores only the two pushed to it.
+        assert_eq!(
+            stored(0),
+            (0..5u64).collect::<Vec<_>>().try_to_vec().unwrap()
+        );
+        assert_eq!(stored(1), vec![5u64, 6].try_to_vec().unwrap());
+
+        // Popping shrinks the persisted chunk rather than leaving a stale slot.
+        assert_eq!(vec.pop(), Some(6));
+        vec.flush();
+        assert_eq!(stored(1), vec![5u64].try_to_vec().unwrap());
+
+        assert_eq!(vec.pop(), Some(5));
+        vec.flush();
+        assert!(!near_sdk::env::storage_has_key(&chunk_key(1)));
+
+        // Chunks longer than the chunk size are rejected when loaded.
+        let oversized = vec![0u64; 6].try_to_vec().unwrap();
+        assert!(super::Chunk::<u64, 5>::try_from_slice(&oversized).is_err());
+    }
+
+    #[test]
+    fn in_memory_storage() {
+        let storage = InMemoryStorage::new();
+        let mut vec = ChunkedVector::<u64, 5, _>::new_in(b"v", storage.clone());
+        let mut other = ChunkedVector::<u64, 5, _>::new_in(b"o", storage.clone());
+        vec.extend(0..12);
+        other.extend(0..3);
+        vec.flush();
+        other.flush();
+
+        // Both vectors share the storage, but nothing is written to the NEAR storage.
+        assert_eq!(storage.len(), 4);
+        assert!(storage.has(&[&b"v"[..], &2u32.to_le_bytes()].concat()));
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+
+        // Vector can be loaded again from the same storage.
+        drop(vec);
+        let mut vec: ChunkedVector<u64, 5, _> = reload(b"v", 12, storage.clone());
+        assert!(Iterator::eq(vec.iter().copied(), 0..12));
+
+        vec.clear();
+        other.clear();
+        drop((vec, other));
+        assert!(storage.is_empty());
+    }
+
     #[test]
     fn serialized_bytes() {
         use borsh::{BorshDeserialize, BorshSerialize};
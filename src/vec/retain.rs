@@ -0,0 +1,248 @@
+use alloc::vec::Vec;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::ChunkedVector;
+use crate::chunk::{chunk_count, Chunk};
+use crate::storage::StorageBackend;
+use crate::utils::expect_consistent_state;
+
+impl<T, const N: usize, B> ChunkedVector<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Retains only the elements specified by the predicate, with the same semantics as
+    /// [`Vec::retain`].
+    ///
+    /// Chunks before the first removed element are only read. From the chunk of that element,
+    /// the following elements are streamed in order into compacted chunks, so every chunk is
+    /// written at most once and the chunks left empty at the end are removed from storage.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 2> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3, 4, 5]);
+    ///
+    /// vec.retain(|&x| x % 2 == 0);
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[2, 4]);
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        for chunk_idx in 0..chunk_count::<N>(self.len) {
+            let chunk = expect_consistent_state(self.values.get(chunk_idx));
+            if let Some(pos) = chunk.iter().position(|e| !f(e)) {
+                let elements = expect_consistent_state(self.values.remove(chunk_idx)).into_vec();
+                return self.compact(chunk_idx, elements, pos, false, |e, _| f(e));
+            }
+        }
+    }
+
+    /// Retains only the elements specified by the predicate, passing a mutable reference to it,
+    /// with the same semantics as [`Vec::retain_mut`].
+    ///
+    /// Chunks are compacted the same way as [`retain`](Self::retain), but every chunk visited
+    /// is written since its elements can be modified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 2> = ChunkedVector::new(b"v");
+    /// vec.extend([1, 2, 3, 4]);
+    ///
+    /// vec.retain_mut(|x| if *x <= 3 {
+    ///     *x += 1;
+    ///     true
+    /// } else {
+    ///     false
+    /// });
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[2, 3, 4]);
+    /// ```
+    pub fn retain_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        for chunk_idx in 0..chunk_count::<N>(self.len) {
+            let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+            if let Some(pos) = chunk.iter_mut().position(|e| !f(e)) {
+                let elements = expect_consistent_state(self.values.remove(chunk_idx)).into_vec();
+                return self.compact(chunk_idx, elements, pos, false, |e, _| f(e));
+            }
+        }
+    }
+
+    /// Removes all but the first of consecutive elements in the vector satisfying a given
+    /// equality relation, with the same semantics as [`Vec::dedup_by`].
+    ///
+    /// Chunks before the first duplicate are accessed in place, so they are only written if
+    /// `same_bucket` modifies their elements. From the chunk of that duplicate, chunks are
+    /// compacted the same way as [`retain`](Self::retain).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<String, 2> = ChunkedVector::new(b"v");
+    /// vec.extend(["foo", "bar", "Bar", "baz", "bar"].map(String::from));
+    ///
+    /// vec.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+    /// assert_eq!(vec.iter().collect::<Vec<_>>(), &["foo", "bar", "baz", "bar"]);
+    /// ```
+    pub fn dedup_by<F>(&mut self, mut same_bucket: F)
+    where
+        F: FnMut(&mut T, &mut T) -> bool,
+    {
+        // References handed out before can't be alive anymore, so only the chunks pinned below
+        // are kept in the cache.
+        self.values.next_epoch();
+        let count = chunk_count::<N>(self.len);
+        let mut prev_last: Option<*mut T> = None;
+        for chunk_idx in 0..count {
+            // The chunk is pinned to compare its last element with the first element of the
+            // next chunk. Flushing only writes it if its elements were modified.
+            let chunk = expect_consistent_state(self.values.get_mut_pinned(chunk_idx));
+            let len = chunk.len();
+            let elements = chunk.as_mut_ptr();
+
+            let mut duplicate = None;
+            for pos in 0..len {
+                let prev = match pos.checked_sub(1) {
+                    //* SAFETY: Elements are accessed through pointers to the elements of pinned
+                    //*         chunks, which are kept in the cache, and the two elements compared
+                    //*         never overlap.
+                    Some(prev) => unsafe { elements.add(prev) },
+                    None => match prev_last {
+                        Some(prev) => prev,
+                        None => continue,
+                    },
+                };
+                //* SAFETY: See above.
+                if unsafe { same_bucket(&mut *elements.add(pos), &mut *prev) } {
+                    duplicate = Some(pos);
+                    break;
+                }
+            }
+
+            if let Some(prev_idx) = chunk_idx.checked_sub(1) {
+                self.values.unpin(prev_idx);
+            }
+            if let Some(pos) = duplicate {
+                self.values.unpin(chunk_idx);
+                let elements = expect_consistent_state(self.values.remove(chunk_idx)).into_vec();
+                return self.compact(chunk_idx, elements, pos, true, |e, prev| {
+                    !prev.is_some_and(|prev| same_bucket(e, prev))
+                });
+            }
+            //* SAFETY: The pointer stays within the elements of the chunk.
+            prev_last = len.checked_sub(1).map(|last| unsafe { elements.add(last) });
+        }
+        if let Some(last_idx) = count.checked_sub(1) {
+            self.values.unpin(last_idx);
+        }
+    }
+
+    /// Removes all but the first of consecutive elements in the vector that resolve to the same
+    /// key, with the same semantics as [`Vec::dedup_by_key`]. See [`dedup_by`](Self::dedup_by)
+    /// for how chunks are written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<u32, 2> = ChunkedVector::new(b"v");
+    /// vec.extend([10, 20, 21, 30, 20]);
+    ///
+    /// vec.dedup_by_key(|i| *i / 10);
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[10, 20, 30, 20]);
+    /// ```
+    pub fn dedup_by_key<F, K>(&mut self, mut key: F)
+    where
+        F: FnMut(&mut T) -> K,
+        K: PartialEq,
+    {
+        self.dedup_by(|a, b| key(a) == key(b))
+    }
+
+    /// Removes consecutive repeated elements in the vector, with the same semantics as
+    /// [`Vec::dedup`]. See [`dedup_by`](Self::dedup_by) for how chunks are written.
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.dedup_by(|a, b| a == b)
+    }
+
+    /// Compacts the vector starting from the chunk at `chunk_idx`, which has been taken out of
+    /// the map as `elements`. The first `kept` elements are kept, the next one is removed, and
+    /// every following element is kept if `keep` returns `true` for it and, if `compare_prev`
+    /// is set, the last element kept before it.
+    ///
+    /// Without `compare_prev`, the chunks before `chunk_idx` are not accessed.
+    fn compact<F>(
+        &mut self,
+        chunk_idx: u32,
+        elements: Vec<T>,
+        kept: usize,
+        compare_prev: bool,
+        mut keep: F,
+    ) where
+        F: FnMut(&mut T, Option<&mut T>) -> bool,
+    {
+        let count = chunk_count::<N>(self.len);
+        let mut elements = elements.into_iter();
+        let mut out: Vec<T> = elements.by_ref().take(kept).collect();
+        drop(elements.next());
+        self.len = chunk_idx * N as u32 + kept as u32;
+
+        let mut out_idx = chunk_idx;
+        let mut next_chunk = chunk_idx + 1;
+        loop {
+            let mut element = match elements.next() {
+                Some(element) => element,
+                None if next_chunk < count => {
+                    let chunk = expect_consistent_state(self.values.remove(next_chunk));
+                    elements = chunk.into_vec().into_iter();
+                    next_chunk += 1;
+                    continue;
+                }
+                None => break,
+            };
+
+            let prev = match out.last_mut() {
+                Some(prev) => Some(prev),
+                // The chunk before was either written by this compaction or accessed mutably by
+                // `dedup_by`, so accessing it mutably does not add a write.
+                None if compare_prev => out_idx
+                    .checked_sub(1)
+                    .and_then(|i| self.values.get_mut(i))
+                    .and_then(|c| c.last_mut()),
+                None => None,
+            };
+            if keep(&mut element, prev) {
+                out.push(element);
+                self.len += 1;
+                if out.len() == N {
+                    // Chunks are only written once all of their elements were taken out, as
+                    // elements are never moved to a later index.
+                    let chunk = core::mem::replace(&mut out, Vec::with_capacity(N));
+                    self.values.set(out_idx, Some(Chunk::from(chunk)));
+                    out_idx += 1;
+                }
+            }
+        }
+
+        // Chunks after the last one written have been taken out, so they are removed.
+        if !out.is_empty() {
+            self.values.set(out_idx, Some(Chunk::from(out)));
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::{ChunkedVector, ERR_INDEX_OUT_OF_BOUNDS};
+use crate::chunk::{chunk_count, Chunk};
+use crate::index_map::IndexMap;
+use crate::storage::StorageBackend;
+use crate::utils::{expect_consistent_state, panic_str};
+
+/// Sorted run of chunks being merged, which takes its chunks out of the map as they are needed.
+struct Run<T> {
+    /// Elements taken out of the run's chunks which have not been merged yet.
+    buf: VecDeque<T>,
+    /// Index of the next chunk of the run to take.
+    next: u32,
+    /// Index of the chunk after the run.
+    end: u32,
+}
+
+impl<T> Run<T>
+where
+    T: BorshSerialize + BorshDeserialize,
+{
+    fn new(start: u32, end: u32) -> Self {
+        Self {
+            buf: VecDeque::new(),
+            next: start,
+            end,
+        }
+    }
+
+    /// Returns the next element of the run, taking its chunk if the buffer is empty.
+    fn front<B, const N: usize>(&mut self, values: &mut IndexMap<Chunk<T, N>, B>) -> Option<&T>
+    where
+        B: StorageBackend,
+    {
+        if self.buf.is_empty() && self.next < self.end {
+            let chunk = expect_consistent_state(values.remove(self.next));
+            self.buf.extend(chunk.into_vec());
+            self.next += 1;
+        }
+        self.buf.front()
+    }
+}
+
+impl<T, const N: usize, B> ChunkedVector<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Sorts the vector, with the same semantics as [`slice::sort`]. This sort is stable.
+    ///
+    /// Each chunk which is not already sorted is first sorted in memory, then sorted runs of
+    /// chunks are merged pairwise, doubling in length each pass. Merges stream through both
+    /// runs, taking a chunk only once the elements taken before are merged, and write the merged
+    /// chunks to a second region of scratch chunks past the end of the vector. The two regions
+    /// swap roles between passes, and the sorted chunks are moved into place if the last pass
+    /// leaves them in the scratch region. A merge thus only holds one chunk of each run and the
+    /// chunk being merged into, while the chunks it loads and writes are kept in the cache as
+    /// limited by [`set_cache_capacity`](Self::set_cache_capacity). Every pass loads and
+    /// rewrites each chunk once, and runs which are already in order relative to each other are
+    /// not rewritten.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<i32, 2> = ChunkedVector::new(b"v");
+    /// vec.extend([5, 4, 1, 3, 2]);
+    ///
+    /// vec.sort();
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 2, 3, 4, 5]);
+    /// ```
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.merge_sort(true, |a, b| a.lt(b))
+    }
+
+    /// Sorts the vector with a comparator function, with the same semantics as
+    /// [`slice::sort_by`]. See [`sort`](Self::sort) for how chunks are loaded and written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<i32> = ChunkedVector::new(b"v");
+    /// vec.extend([5, 4, 1, 3, 2]);
+    ///
+    /// vec.sort_by(|a, b| b.cmp(a));
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[5, 4, 3, 2, 1]);
+    /// ```
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.merge_sort(true, |a, b| compare(a, b) == Ordering::Less)
+    }
+
+    /// Sorts the vector with a key extraction function, with the same semantics as
+    /// [`slice::sort_by_key`]. See [`sort`](Self::sort) for how chunks are loaded and written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVector;
+    ///
+    /// let mut vec: ChunkedVector<i32> = ChunkedVector::new(b"v");
+    /// vec.extend([-5, 4, 1, -3, 2]);
+    ///
+    /// vec.sort_by_key(|k| k.abs());
+    /// assert_eq!(vec.iter().copied().collect::<Vec<_>>(), &[1, 2, -3, 4, -5]);
+    /// ```
+    pub fn sort_by_key<K, F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> K,
+        K: Ord,
+    {
+        self.merge_sort(true, |a, b| f(a).lt(&f(b)))
+    }
+
+    /// Sorts the vector, but might not preserve the order of equal elements, with the same
+    /// semantics as [`slice::sort_unstable`]. Chunks are sorted in memory without allocating,
+    /// and merged the same way as [`sort`](Self::sort).
+    pub fn sort_unstable(&mut self)
+    where
+        T: Ord,
+    {
+        self.merge_sort(false, |a, b| a.lt(b))
+    }
+
+    fn merge_sort<F>(&mut self, stable: bool, mut is_less: F)
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let count = chunk_count::<N>(self.len);
+        for chunk_idx in 0..count {
+            // Chunks which are already sorted are only read.
+            let chunk = expect_consistent_state(self.values.get(chunk_idx));
+            if chunk.windows(2).all(|w| !is_less(&w[1], &w[0])) {
+                continue;
+            }
+            let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+            let compare = |a: &T, b: &T| {
+                if is_less(a, b) {
+                    Ordering::Less
+                } else if is_less(b, a) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            };
+            if stable {
+                chunk.sort_by(compare);
+            } else {
+                chunk.sort_unstable_by(compare);
+            }
+        }
+        if count <= 1 {
+            return;
+        }
+        if count.checked_mul(2).is_none() {
+            panic_str(ERR_INDEX_OUT_OF_BOUNDS);
+        }
+
+        // Whether each run of the current width is in the scratch region.
+        let mut in_scratch = alloc::vec![false; count as usize];
+        let mut width = 1;
+        while width < count {
+            let mut merged = Vec::with_capacity(in_scratch.len().div_ceil(2));
+            for (pair, runs) in (0..).zip(in_scratch.chunks(2)) {
+                let start = pair * 2 * width;
+                let scratch = match *runs {
+                    [left, right] => {
+                        let mid = start + width;
+                        let end = core::cmp::min(mid.saturating_add(width), count);
+                        self.merge_runs(start, mid, end, (left, right), &mut is_less)
+                    }
+                    // Runs without a pair are left where they are.
+                    _ => runs[0],
+                };
+                merged.push(scratch);
+            }
+            in_scratch = merged;
+            width = width.saturating_mul(2);
+        }
+
+        if in_scratch[0] {
+            for chunk_idx in 0..count {
+                self.move_chunk(count + chunk_idx, chunk_idx);
+            }
+        }
+    }
+
+    /// Merges the sorted runs of chunks `start..mid` and `mid..end` into a single run, where
+    /// `in_scratch` is whether each run is in the scratch region past the end of the vector
+    /// rather than at its own indices. Returns whether the merged run is in the scratch region.
+    fn merge_runs<F>(
+        &mut self,
+        start: u32,
+        mid: u32,
+        end: u32,
+        in_scratch: (bool, bool),
+        is_less: &mut F,
+    ) -> bool
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let count = chunk_count::<N>(self.len);
+        let offset = |scratch: bool| if scratch { count } else { 0 };
+        let (left_offset, right_offset) = (offset(in_scratch.0), offset(in_scratch.1));
+
+        let in_order = {
+            // Runs are already in order if the first element of the right run is not less than
+            // the last element of the left run.
+            let left = expect_consistent_state(self.values.get(left_offset + mid - 1));
+            let right = expect_consistent_state(self.values.get(right_offset + mid));
+            !is_less(
+                expect_consistent_state(right.first()),
+                expect_consistent_state(left.last()),
+            )
+        };
+
+        // Merged runs are kept in a single region, so the right run is moved next to the left
+        // one if they are in different regions.
+        if in_scratch.0 != in_scratch.1 {
+            for chunk_idx in mid..end {
+                self.move_chunk(right_offset + chunk_idx, left_offset + chunk_idx);
+            }
+        }
+        if in_order {
+            return in_scratch.0;
+        }
+
+        // Merged chunks are written to the other region, as the chunks of the runs they replace
+        // may not have been taken yet.
+        let out_offset = offset(!in_scratch.0);
+        let mut left = Run::new(left_offset + start, left_offset + mid);
+        let mut right = Run::new(left_offset + mid, left_offset + end);
+        let mut out_idx = out_offset + start;
+        let mut out = Vec::with_capacity(N);
+        loop {
+            let take_right = match (left.front(&mut self.values), right.front(&mut self.values)) {
+                (Some(l), Some(r)) => is_less(r, l),
+                (Some(_), None) => false,
+                (None, Some(_)) => true,
+                (None, None) => break,
+            };
+            let run = if take_right { &mut right } else { &mut left };
+            out.push(expect_consistent_state(run.buf.pop_front()));
+
+            if out.len() == N {
+                self.values.set_vacant(out_idx, Some(Chunk::from(out)));
+                out_idx += 1;
+                out = Vec::with_capacity(N);
+            }
+        }
+        if !out.is_empty() {
+            self.values.set_vacant(out_idx, Some(Chunk::from(out)));
+        }
+        !in_scratch.0
+    }
+
+    /// Moves the chunk at `from` to `to`, which has no chunk, either as it is past the end of
+    /// the vector or as its chunk has been taken.
+    fn move_chunk(&mut self, from: u32, to: u32) {
+        let chunk = self.values.remove(from);
+        self.values.set_vacant(to, chunk);
+    }
+}
@@ -0,0 +1,71 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::iter::{Iter, IterMut};
+use super::{ChunkedVecDeque, ERR_INDEX_OUT_OF_BOUNDS};
+use crate::storage::StorageBackend;
+use crate::utils::panic_str;
+
+impl<'a, T, const N: usize, B> IntoIterator for &'a ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, N, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize, B> IntoIterator for &'a mut ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T, N, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T, const N: usize, B> Extend<T> for ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for item in iter {
+            self.push_back(item)
+        }
+    }
+}
+
+impl<T, const N: usize, B> core::ops::Index<u32> for ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Output = T;
+
+    fn index(&self, index: u32) -> &Self::Output {
+        self.get(index)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS))
+    }
+}
+
+impl<T, const N: usize, B> core::ops::IndexMut<u32> for ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn index_mut(&mut self, index: u32) -> &mut Self::Output {
+        self.get_mut(index)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS))
+    }
+}
@@ -0,0 +1,211 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::{iter::FusedIterator, ops::Range};
+
+use super::{ChunkedVecDeque, ERR_INDEX_OUT_OF_BOUNDS};
+use crate::storage::{DefaultStorage, StorageBackend};
+use crate::utils::panic_str;
+
+/// An iterator over references to each element in the stored deque.
+#[derive(Debug)]
+pub struct Iter<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Underlying deque to iterate through
+    deque: &'a ChunkedVecDeque<T, N, B>,
+    /// Range of indices to iterate.
+    range: Range<u32>,
+}
+
+impl<'a, T, const N: usize, B> Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    pub(super) fn new(deque: &'a ChunkedVecDeque<T, N, B>) -> Self {
+        Self {
+            deque,
+            range: Range {
+                start: 0,
+                end: deque.len(),
+            },
+        }
+    }
+
+    /// Returns number of elements left to iterate.
+    fn remaining(&self) -> usize {
+        self.range.len()
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        <Self as Iterator>::nth(self, 0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth(n)?;
+        Some(
+            self.deque
+                .get(idx)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+        )
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+impl<'a, T, const N: usize, B> DoubleEndedIterator for Iter<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        <Self as DoubleEndedIterator>::nth_back(self, 0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth_back(n)?;
+        Some(
+            self.deque
+                .get(idx)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+        )
+    }
+}
+
+/// An iterator over exclusive references to each element of a stored deque.
+#[derive(Debug)]
+pub struct IterMut<'a, T, const N: usize, B = DefaultStorage>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Mutable reference to deque used to iterate through.
+    deque: &'a mut ChunkedVecDeque<T, N, B>,
+    /// Range of indices to iterate.
+    range: Range<u32>,
+}
+
+impl<'a, T, const N: usize, B> IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Creates a new iterator for the given storage deque.
+    pub(crate) fn new(deque: &'a mut ChunkedVecDeque<T, N, B>) -> Self {
+        let end = deque.len();
+        Self {
+            deque,
+            range: Range { start: 0, end },
+        }
+    }
+
+    /// Returns the amount of remaining elements to yield by the iterator.
+    fn remaining(&self) -> usize {
+        self.range.len()
+    }
+}
+
+impl<'a, T, const N: usize, B> IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn get_mut<'b>(&'b mut self, at: u32) -> Option<&'a mut T> {
+        self.deque.get_mut(at).map(|value| {
+            //* SAFETY: The lifetime can be swapped here because we can assert that the iterator
+            //*         will only give out one mutable reference for every individual item
+            //*         during the iteration, and there is no overlap. This must be checked
+            //*         that no element in this iterator is ever revisited during iteration.
+            unsafe { &mut *(value as *mut T) }
+        })
+    }
+}
+
+impl<'a, T, const N: usize, B> Iterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        <Self as Iterator>::nth(self, 0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth(n)?;
+        Some(
+            self.get_mut(idx)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+        )
+    }
+}
+
+impl<'a, T, const N: usize, B> ExactSizeIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+impl<'a, T, const N: usize, B> FusedIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+}
+
+impl<'a, T, const N: usize, B> DoubleEndedIterator for IterMut<'a, T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        <Self as DoubleEndedIterator>::nth_back(self, 0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let idx = self.range.nth_back(n)?;
+        Some(
+            self.get_mut(idx)
+                .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS)),
+        )
+    }
+}
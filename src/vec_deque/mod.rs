@@ -0,0 +1,631 @@
+//! A double-ended queue with values persisted to storage in chunks and lazily loaded.
+//!
+//! Values in the [`ChunkedVecDeque`] are kept in an in-memory cache and are only persisted on
+//! [`Drop`].
+//!
+//! # Examples
+//!
+//! ```
+//! use near_chunked_collections::ChunkedVecDeque;
+//!
+//! let mut deque: ChunkedVecDeque<u32> = ChunkedVecDeque::new(b"d");
+//! deque.push_back(2);
+//! deque.push_back(3);
+//! deque.push_front(1);
+//!
+//! assert_eq!(deque.iter().copied().collect::<Vec<_>>(), &[1, 2, 3]);
+//! assert_eq!(deque.pop_front(), Some(1));
+//! assert_eq!(deque.pop_back(), Some(3));
+//! ```
+
+mod impls;
+mod iter;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub use self::iter::{Iter, IterMut};
+
+use crate::chunk::Chunk;
+use crate::index_map::IndexMap;
+use crate::storage::{DefaultStorage, IntoStorageKey, StorageBackend};
+use crate::utils::{expect_consistent_state, panic_str};
+
+const ERR_INDEX_OUT_OF_BOUNDS: &str = "Index out of bounds";
+
+/// A double-ended queue implemented with a growable ring of chunks, which stores its content on
+/// the trie. This implementation will load and store values in the underlying storage lazily.
+///
+/// Uses the following map: chunk index -> chunk of up to `N` elements, which is the same layout
+/// as [`ChunkedVector`](crate::ChunkedVector). The position of the front element is tracked as
+/// a chunk index and an offset within that chunk, and chunk indices wrap around, so elements can
+/// be pushed to or popped from either end without shifting any other elements in storage. The
+/// chunks at either end can be partially filled, and a chunk is only removed from storage once
+/// all of its elements have been popped.
+///
+/// This implementation will cache all changes and loads and only updates values that are changed
+/// in storage after it's dropped through it's [`Drop`] implementation. These changes can be updated
+/// in storage before the variable is dropped by using [`ChunkedVecDeque::flush`].
+///
+/// # Examples
+/// ```
+/// use near_chunked_collections::ChunkedVecDeque;
+///
+/// let mut deque: ChunkedVecDeque<u32> = ChunkedVecDeque::new(b"d");
+/// deque.extend([1, 2, 3]);
+/// deque.push_front(0);
+///
+/// assert_eq!(deque.front(), Some(&0));
+/// assert_eq!(deque.back(), Some(&3));
+/// assert_eq!(deque[2], 2);
+///
+/// assert_eq!(deque.pop_front(), Some(0));
+/// assert_eq!(deque.len(), 3);
+/// ```
+pub struct ChunkedVecDeque<T, const N: usize = 5, B = DefaultStorage>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    pub(crate) len: u32,
+    /// Chunk index of the front element.
+    pub(crate) head: u32,
+    /// Position of the front element within the head chunk.
+    pub(crate) offset: u32,
+    pub(crate) values: IndexMap<Chunk<T, N>, B>,
+}
+
+impl<T, const N: usize> ChunkedVecDeque<T, N, DefaultStorage>
+where
+    T: BorshSerialize,
+{
+    /// Create new deque with zero elements. Prefixes storage accesss with the prefix provided.
+    ///
+    /// This prefix can be anything that implements [`IntoStorageKey`]. The prefix is used when
+    /// storing and looking up values in storage to ensure no collisions with other collections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVecDeque;
+    ///
+    /// let mut deque: ChunkedVecDeque<u8> = ChunkedVecDeque::new(b"d");
+    /// ```
+    pub fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self::new_in(prefix, DefaultStorage::default())
+    }
+}
+
+impl<T, const N: usize, B> Drop for ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+impl<T, const N: usize, B> BorshSerialize for ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), borsh::maybestd::io::Error> {
+        BorshSerialize::serialize(&self.len, writer)?;
+        BorshSerialize::serialize(&self.head, writer)?;
+        BorshSerialize::serialize(&self.offset, writer)?;
+        BorshSerialize::serialize(&self.values, writer)?;
+        Ok(())
+    }
+}
+
+impl<T, const N: usize, B> BorshDeserialize for ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend + Default,
+{
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, borsh::maybestd::io::Error> {
+        Ok(Self {
+            len: BorshDeserialize::deserialize(buf)?,
+            head: BorshDeserialize::deserialize(buf)?,
+            offset: BorshDeserialize::deserialize(buf)?,
+            values: BorshDeserialize::deserialize(buf)?,
+        })
+    }
+}
+
+impl<T, const N: usize, B> ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize,
+    B: StorageBackend,
+{
+    /// Returns the number of elements in the deque.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns `true` if the deque contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Create new deque with zero elements, which is persisted to the storage backend provided.
+    /// Prefixes storage accesss with the prefix provided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::storage::InMemoryStorage;
+    /// use near_chunked_collections::ChunkedVecDeque;
+    ///
+    /// let mut deque: ChunkedVecDeque<u8, 5, _> =
+    ///     ChunkedVecDeque::new_in(b"d", InMemoryStorage::new());
+    /// ```
+    pub fn new_in<S>(prefix: S, storage: B) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            len: 0,
+            head: 0,
+            offset: 0,
+            values: IndexMap::new_in(prefix, storage),
+        }
+    }
+
+    /// Returns the chunk index and position within the chunk of the element at `index`.
+    fn locate(&self, index: u32) -> (u32, usize) {
+        let pos = self.offset as usize + index as usize;
+        let chunk = pos / N;
+        // Elements of the head chunk are stored starting from the offset.
+        let chunk_pos = if chunk == 0 {
+            pos - self.offset as usize
+        } else {
+            pos % N
+        };
+        (self.head.wrapping_add(chunk as u32), chunk_pos)
+    }
+
+    /// Returns the number of chunks the elements span.
+    fn chunk_count(&self) -> u32 {
+        (self.offset as usize + self.len as usize).div_ceil(N) as u32
+    }
+
+    fn increment_len(&mut self) {
+        self.len = self
+            .len
+            .checked_add(1)
+            .unwrap_or_else(|| panic_str(ERR_INDEX_OUT_OF_BOUNDS));
+    }
+
+    /// Removes all elements from the deque. This will remove the storage value of every chunk,
+    /// without loading any of them.
+    pub fn clear(&mut self) {
+        for i in 0..self.chunk_count() {
+            self.values.set(self.head.wrapping_add(i), None);
+        }
+        self.len = 0;
+        self.offset = 0;
+    }
+
+    /// Flushes the cache and writes all modified values to storage.
+    ///
+    /// This operation is performed on [`Drop`], but this method can be called to persist
+    /// intermediate writes in cases where [`Drop`] is not called or to identify storage changes.
+    pub fn flush(&mut self) {
+        self.values.flush();
+    }
+}
+
+impl<T, const N: usize, B> ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize,
+    B: StorageBackend,
+{
+    /// Appends an element to the back of the deque.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVecDeque;
+    ///
+    /// let mut deque: ChunkedVecDeque<u8> = ChunkedVecDeque::new(b"d");
+    /// deque.push_back(1);
+    /// deque.push_back(2);
+    /// assert_eq!(deque.back(), Some(&2));
+    /// ```
+    pub fn push_back(&mut self, element: T) {
+        if self.is_empty() {
+            self.values.set(self.head, Some(Chunk::with_first(element)));
+        } else {
+            let (chunk_idx, chunk_pos) = self.locate(self.len);
+            if chunk_pos == 0 {
+                // Back chunk is full, start a new one.
+                self.values.set(chunk_idx, Some(Chunk::with_first(element)));
+            } else {
+                expect_consistent_state(self.values.get_mut(chunk_idx)).push(element);
+            }
+        }
+        self.increment_len();
+    }
+
+    /// Prepends an element to the front of the deque.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new length exceeds `u32::MAX`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVecDeque;
+    ///
+    /// let mut deque: ChunkedVecDeque<u8> = ChunkedVecDeque::new(b"d");
+    /// deque.push_front(1);
+    /// deque.push_front(2);
+    /// assert_eq!(deque.front(), Some(&2));
+    /// ```
+    pub fn push_front(&mut self, element: T) {
+        if self.is_empty() {
+            self.values.set(self.head, Some(Chunk::with_first(element)));
+        } else if self.offset == 0 {
+            // Front chunk is full, start a new one before it which is filled from the back.
+            self.head = self.head.wrapping_sub(1);
+            self.offset = N as u32 - 1;
+            self.values.set(self.head, Some(Chunk::with_first(element)));
+        } else {
+            self.offset -= 1;
+            let chunk = expect_consistent_state(self.values.get_mut(self.head));
+            // There is space at the front of the chunk, so nothing can overflow.
+            let overflow = chunk.insert(0, element);
+            debug_assert!(overflow.is_none());
+        }
+        self.increment_len();
+    }
+
+    /// Removes the first element of the deque and returns it, or [`None`] if it is empty.
+    ///
+    /// The chunk the element is in is only removed from storage once it has no elements left,
+    /// no other elements are moved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVecDeque;
+    ///
+    /// let mut deque: ChunkedVecDeque<u8> = ChunkedVecDeque::new(b"d");
+    /// deque.extend([1, 2]);
+    ///
+    /// assert_eq!(deque.pop_front(), Some(1));
+    /// assert_eq!(deque.pop_front(), Some(2));
+    /// assert_eq!(deque.pop_front(), None);
+    /// ```
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let chunk = expect_consistent_state(self.values.get_mut(self.head));
+        let element = chunk.remove(0);
+        if chunk.is_empty() {
+            // Front chunk is fully consumed, move on to the next one.
+            self.values.set(self.head, None);
+            self.head = self.head.wrapping_add(1);
+            self.offset = 0;
+        } else {
+            self.offset += 1;
+        }
+        self.len -= 1;
+        Some(element)
+    }
+
+    /// Removes the last element of the deque and returns it, or [`None`] if it is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVecDeque;
+    ///
+    /// let mut deque: ChunkedVecDeque<u8> = ChunkedVecDeque::new(b"d");
+    /// deque.extend([1, 2]);
+    ///
+    /// assert_eq!(deque.pop_back(), Some(2));
+    /// assert_eq!(deque.pop_back(), Some(1));
+    /// assert_eq!(deque.pop_back(), None);
+    /// ```
+    pub fn pop_back(&mut self) -> Option<T> {
+        let new_len = self.len.checked_sub(1)?;
+
+        let (chunk_idx, _) = self.locate(new_len);
+        let chunk = expect_consistent_state(self.values.get_mut(chunk_idx));
+        let element = chunk.pop();
+        if chunk.is_empty() {
+            self.values.set(chunk_idx, None);
+        }
+        self.len = new_len;
+        if self.is_empty() {
+            self.offset = 0;
+        }
+        element
+    }
+
+    /// Returns the element by index or `None` if it is not present. Index `0` is the front of the
+    /// deque.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVecDeque;
+    ///
+    /// let mut deque: ChunkedVecDeque<u8> = ChunkedVecDeque::new(b"d");
+    /// deque.extend([2, 3]);
+    /// deque.push_front(1);
+    ///
+    /// assert_eq!(deque.get(0), Some(&1));
+    /// assert_eq!(deque.get(2), Some(&3));
+    /// assert_eq!(deque.get(3), None);
+    /// ```
+    pub fn get(&self, index: u32) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+
+        let (chunk_idx, chunk_pos) = self.locate(index);
+        self.values
+            .get(chunk_idx)
+            .and_then(|chunk| chunk.get(chunk_pos))
+    }
+
+    /// Returns a mutable reference to the element at the `index` provided.
+    pub fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+
+        let (chunk_idx, chunk_pos) = self.locate(index);
+        self.values
+            .get_mut(chunk_idx)
+            .and_then(|chunk| chunk.get_mut(chunk_pos))
+    }
+
+    /// Returns a reference to the front element, or `None` if the deque is empty.
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    /// Returns a mutable reference to the front element, or `None` if the deque is empty.
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.get_mut(0)
+    }
+
+    /// Returns a reference to the back element, or `None` if the deque is empty.
+    pub fn back(&self) -> Option<&T> {
+        self.get(self.len.checked_sub(1)?)
+    }
+
+    /// Returns a mutable reference to the back element, or `None` if the deque is empty.
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.get_mut(self.len.checked_sub(1)?)
+    }
+
+    /// Returns a front-to-back iterator over the deque. This iterator will lazily load any values
+    /// iterated over from storage.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use near_chunked_collections::ChunkedVecDeque;
+    ///
+    /// let mut deque: ChunkedVecDeque<u8> = ChunkedVecDeque::new(b"d");
+    /// deque.extend([2, 3]);
+    /// deque.push_front(1);
+    ///
+    /// let mut iter = deque.iter();
+    /// assert_eq!(iter.next(), Some(&1));
+    /// assert_eq!(iter.next_back(), Some(&3));
+    /// assert_eq!(iter.next(), Some(&2));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, T, N, B> {
+        Iter::new(self)
+    }
+
+    /// Returns a front-to-back iterator that allows modifying each value. This iterator will
+    /// lazily load any values iterated over from storage.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, N, B> {
+        IterMut::new(self)
+    }
+}
+
+impl<T, const N: usize, B> fmt::Debug for ChunkedVecDeque<T, N, B>
+where
+    T: BorshSerialize + BorshDeserialize + fmt::Debug,
+    B: StorageBackend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if cfg!(feature = "expensive-debug") {
+            fmt::Debug::fmt(&self.iter().collect::<Vec<_>>(), f)
+        } else {
+            f.debug_struct("VecDeque")
+                .field("len", &self.len)
+                .field("head", &self.head)
+                .field("offset", &self.offset)
+                .field("prefix", &self.values.prefix)
+                .finish()
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use rand::{Rng, RngCore, SeedableRng};
+    use std::collections::VecDeque;
+
+    use super::ChunkedVecDeque;
+    use crate::utils::test_iters;
+    use near_sdk::test_utils::test_env::setup_free;
+
+    #[test]
+    fn test_push_pop_both_ends() {
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut deque = ChunkedVecDeque::<_>::new(b"d");
+        let mut baseline = VecDeque::new();
+        for _ in 0..test_iters(500) {
+            let value = rng.gen::<u64>();
+            if rng.gen::<bool>() {
+                deque.push_back(value);
+                baseline.push_back(value);
+            } else {
+                deque.push_front(value);
+                baseline.push_front(value);
+            }
+        }
+        assert!(Iterator::eq(deque.iter(), baseline.iter()));
+        assert!(Iterator::eq(deque.iter().rev(), baseline.iter().rev()));
+        for _ in 0..=test_iters(500) {
+            if rng.gen::<bool>() {
+                assert_eq!(deque.pop_back(), baseline.pop_back());
+            } else {
+                assert_eq!(deque.pop_front(), baseline.pop_front());
+            }
+        }
+        deque.flush();
+        near_sdk::mock::with_mocked_blockchain(|m| assert!(m.take_storage().is_empty()));
+    }
+
+    #[test]
+    fn test_pop_front_removes_consumed_chunks() {
+        let mut deque = ChunkedVecDeque::<u32, 5>::new(b"d");
+        deque.extend(0..12);
+        deque.flush();
+
+        let chunk_key = |index: u32| [&b"d"[..], &index.to_le_bytes()].concat();
+        for i in 0..4 {
+            assert_eq!(deque.pop_front(), Some(i));
+        }
+        deque.flush();
+        // First chunk still has an element, nothing was shifted into it.
+        let stored = near_sdk::env::storage_read(&chunk_key(0)).unwrap();
+        assert_eq!(stored, vec![4u32].try_to_vec().unwrap());
+
+        assert_eq!(deque.pop_front(), Some(4));
+        deque.flush();
+        assert!(!near_sdk::env::storage_has_key(&chunk_key(0)));
+        assert!(near_sdk::env::storage_has_key(&chunk_key(1)));
+        assert!(Iterator::eq(deque.iter().copied(), 5..12));
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut deque = ChunkedVecDeque::<u32>::new(b"d");
+        deque.extend(0..10);
+        for i in 0..3 {
+            deque.push_front(100 + i);
+        }
+        for value in deque.iter_mut() {
+            *value += 1;
+        }
+        let mut baseline: VecDeque<u32> = (0..10).collect();
+        for i in 0..3 {
+            baseline.push_front(100 + i);
+        }
+        baseline.iter_mut().for_each(|v| *v += 1);
+        assert!(Iterator::eq(deque.iter(), baseline.iter()));
+    }
+
+    #[derive(Arbitrary, Debug)]
+    enum Op {
+        PushBack(u8),
+        PushFront(u8),
+        PopBack,
+        PopFront,
+        Flush,
+        Reset,
+        Get(u32),
+        Set(u32, u8),
+        Clear,
+    }
+
+    #[test]
+    fn arbitrary() {
+        setup_free();
+
+        let mut rng = rand_xorshift::XorShiftRng::seed_from_u64(0);
+        let mut buf = vec![0; 4096];
+        // Miri is too slow to go through every run.
+        let runs = if cfg!(miri) { 4 } else { 1024 };
+        for _ in 0..runs {
+            // Clear storage in-between runs
+            near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+            rng.fill_bytes(&mut buf);
+
+            let mut sd = ChunkedVecDeque::<_, 3>::new(b"d");
+            let mut md = VecDeque::new();
+            let u = Unstructured::new(&buf);
+            if let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) {
+                for op in ops {
+                    match op {
+                        Op::PushBack(v) => {
+                            sd.push_back(v);
+                            md.push_back(v);
+                        }
+                        Op::PushFront(v) => {
+                            sd.push_front(v);
+                            md.push_front(v);
+                        }
+                        Op::PopBack => {
+                            assert_eq!(sd.pop_back(), md.pop_back());
+                        }
+                        Op::PopFront => {
+                            assert_eq!(sd.pop_front(), md.pop_front());
+                        }
+                        Op::Flush => {
+                            sd.flush();
+                        }
+                        Op::Reset => {
+                            let serialized = sd.try_to_vec().unwrap();
+                            sd = ChunkedVecDeque::deserialize(&mut serialized.as_slice()).unwrap();
+                        }
+                        Op::Get(k) => {
+                            assert_eq!(sd.get(k), md.get(k as usize));
+                        }
+                        Op::Set(k, v) => {
+                            if let Some(value) = sd.get_mut(k) {
+                                *value = v;
+                            }
+                            if let Some(value) = md.get_mut(k as usize) {
+                                *value = v;
+                            }
+                        }
+                        Op::Clear => {
+                            sd.clear();
+                            md.clear();
+                        }
+                    }
+                    assert_eq!(sd.len() as usize, md.len());
+                    assert_eq!(sd.front(), md.front());
+                    assert_eq!(sd.back(), md.back());
+                }
+            }
+
+            // After all operations, compare both deques
+            assert!(Iterator::eq(sd.iter(), md.iter()));
+        }
+    }
+}